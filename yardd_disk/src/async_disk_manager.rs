@@ -0,0 +1,157 @@
+use std::{
+    error::Error,
+    io::{ErrorKind, SeekFrom},
+};
+
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+};
+
+use crate::{
+    disk_manager::{check_full_read, DiskManager},
+    error::DiskError,
+    page::{PageId, PAGE_SIZE_BYTES},
+};
+
+pub type AsyncResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Reads and writes pages with tokio's file I/O so a miss doesn't block the
+/// runtime's thread. Pages are allocated and located the same way as in
+/// `DiskManager`, which this wraps. Loads only need `&self`, so many can be
+/// in flight at once.
+pub struct AsyncDiskManager {
+    disk_manager: DiskManager,
+}
+
+impl AsyncDiskManager {
+    pub fn new(base_directory: &str) -> Self {
+        AsyncDiskManager {
+            disk_manager: DiskManager::new(base_directory),
+        }
+    }
+
+    // Allocation only touches the end of a file once, so it stays blocking
+    pub fn allocate_pages(&mut self, pages: usize, file_name: &str) -> AsyncResult<Vec<PageId>> {
+        Ok(self.disk_manager.allocate_pages(pages, file_name)?)
+    }
+
+    pub async fn load_page(&self, page_id: PageId) -> AsyncResult<Vec<u8>> {
+        let mut buffer = vec![0; PAGE_SIZE_BYTES as usize];
+        self.load_page_into(page_id, &mut buffer).await?;
+
+        Ok(buffer)
+    }
+
+    // Same truncation rules as DiskManager::load_page_into
+    pub async fn load_page_into(&self, page_id: PageId, buffer: &mut [u8]) -> AsyncResult<()> {
+        let Some((file_path, offset)) = self.disk_manager.read_location(page_id, buffer)? else {
+            return Ok(());
+        };
+
+        let mut file = File::open(file_path).await?;
+        file.seek(SeekFrom::Start(offset)).await?;
+
+        if let Err(error) = file.read_exact(buffer).await {
+            if error.kind() != ErrorKind::UnexpectedEof {
+                return Err(error.into());
+            }
+
+            let got = file.metadata().await?.len().saturating_sub(offset);
+            check_full_read(page_id, buffer, got as usize)?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn save_page(&mut self, page_id: PageId, data: &[u8]) -> AsyncResult<()> {
+        if self.disk_manager.is_read_only() {
+            return Err(DiskError::ReadOnly.into());
+        }
+
+        let (file_path, offset, _) = self.disk_manager.locate(page_id)?;
+
+        let mut file = File::options().write(true).open(file_path).await?;
+        file.seek(SeekFrom::Start(offset)).await?;
+        file.write_all(data).await?;
+        file.flush().await?;
+
+        self.disk_manager.mark_written(page_id)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod async_disk_manager_tests {
+    use std::fs::{remove_dir_all, OpenOptions};
+
+    use super::AsyncDiskManager;
+    use crate::error::DiskError;
+
+    #[tokio::test]
+    pub async fn concurrent_loads() {
+        let base_dir = "./test44";
+        let _ = remove_dir_all(base_dir);
+
+        let mut disk_manager = AsyncDiskManager::new(base_dir);
+        let page_ids = disk_manager.allocate_pages(3, "pages.db").unwrap();
+
+        for page_id in page_ids.iter() {
+            disk_manager
+                .save_page(*page_id, &[*page_id as u8 + 1; 1024])
+                .await
+                .unwrap();
+        }
+
+        let (first, second, third) = tokio::join!(
+            disk_manager.load_page(page_ids[0]),
+            disk_manager.load_page(page_ids[1]),
+            disk_manager.load_page(page_ids[2]),
+        );
+
+        assert_eq!(vec![1; 1024], first.unwrap());
+        assert_eq!(vec![2; 1024], second.unwrap());
+        assert_eq!(vec![3; 1024], third.unwrap());
+
+        let _ = remove_dir_all(base_dir);
+    }
+
+    #[tokio::test]
+    pub async fn loads_follow_the_sync_truncation_rules() {
+        let base_dir = "./test78";
+        let _ = remove_dir_all(base_dir);
+
+        let mut disk_manager = AsyncDiskManager::new(base_dir);
+        let page_ids = disk_manager.allocate_pages(3, "pages.db").unwrap();
+        disk_manager
+            .save_page(page_ids[1], &[9; 1024])
+            .await
+            .unwrap();
+
+        OpenOptions::new()
+            .write(true)
+            .open("./test78/pages.db")
+            .unwrap()
+            .set_len(1024 + 512)
+            .unwrap();
+
+        // Never saved, so it reads as zeroes even though the file doesn't reach it
+        assert_eq!(
+            vec![0; 1024],
+            disk_manager.load_page(page_ids[2]).await.unwrap()
+        );
+
+        let err = disk_manager.load_page(page_ids[1]).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<DiskError>(),
+            Some(DiskError::Truncated {
+                expected: 1024,
+                got: 512,
+                ..
+            })
+        ));
+
+        let _ = remove_dir_all(base_dir);
+    }
+}
@@ -0,0 +1,53 @@
+/// Size of a bloom filter over a page's keys, stored in the slot header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BloomFilterParams {
+    pub n_bytes: u16,
+    pub n_hashes: u16,
+}
+
+impl BloomFilterParams {
+    /// The smallest filter that keeps the false positive rate at or below
+    /// `false_positive_rate` once `expected_keys` keys have been added.
+    pub fn for_rate(expected_keys: usize, false_positive_rate: f64) -> Self {
+        assert!(
+            false_positive_rate > 0.0 && false_positive_rate < 1.0,
+            "False positive rate must be between 0 and 1"
+        );
+
+        let expected_keys = expected_keys.max(1) as f64;
+        let ln_2 = std::f64::consts::LN_2;
+        let n_bits = (-expected_keys * false_positive_rate.ln() / (ln_2 * ln_2)).ceil();
+        let n_hashes = (n_bits / expected_keys * ln_2).round();
+
+        BloomFilterParams {
+            n_bytes: (n_bits / 8.0).ceil().clamp(1.0, u16::MAX as f64) as u16,
+            n_hashes: n_hashes.clamp(1.0, 16.0) as u16,
+        }
+    }
+}
+
+pub fn insert(bits: &mut [u8], n_hashes: u16, key_bytes: &[u8]) {
+    for bit in bit_positions(bits.len(), n_hashes, key_bytes) {
+        bits[bit / 8] |= 1 << (bit % 8);
+    }
+}
+
+/// False means the key was never inserted. True only means it may have been.
+pub fn may_contain(bits: &[u8], n_hashes: u16, key_bytes: &[u8]) -> bool {
+    bit_positions(bits.len(), n_hashes, key_bytes).all(|bit| bits[bit / 8] & (1 << (bit % 8)) != 0)
+}
+
+// Double hashing: the i-th position is h1 + i * h2, from one 64 bit FNV-1a
+// hash. FNV mixes short keys poorly, so its result goes through a finalizer.
+fn bit_positions(n_bytes: usize, n_hashes: u16, key_bytes: &[u8]) -> impl Iterator<Item = usize> {
+    let hash = key_bytes.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    });
+    let hash = (hash ^ (hash >> 33)).wrapping_mul(0xff51afd7ed558ccd);
+    let hash = (hash ^ (hash >> 33)).wrapping_mul(0xc4ceb9fe1a85ec53);
+    let hash = hash ^ (hash >> 33);
+    let (h1, h2) = (hash & 0xFFFF_FFFF, (hash >> 32) | 1);
+    let n_bits = (n_bytes * 8) as u64;
+
+    (0..n_hashes as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % n_bits) as usize)
+}
@@ -0,0 +1,3335 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+    marker::PhantomData,
+    mem::size_of,
+    ops::Bound,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+};
+
+use crate::{
+    cursor::{Continuation, Cursor, TreeIter},
+    data_page::DataPageMut,
+    disk_btree::{
+        IndexPage, IndexPageMut, IndexPageRead, IndexPageReadSized, KeyEntry, SplitPolicy,
+        SplitResult, MAX_ENTRY_BYTES, TUPLE_HEADER_SIZE,
+    },
+    error::{DuplicateKey, YarddError},
+    overflow::{
+        free_overflow_chain, overwrite_overflow_chain_start, read_overflow_chain,
+        write_overflow_chain,
+    },
+    page::{DbColumn, Page, PageId, PageType, SlotIndex, NULL_PAGE_ID},
+    page_manager::{PagePointer, SharedPageManager},
+    serialization_helpers::{read_u16, read_u64, write_u16, write_u64},
+};
+
+// Longer keys are moved to overflow pages, leaving a prefix in the leaf
+pub const MAX_INLINE_KEY_BYTES: usize = 128;
+
+// Marks a leaf entry whose page id is the start of an overflow chain holding
+// the full key and the real tuple pointer
+pub const OVERFLOW_SLOT_INDEX: SlotIndex = SlotIndex::MAX;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct TreeStats {
+    pub height: usize, // A tree that's a single leaf has height 1
+    pub internal_pages: usize,
+    pub leaf_pages: usize,
+    pub total_keys: usize, // Live entries, tombstones aren't counted
+}
+
+/// The pages a search went through, from the root down to the leaf.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DescentPath {
+    page_ids: Vec<PageId>,
+}
+
+impl DescentPath {
+    pub fn page_ids(&self) -> &[PageId] {
+        &self.page_ids
+    }
+
+    pub fn leaf_page_id(&self) -> Option<PageId> {
+        self.page_ids.last().copied()
+    }
+}
+
+/// A slice of the key space returned by `BTree::partition_scan`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeafRange<KeyType> {
+    pub start: Bound<KeyType>,
+    pub end: Bound<KeyType>,
+}
+
+impl<KeyType: DbColumn> LeafRange<KeyType> {
+    pub fn contains(&self, key: &KeyType) -> bool {
+        let after_start = match &self.start {
+            Bound::Included(start) => key.cmp_key(start).is_ge(),
+            Bound::Excluded(start) => key.cmp_key(start).is_gt(),
+            Bound::Unbounded => true,
+        };
+        let before_end = match &self.end {
+            Bound::Included(end) => key.cmp_key(end).is_le(),
+            Bound::Excluded(end) => key.cmp_key(end).is_lt(),
+            Bound::Unbounded => true,
+        };
+
+        after_start && before_end
+    }
+}
+
+/// A tuple's place in a data file, as its page id and slot.
+pub type TuplePointer = (PageId, SlotIndex);
+
+/// An index over a data file, so compacting the file can point it at the
+/// tuples' new places.
+pub trait TupleIndex {
+    /// Purges tombstoned entries and returns the tuples the live ones point to.
+    fn tuple_pointers(&mut self) -> Result<Vec<TuplePointer>, YarddError>;
+
+    /// Points every entry whose tuple is a key of `moves` at the tuple's new place.
+    fn move_tuples(
+        &mut self,
+        moves: &HashMap<TuplePointer, TuplePointer>,
+    ) -> Result<(), YarddError>;
+}
+
+// Called with every split once the pending entry has been placed
+pub type SplitTrace<KeyType> = Box<dyn Fn(&SplitResult<KeyType>) + Send + Sync>;
+
+/// A B+ tree of index pages living in the buffer pool.
+///
+/// Latch protocol: locks are always acquired top-down, starting with the
+/// `root_page_id` lock, then the root page, then one child per level. A
+/// child's latch is acquired before its parent's latch is released
+/// ("latch coupling"), so a reader never observes a node that its parent no
+/// longer points to. Writers take write latches on the same path in the same
+/// order, so readers and writers can't deadlock with each other.
+///
+/// An insert first descends releasing each parent as soon as the child is
+/// latched. If the leaf turns out to be full it starts over, this time
+/// holding write latches on the root pointer and the whole path so the split
+/// can be absorbed by the ancestors. When a leaf splits, the new right
+/// sibling's neighbour is latched after the leaf, so sibling latches are
+/// always taken left to right.
+///
+/// The page manager's mutex is only held while fetching a page pointer and
+/// never while waiting on a page latch.
+pub struct BTree<KeyType>
+where
+    KeyType: DbColumn,
+{
+    page_manager: SharedPageManager,
+    root_page_id: RwLock<PageId>,
+    split_policy: SplitPolicy,
+    name: Option<String>, // Catalog entry kept up to date with the root
+    key_count: AtomicU64, // Counted when the tree is opened, then kept up to date
+    split_trace: Option<SplitTrace<KeyType>>,
+    phantom: PhantomData<KeyType>,
+}
+
+impl<KeyType> BTree<KeyType>
+where
+    KeyType: DbColumn,
+{
+    pub fn new(page_manager: SharedPageManager) -> Result<Self, YarddError> {
+        let root = page_manager.lock().unwrap().next_free_page()?;
+
+        let root_page_id = {
+            let mut page = root.write().unwrap();
+            IndexPageMut::<KeyType>::init_page(PageType::IndexLeaf, NULL_PAGE_ID, &mut page);
+            page.page_id
+        };
+
+        Self::open(page_manager, root_page_id)
+    }
+
+    /// Opens the tree rooted at `root_page_id`. The entry count behind `len`
+    /// isn't stored on disk, so opening walks every leaf to count the entries,
+    /// which is O(n) in the size of the tree.
+    pub fn open(page_manager: SharedPageManager, root_page_id: PageId) -> Result<Self, YarddError> {
+        let tree = BTree {
+            page_manager,
+            root_page_id: RwLock::new(root_page_id),
+            split_policy: SplitPolicy::Even,
+            name: None,
+            key_count: AtomicU64::new(0),
+            split_trace: None,
+            phantom: PhantomData,
+        };
+
+        tree.key_count
+            .store(tree.count_entries()?, Ordering::Relaxed);
+
+        Ok(tree)
+    }
+
+    /// Creates an empty tree with a leaf for each range between `boundaries`,
+    /// which must be ascending, so concurrent writers to different ranges
+    /// start out on different leaves. Every leaf hangs off the root, so the
+    /// boundaries have to fit in a single internal node.
+    pub fn presplit(
+        page_manager: SharedPageManager,
+        boundaries: &[KeyType],
+    ) -> Result<Self, YarddError> {
+        assert!(
+            boundaries
+                .windows(2)
+                .all(|pair| pair[0].cmp_key(&pair[1]).is_lt()),
+            "Boundaries must be strictly ascending"
+        );
+
+        let Some(first_boundary) = boundaries.first() else {
+            return Self::new(page_manager);
+        };
+
+        let root = page_manager.lock().unwrap().next_free_page()?;
+        let mut root = root.write().unwrap();
+        let root_page_id = root.page_id;
+        let mut root_node =
+            IndexPageMut::<KeyType>::init_page(PageType::IndexNode, NULL_PAGE_ID, &mut root);
+
+        // Keys below every boundary go to the first leaf whatever its separator is
+        let leaves: Vec<PagePointer> = std::iter::once(first_boundary)
+            .chain(boundaries)
+            .map(|key| {
+                let leaf = page_manager.lock().unwrap().next_free_page()?;
+                let leaf_page_id = leaf.read().unwrap().page_id;
+
+                IndexPageMut::<KeyType>::init_page(
+                    PageType::IndexLeaf,
+                    root_page_id,
+                    &mut leaf.write().unwrap(),
+                );
+                root_node
+                    .append_key(KeyEntry::internal(Self::stored_key(key), leaf_page_id))
+                    .expect("Too many boundaries for one root");
+
+                Ok(leaf)
+            })
+            .collect::<Result<_, YarddError>>()?;
+
+        for pair in leaves.windows(2) {
+            let (mut left, mut right) = (pair[0].write().unwrap(), pair[1].write().unwrap());
+            left.as_index_node_mut::<KeyType>()
+                .write_next_leaf_page_id(right.page_id);
+            right
+                .as_index_node_mut::<KeyType>()
+                .write_prev_leaf_page_id(left.page_id);
+        }
+
+        drop(root);
+        Self::open(page_manager, root_page_id)
+    }
+
+    /// Creates a tree whose root is recorded in the catalog under `name`.
+    pub fn new_named(page_manager: SharedPageManager, name: &str) -> Result<Self, YarddError> {
+        let mut tree = Self::new(page_manager)?;
+        tree.name = Some(name.to_string());
+        tree.record_root(tree.root_page_id())?;
+
+        Ok(tree)
+    }
+
+    /// Opens the tree recorded in the catalog under `name`, if there is one.
+    pub fn open_named(
+        page_manager: SharedPageManager,
+        name: &str,
+    ) -> Result<Option<Self>, YarddError> {
+        let Some(root_page_id) = page_manager.lock().unwrap().get_root(name) else {
+            return Ok(None);
+        };
+
+        let mut tree = Self::open(page_manager, root_page_id)?;
+        tree.name = Some(name.to_string());
+
+        Ok(Some(tree))
+    }
+
+    fn record_root(&self, root_page_id: PageId) -> Result<(), YarddError> {
+        match &self.name {
+            Some(name) => self
+                .page_manager
+                .lock()
+                .unwrap()
+                .set_root(name, root_page_id),
+            None => Ok(()),
+        }
+    }
+
+    pub fn set_split_policy(&mut self, split_policy: SplitPolicy) {
+        self.split_policy = split_policy;
+    }
+
+    /// Reports every node split to `trace`, e.g. for logging skewed splits.
+    pub fn set_split_trace(&mut self, trace: Option<SplitTrace<KeyType>>) {
+        self.split_trace = trace;
+    }
+
+    pub fn root_page_id(&self) -> PageId {
+        *self.root_page_id.read().unwrap()
+    }
+
+    pub(crate) fn fetch_page(&self, page_id: PageId) -> Result<PagePointer, YarddError> {
+        self.page_manager.lock().unwrap().find_page(page_id)
+    }
+
+    // Descends to a leaf with read latch coupling, picking a child at each internal node
+    fn with_leaf<T, C, L>(&self, choose_child: C, at_leaf: L) -> Result<T, YarddError>
+    where
+        C: Fn(&IndexPage<KeyType>) -> PageId,
+        L: FnOnce(&Page) -> T,
+    {
+        let root_page_id = self.root_page_id.read().unwrap();
+        let root = self.fetch_page(*root_page_id)?;
+        let root_guard = root.read().unwrap();
+        drop(root_page_id);
+
+        self.with_leaf_from(root_guard, &choose_child, at_leaf)
+    }
+
+    fn with_leaf_from<T, C, L>(
+        &self,
+        guard: RwLockReadGuard<Page>,
+        choose_child: &C,
+        at_leaf: L,
+    ) -> Result<T, YarddError>
+    where
+        C: Fn(&IndexPage<KeyType>) -> PageId,
+        L: FnOnce(&Page) -> T,
+    {
+        let child_page_id = {
+            let node = guard.as_index_node::<KeyType>();
+            if node.is_leaf() {
+                return Ok(at_leaf(&guard));
+            }
+
+            choose_child(&node)
+        };
+
+        let child = self.fetch_page(child_page_id)?;
+        let child_guard = child.read().unwrap();
+        drop(guard);
+
+        self.with_leaf_from(child_guard, choose_child, at_leaf)
+    }
+
+    /// Finds a live entry for `key`. Fails if a page on the way can't be read.
+    pub fn search(&self, key: &KeyType) -> Result<Option<KeyEntry<KeyType>>, YarddError> {
+        Ok(self.search_with_path(key)?.0)
+    }
+
+    /// Like `search`, but also returns the pages visited on the way down, so
+    /// a key that turned out to be missing can be inserted with `insert_at_path`.
+    pub fn search_with_path(
+        &self,
+        key: &KeyType,
+    ) -> Result<(Option<KeyEntry<KeyType>>, DescentPath), YarddError> {
+        let stored_key = Self::stored_key(key);
+        let page_ids = RefCell::new(vec![]);
+
+        let candidates = self.with_leaf(
+            |node| {
+                let mut page_ids = page_ids.borrow_mut();
+                if page_ids.is_empty() {
+                    page_ids.push(node.inner_page().page_id);
+                }
+
+                let child_page_id = node.find_child(&stored_key);
+                page_ids.push(child_page_id);
+                child_page_id
+            },
+            |leaf| {
+                let mut page_ids = page_ids.borrow_mut();
+                if page_ids.is_empty() {
+                    page_ids.push(leaf.page_id);
+                }
+
+                leaf.as_index_node::<KeyType>().find_all(&stored_key)
+            },
+        )?;
+
+        // Overflow chains are read after the leaf latch has been released
+        let mut found = None;
+        for entry in candidates {
+            let entry = self.load_large_key(entry)?;
+            if entry.key().cmp_key(key).is_eq() {
+                found = Some(entry);
+                break;
+            }
+        }
+
+        let path = DescentPath {
+            page_ids: page_ids.into_inner(),
+        };
+
+        Ok((found, path))
+    }
+
+    /// Whether the tree has a live entry for `key`, reading only keys where possible.
+    pub fn contains(&self, key: &KeyType) -> Result<bool, YarddError> {
+        let stored_key = Self::stored_key(key);
+
+        // A truncated key only tells us a prefix matched
+        if stored_key.cmp_key(key).is_ne() {
+            return Ok(self.search(key)?.is_some());
+        }
+
+        self.with_leaf(
+            |node| node.find_child(key),
+            |leaf| leaf.as_index_node::<KeyType>().contains_key(key),
+        )
+    }
+
+    // The key as it appears in the index pages
+    pub(crate) fn stored_key(key: &KeyType) -> KeyType {
+        if key.len() <= MAX_INLINE_KEY_BYTES {
+            return key.clone();
+        }
+
+        key.truncated(MAX_INLINE_KEY_BYTES)
+            .unwrap_or_else(|| key.clone())
+    }
+
+    // Moves a key too large for an index page into an overflow chain. The leaf
+    // keeps a prefix of the key so the entry still sorts near its full key.
+    fn store_large_key(&self, entry: KeyEntry<KeyType>) -> Result<KeyEntry<KeyType>, YarddError> {
+        let stored_key = Self::stored_key(entry.key());
+        if stored_key.cmp_key(entry.key()).is_eq() {
+            return Ok(entry);
+        }
+
+        let slot_index = entry.slot_index().expect("Only leaf entries are inserted");
+
+        let mut bytes = vec![0; TUPLE_HEADER_SIZE];
+        let cursor = write_u64(&mut bytes, 0, entry.page_id());
+        write_u16(&mut bytes, cursor, slot_index);
+        bytes.extend(entry.key().to_bytes());
+
+        let first_page_id = write_overflow_chain(&self.page_manager, &bytes)?;
+        Ok(KeyEntry::leaf(
+            stored_key,
+            first_page_id,
+            OVERFLOW_SLOT_INDEX,
+        ))
+    }
+
+    // Reads back the full entry behind an overflow entry
+    pub(crate) fn load_large_key(
+        &self,
+        entry: KeyEntry<KeyType>,
+    ) -> Result<KeyEntry<KeyType>, YarddError> {
+        if entry.slot_index() != Some(OVERFLOW_SLOT_INDEX) {
+            return Ok(entry);
+        }
+
+        let bytes = read_overflow_chain(&self.page_manager, entry.page_id())?;
+        let page_id = read_u64(&bytes, 0);
+        let slot_index = read_u16(&bytes, size_of::<PageId>());
+
+        Ok(KeyEntry::leaf(
+            KeyType::from_bytes(&bytes, TUPLE_HEADER_SIZE),
+            page_id,
+            slot_index,
+        ))
+    }
+
+    // The leaf holding the smallest keys, where an ordered scan starts
+    pub fn first_leaf(&self) -> Result<PageId, YarddError> {
+        self.with_leaf(|node| node.read_key_node(0).page_id(), |leaf| leaf.page_id)
+    }
+
+    pub(crate) fn last_leaf(&self) -> Result<PageId, YarddError> {
+        self.with_leaf(
+            |node| node.read_key_node(node.read_n_slots() - 1).page_id(),
+            |leaf| leaf.page_id,
+        )
+    }
+
+    // The leftmost leaf that may hold `key`. Equal keys can straddle a
+    // separator, so this can be left of the leaf search would pick.
+    pub(crate) fn first_leaf_for_key(&self, key: &KeyType) -> Result<PageId, YarddError> {
+        self.with_leaf(
+            |node| {
+                let (first, _) = Self::child_range_for_key(node, key);
+                node.read_key_node(first).page_id()
+            },
+            |leaf| leaf.page_id,
+        )
+    }
+
+    /// Scans from `start` to the end of the tree, collecting the entries the
+    /// predicate accepts.
+    pub fn filter<F>(
+        &mut self,
+        start: Bound<KeyType>,
+        pred: F,
+    ) -> Result<Vec<KeyEntry<KeyType>>, YarddError>
+    where
+        F: Fn(&KeyEntry<KeyType>) -> bool,
+    {
+        self.filter_range(start, Bound::Unbounded, pred)
+    }
+
+    /// Every live entry in key order. See `TreeIter`.
+    pub fn iter(&self) -> Result<TreeIter<'_, KeyType>, YarddError> {
+        TreeIter::new(self)
+    }
+
+    /// Resumes a scan saved with `Cursor::continuation`, starting at the saved
+    /// leaf and slot and following sibling links from there.
+    pub fn scan_from_leaf(
+        &self,
+        continuation: Continuation<KeyType>,
+    ) -> Result<Cursor<'_, KeyType>, YarddError> {
+        Cursor::resume(self, continuation)
+    }
+
+    /// Like `filter` but stops at the upper bound instead of scanning to the end.
+    pub fn filter_range<F>(
+        &mut self,
+        start: Bound<KeyType>,
+        end: Bound<KeyType>,
+        pred: F,
+    ) -> Result<Vec<KeyEntry<KeyType>>, YarddError>
+    where
+        F: Fn(&KeyEntry<KeyType>) -> bool,
+    {
+        let mut entries = vec![];
+        for entry in self.scan_range(LeafRange { start, end })? {
+            let entry = entry?;
+            if pred(&entry) {
+                entries.push(entry);
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Iterates over the entries in `range` in key order. Only takes read
+    /// latches, so several ranges can be scanned from different threads.
+    /// Pages that can't be read are returned as errors, see `Cursor`.
+    pub fn scan_range(
+        &self,
+        range: LeafRange<KeyType>,
+    ) -> Result<impl Iterator<Item = Result<KeyEntry<KeyType>, YarddError>> + '_, YarddError> {
+        let mut cursor = Cursor::new(self)?;
+        if let Bound::Included(key) | Bound::Excluded(key) = &range.start {
+            cursor.seek(key)?;
+        }
+
+        let entries = cursor
+            .skip_while({
+                let start = range.start.clone();
+                move |entry| {
+                    matches!((&start, entry), (Bound::Excluded(key), Ok(entry)) if entry.key().cmp_key(key).is_eq())
+                }
+            })
+            .take_while(move |entry| match (&range.end, entry) {
+                (_, Err(_)) | (Bound::Unbounded, _) => true,
+                (Bound::Included(key), Ok(entry)) => entry.key().cmp_key(key).is_le(),
+                (Bound::Excluded(key), Ok(entry)) => entry.key().cmp_key(key).is_lt(),
+            });
+
+        Ok(entries)
+    }
+
+    /// Splits the key space into up to `n` ranges holding roughly the same
+    /// number of leaves, for scanning in parallel with `scan_range`.
+    ///
+    /// Boundaries are separator keys sampled from the highest internal level
+    /// with at least `n` entries, so a tree with fewer leaves than `n` gets
+    /// fewer ranges.
+    pub fn partition_scan(&mut self, n: usize) -> Result<Vec<LeafRange<KeyType>>, YarddError> {
+        assert!(n > 0, "Need at least one partition");
+
+        let root_page_id = *self.root_page_id.get_mut().unwrap();
+        let mut level = vec![root_page_id];
+        let mut separators = vec![];
+
+        loop {
+            let mut next_level = vec![];
+            let mut keys = vec![];
+            for page_id in &level {
+                let page = self.fetch_page(*page_id)?;
+                let page = page.read().unwrap();
+                let node = page.as_index_node::<KeyType>();
+                if node.is_leaf() {
+                    break;
+                }
+
+                for entry in node.iter() {
+                    next_level.push(entry.page_id());
+                    keys.push(entry.key().clone());
+                }
+            }
+
+            if next_level.is_empty() {
+                break;
+            }
+
+            // The first key of the level only bounds the leftmost child
+            separators = keys.split_off(1);
+            if next_level.len() >= n {
+                break;
+            }
+            level = next_level;
+        }
+
+        let n_children = separators.len() + 1;
+        let mut boundaries: Vec<KeyType> = vec![];
+        for i in 1..n.min(n_children) {
+            let key = &separators[i * n_children / n.min(n_children) - 1];
+            if boundaries
+                .last()
+                .is_none_or(|last| last.cmp_key(key).is_lt())
+            {
+                boundaries.push(key.clone());
+            }
+        }
+
+        let mut ranges = vec![];
+        let mut start = Bound::Unbounded;
+        for key in boundaries {
+            ranges.push(LeafRange {
+                start,
+                end: Bound::Excluded(key.clone()),
+            });
+            start = Bound::Included(key);
+        }
+        ranges.push(LeafRange {
+            start,
+            end: Bound::Unbounded,
+        });
+
+        Ok(ranges)
+    }
+
+    /// Inserts the entry into its leaf. A full leaf splits, and the split
+    /// propagates up through full ancestors, growing a new root if the root splits.
+    /// Fails if a page on the way can't be read, or with `KeyTooLarge`,
+    /// leaving the tree unchanged, if the entry is too large for a page to hold
+    /// two of. Keys that can be truncated are moved to an overflow chain
+    /// instead, so only wide fixed size keys are refused.
+    pub fn insert(&self, entry: KeyEntry<KeyType>) -> Result<(), YarddError> {
+        let entry = self.store_large_key(entry)?;
+        Self::check_entry_size(&entry)?;
+
+        if let Some(entry) = self.try_insert(entry)? {
+            self.insert_with_split(entry)?;
+        }
+
+        self.key_count.fetch_add(1, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    // Checked after store_large_key, since a key moved to an overflow chain only
+    // leaves its prefix in the leaf
+    fn check_entry_size(entry: &KeyEntry<KeyType>) -> Result<(), YarddError> {
+        if entry.size_bytes() > MAX_ENTRY_BYTES {
+            return Err(YarddError::KeyTooLarge {
+                len: entry.size_bytes(),
+                max: MAX_ENTRY_BYTES,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Inserts the entry unless a live entry with the same key exists. Taking
+    /// the tree mutably keeps another insert from slipping in after the check.
+    /// The outer error is a page that couldn't be read.
+    pub fn insert_unique(
+        &mut self,
+        entry: KeyEntry<KeyType>,
+    ) -> Result<Result<(), DuplicateKey<KeyType>>, YarddError> {
+        if self.contains(entry.key())? {
+            return Ok(Err(DuplicateKey(entry.key().clone())));
+        }
+
+        self.insert(entry)?;
+
+        Ok(Ok(()))
+    }
+
+    /// Points an existing key at the entry's tuple, or inserts the entry if the
+    /// key is absent. Returns true if it was inserted. Keys are never rewritten,
+    /// so a large key keeps its overflow chain and only its tuple pointer changes.
+    pub fn upsert(&mut self, entry: KeyEntry<KeyType>) -> Result<bool, YarddError> {
+        let Some((leaf_page_id, slot_index)) = self.find_live_entry(entry.key())? else {
+            self.insert(entry)?;
+            return Ok(true);
+        };
+
+        let tuple_slot_index = entry.slot_index().expect("Only leaf entries are inserted");
+        let pointer = (entry.page_id(), tuple_slot_index);
+
+        let leaf = self.fetch_page(leaf_page_id)?;
+        let mut leaf = leaf.write().unwrap();
+        match self.repoint_slot(&mut leaf, slot_index, pointer) {
+            Ok(()) => {}
+            // The leaf shares one page id and has no room for the entry's own,
+            // so it moves through an insert, which can split the leaf
+            Err(YarddError::PageFull { .. }) => {
+                leaf.as_index_node_mut::<KeyType>().remove_slot(slot_index);
+                drop(leaf);
+                self.reinsert(entry)?;
+            }
+            Err(error) => return Err(error),
+        }
+
+        Ok(false)
+    }
+
+    // Puts back an entry taken out of its leaf, so the key count is unchanged
+    fn reinsert(&self, entry: KeyEntry<KeyType>) -> Result<(), YarddError> {
+        if let Some(entry) = self.try_insert(entry)? {
+            self.insert_with_split(entry)?;
+        }
+
+        Ok(())
+    }
+
+    // The leaf and slot of the first live entry for `key`. Equal keys can span
+    // leaves, so the scan follows the sibling link while a leaf ends with the key.
+    fn find_live_entry(&self, key: &KeyType) -> Result<Option<(PageId, SlotIndex)>, YarddError> {
+        let stored_key = Self::stored_key(key);
+        let mut leaf_page_id = self.first_leaf_for_key(&stored_key)?;
+
+        while leaf_page_id != NULL_PAGE_ID {
+            let leaf = self.fetch_page(leaf_page_id)?;
+            let leaf = leaf.read().unwrap();
+
+            if let Some(slot_index) = self.find_live_slot(&leaf, key, &stored_key)? {
+                return Ok(Some((leaf_page_id, slot_index)));
+            }
+
+            let node = leaf.as_index_node::<KeyType>();
+            let n_slots = node.read_n_slots();
+            if n_slots == 0 || node.compare_key(n_slots - 1, &stored_key).is_ne() {
+                break;
+            }
+            leaf_page_id = node.read_next_leaf_page_id();
+        }
+
+        Ok(None)
+    }
+
+    // Points the entry in a leaf slot at another tuple. A large key's tuple
+    // pointer is kept at the start of its overflow chain, so only that changes.
+    // Fails with PageFull if the leaf shares a page id and has no room for one.
+    fn repoint_slot(
+        &self,
+        leaf: &mut Page,
+        slot_index: SlotIndex,
+        (page_id, tuple_slot_index): TuplePointer,
+    ) -> Result<(), YarddError> {
+        let stored = leaf.as_index_node::<KeyType>().read_key_node(slot_index);
+        if stored.slot_index() != Some(OVERFLOW_SLOT_INDEX) {
+            return leaf.as_index_node_mut::<KeyType>().update_slot(
+                slot_index,
+                page_id,
+                Some(tuple_slot_index),
+            );
+        }
+
+        let mut bytes = vec![0; TUPLE_HEADER_SIZE];
+        let cursor = write_u64(&mut bytes, 0, page_id);
+        write_u16(&mut bytes, cursor, tuple_slot_index);
+
+        overwrite_overflow_chain_start(&self.page_manager, stored.page_id(), &bytes)
+    }
+
+    // The slot of the first live entry for `key` on a leaf, where `stored_key`
+    // is the key as it's stored in the index pages
+    fn find_live_slot(
+        &self,
+        leaf: &Page,
+        key: &KeyType,
+        stored_key: &KeyType,
+    ) -> Result<Option<SlotIndex>, YarddError> {
+        let node = leaf.as_index_node::<KeyType>();
+
+        let candidates = (node.lower_bound(stored_key)..node.read_n_slots())
+            .take_while(|slot_index| node.compare_key(*slot_index, stored_key).is_eq())
+            .filter(|slot_index| !node.is_slot_deleted(*slot_index));
+        for slot_index in candidates {
+            if self
+                .load_large_key(node.read_key_node(slot_index))?
+                .key()
+                .cmp_key(key)
+                .is_eq()
+            {
+                return Ok(Some(slot_index));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Inserts the entry straight into the leaf at the end of `path`, from
+    /// `search_with_path`, without descending from the root. If the tree has
+    /// changed so the leaf might no longer be the right one for the key, or the
+    /// leaf is full, this falls back to a normal insert.
+    pub fn insert_at_path(
+        &self,
+        entry: KeyEntry<KeyType>,
+        path: &DescentPath,
+    ) -> Result<(), YarddError> {
+        let entry = self.store_large_key(entry)?;
+        Self::check_entry_size(&entry)?;
+
+        let pending = match self.try_insert_at(entry, path) {
+            Ok(pending) => pending,
+            Err(entry) => self.try_insert(entry)?,
+        };
+        if let Some(entry) = pending {
+            self.insert_with_split(entry)?;
+        }
+
+        self.key_count.fetch_add(1, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    // Errs with the entry if the path is stale. Otherwise behaves like
+    // try_insert. Only the leaf is latched, so the latch order isn't broken.
+    fn try_insert_at(
+        &self,
+        entry: KeyEntry<KeyType>,
+        path: &DescentPath,
+    ) -> Result<Option<KeyEntry<KeyType>>, KeyEntry<KeyType>> {
+        let Some((&leaf_page_id, ancestors)) = path.page_ids.split_last() else {
+            return Err(entry);
+        };
+        let expected_parent = ancestors.last().copied().unwrap_or(NULL_PAGE_ID);
+
+        // The page may have been freed since, and dropped from its file or reused
+        let Ok(leaf) = self.fetch_page(leaf_page_id) else {
+            return Err(entry);
+        };
+        let mut leaf = leaf.write().unwrap();
+
+        if !leaf.is_initialized() || leaf.try_read_page_type() != Ok(PageType::IndexLeaf) {
+            return Err(entry);
+        }
+
+        let node = leaf.as_index_node::<KeyType>();
+        if node.parent_page_id() != expected_parent {
+            return Err(entry);
+        }
+
+        // Only trust the leaf if its own keys show the key belongs in it, or
+        // there's no neighbour it could belong to instead
+        let n_slots = node.read_n_slots();
+        let key = entry.key();
+        let after_start = node.read_prev_leaf_page_id() == NULL_PAGE_ID
+            || (n_slots > 0 && node.read_key(0).cmp_key(key).is_le());
+        let before_end = node.read_next_leaf_page_id() == NULL_PAGE_ID
+            || (n_slots > 0 && node.read_key(n_slots - 1).cmp_key(key).is_ge());
+        if !(after_start && before_end) {
+            return Err(entry);
+        }
+
+        let mut node = leaf.as_index_node_mut::<KeyType>();
+        if !node.can_fit(&entry) {
+            return Ok(Some(entry));
+        }
+
+        node.append_key(entry).expect("Entry was checked to fit");
+        Ok(None)
+    }
+
+    // Inserts holding one latch at a time. Hands the entry back if the leaf is full.
+    fn try_insert(
+        &self,
+        entry: KeyEntry<KeyType>,
+    ) -> Result<Option<KeyEntry<KeyType>>, YarddError> {
+        let root_page_id = self.root_page_id.read().unwrap();
+        let root = self.fetch_page(*root_page_id)?;
+        let root_guard = root.write().unwrap();
+        drop(root_page_id);
+
+        self.try_insert_from(root_guard, entry)
+    }
+
+    fn try_insert_from(
+        &self,
+        mut guard: RwLockWriteGuard<Page>,
+        entry: KeyEntry<KeyType>,
+    ) -> Result<Option<KeyEntry<KeyType>>, YarddError> {
+        if guard.as_index_node::<KeyType>().is_leaf() {
+            let mut node = guard.as_index_node_mut::<KeyType>();
+            if !node.can_fit(&entry) {
+                return Ok(Some(entry));
+            }
+
+            node.append_key(entry).expect("Entry was checked to fit");
+            return Ok(None);
+        }
+
+        let child_page_id = guard.as_index_node().find_child(entry.key());
+
+        let child = self.fetch_page(child_page_id)?;
+        let child_guard = child.write().unwrap();
+        drop(guard);
+
+        self.try_insert_from(child_guard, entry)
+    }
+
+    fn insert_with_split(&self, entry: KeyEntry<KeyType>) -> Result<(), YarddError> {
+        let mut root_page_id = self.root_page_id.write().unwrap();
+        let root = self.fetch_page(*root_page_id)?;
+        let mut root_guard = root.write().unwrap();
+
+        if let Some(split) = self.insert_split_from(&mut root_guard, entry)? {
+            *root_page_id = self.grow_root(&mut root_guard, split)?;
+            self.record_root(*root_page_id)?;
+        }
+
+        Ok(())
+    }
+
+    // Inserts below a write-latched node. If the node splits, returns the split
+    // for the parent to absorb.
+    fn insert_split_from(
+        &self,
+        page: &mut Page,
+        entry: KeyEntry<KeyType>,
+    ) -> Result<Option<SplitResult<KeyType>>, YarddError> {
+        if page.as_index_node::<KeyType>().is_leaf() {
+            return self.insert_into_leaf(page, entry);
+        }
+
+        let child_page_id = page.as_index_node().find_child(entry.key());
+        let child = self.fetch_page(child_page_id)?;
+        let mut child_guard = child.write().unwrap();
+
+        match self.insert_split_from(&mut child_guard, entry)? {
+            Some(split) => self.insert_into_internal(page, &mut child_guard, split),
+            None => Ok(None),
+        }
+    }
+
+    // Adds the separator for a child that split, splitting this node in turn if it's full.
+    // `child` is the latched left half of the child's split.
+    fn insert_into_internal(
+        &self,
+        page: &mut Page,
+        child: &mut Page,
+        split: SplitResult<KeyType>,
+    ) -> Result<Option<SplitResult<KeyType>>, YarddError> {
+        let separator = KeyEntry::internal(split.separator, split.right_page_id);
+
+        let mut node = page.as_index_node_mut::<KeyType>();
+        if node.can_fit(&separator) {
+            node.append_key(separator)
+                .expect("Entry was checked to fit");
+            return Ok(None);
+        }
+
+        let parent_page_id = page.as_index_node::<KeyType>().parent_page_id();
+
+        let right = self.page_manager.lock().unwrap().next_free_page()?;
+        let mut right = right.write().unwrap();
+        let right_page_id = right.page_id;
+        let mut right_node =
+            IndexPageMut::<KeyType>::init_page(PageType::IndexNode, parent_page_id, &mut right);
+
+        let mut node = page.as_index_node_mut::<KeyType>();
+        let mut node_split = node.split_into(&mut right_node, self.split_policy);
+
+        let inserted = if separator.key().cmp_key(&node_split.separator).is_lt() {
+            node_split.left_count += 1;
+            node.append_key(separator)
+        } else {
+            node_split.right_count += 1;
+            right_node.append_key(separator)
+        };
+        inserted.expect("Separator doesn't fit in a node even after a split");
+        self.trace_split(&node_split);
+
+        // Children that moved to the new node need their parent pointers updated.
+        // The child we came through is already latched, so it's updated through its guard.
+        let moved_children: Vec<PageId> = right_node.iter().map(|entry| entry.page_id()).collect();
+        for child_page_id in moved_children {
+            if child_page_id == child.page_id {
+                child
+                    .as_index_node_mut::<KeyType>()
+                    .set_parent(right_page_id);
+            } else {
+                self.fetch_page(child_page_id)?
+                    .write()
+                    .unwrap()
+                    .as_index_node_mut::<KeyType>()
+                    .set_parent(right_page_id);
+            }
+        }
+
+        Ok(Some(node_split))
+    }
+
+    fn insert_into_leaf(
+        &self,
+        page: &mut Page,
+        entry: KeyEntry<KeyType>,
+    ) -> Result<Option<SplitResult<KeyType>>, YarddError> {
+        // Tombstones are purged before splitting, as change_page_type does, so
+        // they don't force a split or become a separator in a parent or new root
+        let mut node = page.as_index_node_mut::<KeyType>();
+        if !node.can_fit(&entry) {
+            node.vacuum();
+        }
+        if node.can_fit(&entry) {
+            node.append_key(entry).expect("Entry was checked to fit");
+            return Ok(None);
+        }
+
+        let page_id = page.page_id;
+        let parent_page_id = page.as_index_node::<KeyType>().parent_page_id();
+
+        let right = self.page_manager.lock().unwrap().next_free_page()?;
+        let mut right = right.write().unwrap();
+        let right_page_id = right.page_id;
+        let mut right_node =
+            IndexPageMut::<KeyType>::init_page(PageType::IndexLeaf, parent_page_id, &mut right);
+
+        let mut node = page.as_index_node_mut::<KeyType>();
+        let mut split = node.split_into(&mut right_node, self.split_policy);
+
+        // Link the new leaf into the sibling chain
+        let next_leaf_page_id = node.read_next_leaf_page_id();
+        right_node.write_next_leaf_page_id(next_leaf_page_id);
+        right_node.write_prev_leaf_page_id(page_id);
+        node.write_next_leaf_page_id(right_page_id);
+
+        if next_leaf_page_id != NULL_PAGE_ID {
+            let next = self.fetch_page(next_leaf_page_id)?;
+            let mut next = next.write().unwrap();
+            next.as_index_node_mut::<KeyType>()
+                .write_prev_leaf_page_id(right_page_id);
+        }
+
+        let inserted = if entry.key().cmp_key(&split.separator).is_lt() {
+            split.left_count += 1;
+            node.append_key(entry)
+        } else {
+            split.right_count += 1;
+            right_node.append_key(entry)
+        };
+        inserted.expect("Entry doesn't fit in a leaf even after a split");
+        self.trace_split(&split);
+
+        Ok(Some(split))
+    }
+
+    fn trace_split(&self, split: &SplitResult<KeyType>) {
+        if let Some(trace) = &self.split_trace {
+            trace(split);
+        }
+    }
+
+    // Puts a new internal root above the old root and the page split off from it
+    fn grow_root(
+        &self,
+        old_root: &mut Page,
+        split: SplitResult<KeyType>,
+    ) -> Result<PageId, YarddError> {
+        let new_root = self.page_manager.lock().unwrap().next_free_page()?;
+        let mut new_root = new_root.write().unwrap();
+        let new_root_page_id = new_root.page_id;
+
+        let first_key = old_root
+            .as_index_node::<KeyType>()
+            .read_key_node(0)
+            .key()
+            .clone();
+
+        let mut root_node =
+            IndexPageMut::<KeyType>::init_page(PageType::IndexNode, NULL_PAGE_ID, &mut new_root);
+        root_node
+            .append_key(KeyEntry::internal(first_key, old_root.page_id))
+            .and_then(|()| {
+                root_node.append_key(KeyEntry::internal(split.separator, split.right_page_id))
+            })
+            .expect("Separators don't fit in an empty root");
+
+        old_root
+            .as_index_node_mut::<KeyType>()
+            .set_parent(new_root_page_id);
+
+        let right = self.fetch_page(split.right_page_id)?;
+        right
+            .write()
+            .unwrap()
+            .as_index_node_mut::<KeyType>()
+            .set_parent(new_root_page_id);
+
+        Ok(new_root_page_id)
+    }
+
+    /// Removes every entry matching `key` and returns how many were removed.
+    ///
+    /// Equal keys may straddle a separator, so every child that can hold the
+    /// key is visited. Afterwards neighbouring children that fit in one page
+    /// are merged, and the root collapses while it has a single child.
+    pub fn remove_all(&mut self, key: &KeyType) -> Result<usize, YarddError> {
+        let root_page_id = *self.root_page_id.get_mut().unwrap();
+
+        let root = self.fetch_page(root_page_id)?;
+        let removed = self.remove_from(&mut root.write().unwrap(), key)?;
+
+        let new_root_page_id = self.collapse_root(root_page_id)?;
+        if new_root_page_id != root_page_id {
+            *self.root_page_id.get_mut().unwrap() = new_root_page_id;
+            self.record_root(new_root_page_id)?;
+        }
+
+        *self.key_count.get_mut() -= removed as u64;
+
+        Ok(removed)
+    }
+
+    /// Purges tombstoned entries from every leaf and returns how many were
+    /// removed. Leaves left sparse aren't merged.
+    pub fn vacuum(&mut self) -> Result<usize, YarddError> {
+        let mut removed = 0;
+        let mut live = 0;
+
+        let mut leaf_page_id = self.first_leaf()?;
+        while leaf_page_id != NULL_PAGE_ID {
+            let leaf = self.fetch_page(leaf_page_id)?;
+            let mut leaf = leaf.write().unwrap();
+
+            let mut node = leaf.as_index_node_mut::<KeyType>();
+            removed += node.vacuum();
+            live += node.read_n_slots() as u64;
+            leaf_page_id = node.read_next_leaf_page_id();
+        }
+
+        // Entries tombstoned in the pages directly were never taken off the count
+        *self.key_count.get_mut() = live;
+
+        Ok(removed)
+    }
+
+    /// Rewrites the data pages of `file_name` with the tuples the indexes point
+    /// to packed densely from the start of the file, and points the indexes at
+    /// their new places. `other_indexes` must hold every other index on the
+    /// file. Tuples no index points to are dropped, and the data pages left
+    /// empty are released. Returns how many were released.
+    ///
+    /// The file's live tuples are held in memory while it's rewritten, and
+    /// nothing else may reach the file while this runs, like an offline VACUUM.
+    pub fn compact_data_file(
+        &mut self,
+        file_name: &str,
+        other_indexes: &mut [&mut dyn TupleIndex],
+    ) -> Result<usize, YarddError> {
+        let page_ids = self.page_manager.lock().unwrap().pages_in_file(file_name);
+        let mut data_page_ids = vec![];
+        for page_id in page_ids {
+            let page = self.fetch_page(page_id)?;
+            let page = page.read().unwrap();
+            if page.is_initialized() && page.try_read_page_type() == Ok(PageType::DataPage) {
+                data_page_ids.push(page_id);
+            }
+        }
+        let positions: HashMap<PageId, usize> = data_page_ids
+            .iter()
+            .enumerate()
+            .map(|(position, page_id)| (*page_id, position))
+            .collect();
+
+        // Every live tuple some index points to, in the tuples' order in the file
+        let mut live = BTreeMap::new();
+        for index in std::iter::once(self as &mut dyn TupleIndex).chain(
+            other_indexes
+                .iter_mut()
+                .map(|index| &mut **index as &mut dyn TupleIndex),
+        ) {
+            for (page_id, slot_index) in index.tuple_pointers()? {
+                if let Some(position) = positions.get(&page_id) {
+                    live.insert((*position, slot_index), (page_id, slot_index));
+                }
+            }
+        }
+
+        // Read everything before any page is rewritten
+        let mut tuples: Vec<(Vec<u8>, TuplePointer)> = vec![];
+        for (page_id, slot_index) in live.into_values() {
+            let page = self.fetch_page(page_id)?;
+            let tuple = page
+                .read()
+                .unwrap()
+                .as_data_page()
+                .read_tuple(slot_index)
+                .to_vec();
+            tuples.push((tuple, (page_id, slot_index)));
+        }
+
+        let mut tuples = tuples.into_iter().peekable();
+        let mut moves = HashMap::new();
+        let mut released = vec![];
+
+        for page_id in data_page_ids {
+            let page = self.fetch_page(page_id)?;
+            let mut page = page.write().unwrap();
+
+            if tuples.peek().is_none() {
+                page.reset();
+                page.is_dirty = true;
+                released.push(page_id);
+                continue;
+            }
+
+            let mut data_page = DataPageMut::init_page(&mut page);
+            while let Some((tuple, _)) = tuples.peek() {
+                let Ok(slot_index) = data_page.append_tuple(tuple) else {
+                    break;
+                };
+
+                let (_, old_pointer) = tuples.next().unwrap();
+                if old_pointer != (page_id, slot_index) {
+                    moves.insert(old_pointer, (page_id, slot_index));
+                }
+            }
+
+            let free_space = data_page.as_read_only().free_space();
+            self.page_manager
+                .lock()
+                .unwrap()
+                .update_free_space_hint(page_id, free_space);
+        }
+
+        // Packing only closes gaps, so the tuples fit in the pages they came from
+        assert!(
+            tuples.peek().is_none(),
+            "Compacted tuples don't fit in the file"
+        );
+
+        self.move_tuples(&moves)?;
+        for index in other_indexes.iter_mut() {
+            index.move_tuples(&moves)?;
+        }
+
+        let mut page_manager = self.page_manager.lock().unwrap();
+        for page_id in released.iter() {
+            page_manager.release_page(*page_id);
+        }
+
+        Ok(released.len())
+    }
+
+    fn remove_from(&self, page: &mut Page, key: &KeyType) -> Result<usize, YarddError> {
+        let stored_key = Self::stored_key(key);
+
+        if page.as_index_node::<KeyType>().is_leaf() {
+            // A truncated key only tells us a prefix matched. Full keys are read
+            // before anything is removed, and tell entries apart by their chain.
+            let mut matching_chains = HashSet::new();
+            for entry in page.as_index_node::<KeyType>().iter() {
+                let is_large = entry.slot_index() == Some(OVERFLOW_SLOT_INDEX);
+                if is_large
+                    && entry.key().cmp_key(&stored_key).is_eq()
+                    && self
+                        .load_large_key(entry.clone())?
+                        .key()
+                        .cmp_key(key)
+                        .is_eq()
+                {
+                    matching_chains.insert(entry.page_id());
+                }
+            }
+
+            let removed = page.as_index_node_mut::<KeyType>().remove_where(|entry| {
+                entry.key().cmp_key(&stored_key).is_eq()
+                    && if entry.slot_index() == Some(OVERFLOW_SLOT_INDEX) {
+                        matching_chains.contains(&entry.page_id())
+                    } else {
+                        stored_key.cmp_key(key).is_eq()
+                    }
+            });
+
+            for entry in removed.iter() {
+                if entry.slot_index() == Some(OVERFLOW_SLOT_INDEX) {
+                    free_overflow_chain(&self.page_manager, entry.page_id())?;
+                }
+            }
+
+            return Ok(removed.len());
+        }
+
+        let (first, last) = Self::child_range_for_key(&page.as_index_node(), &stored_key);
+
+        let mut removed = 0;
+        for slot_index in first..=last {
+            let child_page_id = page
+                .as_index_node::<KeyType>()
+                .read_key_node(slot_index)
+                .page_id();
+            let child = self.fetch_page(child_page_id)?;
+            removed += self.remove_from(&mut child.write().unwrap(), key)?;
+        }
+
+        if removed > 0 {
+            let mut last = last;
+            for slot_index in (first..=last).rev() {
+                if self.reclaim_empty_leaf(page, slot_index)? {
+                    last = last.saturating_sub(1);
+                }
+            }
+
+            // Also try the right neighbour of the last child visited
+            let n_slots = page.as_index_node::<KeyType>().read_n_slots();
+            let last = (last + 1).min(n_slots - 1);
+
+            for slot_index in (first.max(1)..=last).rev() {
+                self.merge_child(page, slot_index)?;
+            }
+        }
+
+        Ok(removed)
+    }
+
+    // Slots of the first and last child that may hold `key`. The child left
+    // of a separator equal to `key` is included since duplicates can span it.
+    fn child_range_for_key(node: &IndexPage<KeyType>, key: &KeyType) -> (SlotIndex, SlotIndex) {
+        let entries: Vec<_> = node.iter().collect();
+
+        let first = entries
+            .iter()
+            .rposition(|entry| entry.key().cmp_key(key).is_lt())
+            .unwrap_or(0);
+        let last = entries
+            .iter()
+            .rposition(|entry| entry.key().cmp_key(key).is_le())
+            .unwrap_or(0);
+
+        (first as SlotIndex, last as SlotIndex)
+    }
+
+    // Unlinks the child at `slot_index` and frees its page if it's an empty leaf.
+    // The parent keeps at least one child, an empty root leaf is left alone.
+    fn reclaim_empty_leaf(
+        &self,
+        parent: &mut Page,
+        slot_index: SlotIndex,
+    ) -> Result<bool, YarddError> {
+        let node = parent.as_index_node::<KeyType>();
+        if node.read_n_slots() < 2 {
+            return Ok(false);
+        }
+        let page_id = node.read_key_node(slot_index).page_id();
+
+        let (prev_leaf_page_id, next_leaf_page_id) = {
+            let leaf = self.fetch_page(page_id)?;
+            let leaf = leaf.read().unwrap();
+            let node = leaf.as_index_node::<KeyType>();
+            if !node.is_leaf() || node.read_n_slots() > 0 {
+                return Ok(false);
+            }
+
+            (node.read_prev_leaf_page_id(), node.read_next_leaf_page_id())
+        };
+
+        // Nothing can be added to the leaf while its parent is latched. Its
+        // neighbours are latched left to right, without holding the leaf.
+        if prev_leaf_page_id != NULL_PAGE_ID {
+            self.fetch_page(prev_leaf_page_id)?
+                .write()
+                .unwrap()
+                .as_index_node_mut::<KeyType>()
+                .write_next_leaf_page_id(next_leaf_page_id);
+        }
+        if next_leaf_page_id != NULL_PAGE_ID {
+            self.fetch_page(next_leaf_page_id)?
+                .write()
+                .unwrap()
+                .as_index_node_mut::<KeyType>()
+                .write_prev_leaf_page_id(prev_leaf_page_id);
+        }
+
+        parent
+            .as_index_node_mut::<KeyType>()
+            .remove_slot(slot_index);
+        self.page_manager.lock().unwrap().release_page(page_id);
+
+        Ok(true)
+    }
+
+    // Folds the child at `slot_index` into its left neighbour if their entries
+    // fit in one page
+    fn merge_child(&self, parent: &mut Page, slot_index: SlotIndex) -> Result<(), YarddError> {
+        let (left_page_id, right_page_id) = {
+            let node = parent.as_index_node::<KeyType>();
+            (
+                node.read_key_node(slot_index - 1).page_id(),
+                node.read_key_node(slot_index).page_id(),
+            )
+        };
+
+        let left = self.fetch_page(left_page_id)?;
+        let mut left = left.write().unwrap();
+        let right = self.fetch_page(right_page_id)?;
+        let right = right.write().unwrap();
+
+        // Separators in this tree are the first key of each child, so the right
+        // page's entries already carry theirs
+        let merged = left
+            .as_index_node_mut::<KeyType>()
+            .merge_from(&right.as_index_node(), None);
+        if merged.is_err() {
+            return Ok(());
+        }
+
+        if right.as_index_node::<KeyType>().is_leaf() {
+            let next_leaf_page_id = right.as_index_node::<KeyType>().read_next_leaf_page_id();
+            if next_leaf_page_id != NULL_PAGE_ID {
+                let next = self.fetch_page(next_leaf_page_id)?;
+                let mut next = next.write().unwrap();
+                next.as_index_node_mut::<KeyType>()
+                    .write_prev_leaf_page_id(left_page_id);
+            }
+        } else {
+            let entries: Vec<_> = right.as_index_node::<KeyType>().iter().collect();
+            for entry in entries {
+                let child = self.fetch_page(entry.page_id())?;
+                child
+                    .write()
+                    .unwrap()
+                    .as_index_node_mut::<KeyType>()
+                    .set_parent(left_page_id);
+            }
+        }
+
+        drop(right);
+        parent
+            .as_index_node_mut::<KeyType>()
+            .remove_slot(slot_index);
+        self.page_manager
+            .lock()
+            .unwrap()
+            .release_page(right_page_id);
+
+        Ok(())
+    }
+
+    // Replaces the root with its child while the root has a single child
+    fn collapse_root(&self, mut root_page_id: PageId) -> Result<PageId, YarddError> {
+        loop {
+            let root = self.fetch_page(root_page_id)?;
+            let child_page_id = {
+                let root = root.read().unwrap();
+                let node = root.as_index_node::<KeyType>();
+                if node.is_leaf() || node.read_n_slots() > 1 {
+                    return Ok(root_page_id);
+                }
+
+                node.read_key_node(0).page_id()
+            };
+
+            let child = self.fetch_page(child_page_id)?;
+            child
+                .write()
+                .unwrap()
+                .as_index_node_mut::<KeyType>()
+                .set_parent(NULL_PAGE_ID);
+
+            self.page_manager.lock().unwrap().release_page(root_page_id);
+            root_page_id = child_page_id;
+        }
+    }
+
+    /// The number of live entries in the tree.
+    pub fn len(&self) -> u64 {
+        self.key_count.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Counts the live entries by walking every leaf. `len` is the cheap way to get this.
+    pub fn count_entries(&self) -> Result<u64, YarddError> {
+        let mut stats = TreeStats::default();
+        self.collect_stats(self.root_page_id(), 1, &mut stats)?;
+
+        Ok(stats.total_keys as u64)
+    }
+
+    /// An estimate of how many live entries fall in `[low, high]`, for query
+    /// planning. Only the two boundary leaves are counted exactly; the leaves
+    /// between them are assumed to be as full as the boundary leaves, and their
+    /// number is worked out from the child positions along the two paths.
+    pub fn estimate_count(&mut self, low: &KeyType, high: &KeyType) -> Result<u64, YarddError> {
+        if low.cmp_key(high).is_gt() {
+            return Ok(0);
+        }
+
+        let in_range = |key: &KeyType| key.cmp_key(low).is_ge() && key.cmp_key(high).is_le();
+
+        // For each boundary leaf: the child picked and the node's size at each
+        // level, the leaf, its live entries and how many of them are in range
+        let descend = |pick_last: bool| {
+            let path = RefCell::new(vec![]);
+            let (leaf_page_id, fill, count) = self.with_leaf(
+                |node| {
+                    let (first, last) =
+                        Self::child_range_for_key(node, if pick_last { high } else { low });
+                    let slot_index = if pick_last { last } else { first };
+
+                    path.borrow_mut()
+                        .push((slot_index as f64, node.read_n_slots() as f64));
+                    node.read_key_node(slot_index).page_id()
+                },
+                |leaf| {
+                    let node = leaf.as_index_node::<KeyType>();
+                    let (fill, count) = node.iter_visible().fold((0, 0), |(fill, count), entry| {
+                        (fill + 1, count + in_range(entry.key()) as u64)
+                    });
+
+                    (leaf.page_id, fill, count)
+                },
+            )?;
+
+            Ok::<_, YarddError>((path.into_inner(), leaf_page_id, fill, count))
+        };
+
+        let (low_path, low_leaf, low_fill, low_count) = descend(false)?;
+        let (high_path, high_leaf, high_fill, high_count) = descend(true)?;
+
+        if low_leaf == high_leaf {
+            return Ok(low_count);
+        }
+
+        // Each leaf's position counted in average subtree sizes, deepest level first
+        let mut subtree_leaves = 1.0;
+        let mut leaves_apart = 0.0;
+        for ((low_slot, low_size), (high_slot, high_size)) in low_path.iter().zip(&high_path).rev()
+        {
+            leaves_apart += (high_slot - low_slot) * subtree_leaves;
+            subtree_leaves *= (low_size + high_size) / 2.0;
+        }
+
+        let leaves_between = (leaves_apart - 1.0).max(0.0);
+        let average_fill = (low_fill + high_fill) as f64 / 2.0;
+
+        Ok(low_count + high_count + (leaves_between * average_fill).round() as u64)
+    }
+
+    /// Every leaf with its fragmentation ratio, most fragmented first, for
+    /// deciding which leaves are worth compacting.
+    pub fn fragmentation_report(&self) -> Result<Vec<(PageId, f32)>, YarddError> {
+        let mut report = vec![];
+
+        let mut leaf_page_id = self.first_leaf()?;
+        while leaf_page_id != NULL_PAGE_ID {
+            let leaf = self.fetch_page(leaf_page_id)?;
+            let leaf = leaf.read().unwrap();
+            let node = leaf.as_index_node::<KeyType>();
+
+            report.push((leaf_page_id, node.fragmentation_ratio()));
+            leaf_page_id = node.read_next_leaf_page_id();
+        }
+
+        report.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        Ok(report)
+    }
+
+    /// Walks the whole tree counting pages and keys.
+    pub fn stats(&mut self) -> Result<TreeStats, YarddError> {
+        let root_page_id = *self.root_page_id.get_mut().unwrap();
+
+        let mut stats = TreeStats::default();
+        self.collect_stats(root_page_id, 1, &mut stats)?;
+
+        Ok(stats)
+    }
+
+    fn collect_stats(
+        &self,
+        page_id: PageId,
+        depth: usize,
+        stats: &mut TreeStats,
+    ) -> Result<(), YarddError> {
+        let page = self.fetch_page(page_id)?;
+        let children: Vec<PageId> = {
+            let page = page.read().unwrap();
+            let node = page.as_index_node::<KeyType>();
+
+            stats.height = stats.height.max(depth);
+
+            if node.is_leaf() {
+                stats.leaf_pages += 1;
+                stats.total_keys += (0..node.read_n_slots())
+                    .filter(|slot_index| !node.is_slot_deleted(*slot_index))
+                    .count();
+                return Ok(());
+            }
+
+            stats.internal_pages += 1;
+            node.iter().map(|entry| entry.page_id()).collect()
+        };
+
+        for child_page_id in children {
+            self.collect_stats(child_page_id, depth + 1, stats)?;
+        }
+
+        Ok(())
+    }
+
+    /// Walks the whole tree checking parent pointers, separator bounds, leaf
+    /// depth and the sibling chain. Reports the first violation found, or the
+    /// first page that can't be read.
+    pub fn validate(&mut self) -> Result<(), String> {
+        let root_page_id = *self.root_page_id.get_mut().unwrap();
+
+        let mut leaves = vec![];
+        let mut leaf_depth = None;
+        self.validate_node(
+            root_page_id,
+            NULL_PAGE_ID,
+            (None, None),
+            0,
+            &mut leaf_depth,
+            &mut leaves,
+        )?;
+
+        for (i, leaf_page_id) in leaves.iter().enumerate() {
+            let expected_prev = if i > 0 { leaves[i - 1] } else { NULL_PAGE_ID };
+            let expected_next = leaves.get(i + 1).copied().unwrap_or(NULL_PAGE_ID);
+
+            let leaf = self.fetch_page_for_validate(*leaf_page_id)?;
+            let leaf = leaf.read().unwrap();
+            let node = leaf.as_index_node::<KeyType>();
+
+            if node.read_prev_leaf_page_id() != expected_prev {
+                return Err(format!(
+                    "Leaf {} points back to {} instead of {}",
+                    leaf_page_id,
+                    node.read_prev_leaf_page_id(),
+                    expected_prev
+                ));
+            }
+            if node.read_next_leaf_page_id() != expected_next {
+                return Err(format!(
+                    "Leaf {} points forward to {} instead of {}",
+                    leaf_page_id,
+                    node.read_next_leaf_page_id(),
+                    expected_next
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn fetch_page_for_validate(&self, page_id: PageId) -> Result<PagePointer, String> {
+        self.fetch_page(page_id)
+            .map_err(|error| format!("Page {} can't be read: {}", page_id, error))
+    }
+
+    // Checks a subtree whose keys must lie within `bounds` (both inclusive,
+    // since equal keys may straddle a separator). Leaves are collected in key order.
+    fn validate_node(
+        &self,
+        page_id: PageId,
+        expected_parent: PageId,
+        bounds: (Option<KeyType>, Option<KeyType>),
+        depth: usize,
+        leaf_depth: &mut Option<usize>,
+        leaves: &mut Vec<PageId>,
+    ) -> Result<(), String> {
+        let page = self.fetch_page_for_validate(page_id)?;
+        let (is_leaf, entries) = {
+            let page = page.read().unwrap();
+
+            let node = page.as_index_node::<KeyType>();
+
+            let parent_page_id = node.parent_page_id();
+            if parent_page_id != expected_parent {
+                return Err(format!(
+                    "Page {} has parent {} but is a child of {}",
+                    page_id, parent_page_id, expected_parent
+                ));
+            }
+
+            if let Err(errors) = node.validate() {
+                return Err(format!(
+                    "Page {} is corrupt: {}",
+                    page_id,
+                    errors.join("; ")
+                ));
+            }
+
+            (node.is_leaf(), node.iter().collect::<Vec<_>>())
+        };
+
+        let (lower, upper) = bounds;
+        for (slot_index, entry) in entries.iter().enumerate() {
+            // The first separator may be stale, smaller keys are routed to the first child anyway
+            let checks_lower = is_leaf || slot_index > 0;
+            let below = checks_lower
+                && lower
+                    .as_ref()
+                    .is_some_and(|lower| entry.key().cmp_key(lower).is_lt());
+            let above = upper
+                .as_ref()
+                .is_some_and(|upper| entry.key().cmp_key(upper).is_gt());
+
+            if below || above {
+                return Err(format!(
+                    "Slot {} of page {} is outside the bounds set by its parent {}",
+                    slot_index, page_id, expected_parent
+                ));
+            }
+        }
+
+        if is_leaf {
+            match *leaf_depth {
+                None => *leaf_depth = Some(depth),
+                Some(leaf_depth) if leaf_depth != depth => {
+                    return Err(format!(
+                        "Leaf {} is at depth {} but other leaves are at depth {}",
+                        page_id, depth, leaf_depth
+                    ));
+                }
+                _ => {}
+            }
+
+            leaves.push(page_id);
+            return Ok(());
+        }
+
+        if entries.is_empty() {
+            return Err(format!("Internal node {} has no children", page_id));
+        }
+
+        for (slot_index, entry) in entries.iter().enumerate() {
+            let child_lower = if slot_index == 0 {
+                lower.clone()
+            } else {
+                Some(entry.key().clone())
+            };
+            let child_upper = match entries.get(slot_index + 1) {
+                Some(next) => Some(next.key().clone()),
+                None => upper.clone(),
+            };
+
+            self.validate_node(
+                entry.page_id(),
+                page_id,
+                (child_lower, child_upper),
+                depth + 1,
+                leaf_depth,
+                leaves,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<KeyType> TupleIndex for BTree<KeyType>
+where
+    KeyType: DbColumn,
+{
+    fn tuple_pointers(&mut self) -> Result<Vec<TuplePointer>, YarddError> {
+        self.vacuum()?;
+
+        let mut pointers = vec![];
+        for entry in self.iter()? {
+            let entry = entry?;
+            if let Some(slot_index) = entry.slot_index() {
+                pointers.push((entry.page_id(), slot_index));
+            }
+        }
+
+        Ok(pointers)
+    }
+
+    fn move_tuples(
+        &mut self,
+        moves: &HashMap<TuplePointer, TuplePointer>,
+    ) -> Result<(), YarddError> {
+        if moves.is_empty() {
+            return Ok(());
+        }
+
+        let mut pending = vec![];
+        let mut leaf_page_id = self.first_leaf()?;
+        while leaf_page_id != NULL_PAGE_ID {
+            let leaf = self.fetch_page(leaf_page_id)?;
+            let mut leaf = leaf.write().unwrap();
+
+            // A large key's tuple pointer is behind its overflow chain
+            let mut moved = vec![];
+            for (slot_index, entry) in leaf.as_index_node::<KeyType>().iter_indexed() {
+                let entry = self.load_large_key(entry)?;
+                let Some(tuple_slot_index) = entry.slot_index() else {
+                    continue;
+                };
+                if let Some(new_pointer) = moves.get(&(entry.page_id(), tuple_slot_index)) {
+                    moved.push((slot_index, *new_pointer));
+                }
+            }
+
+            let mut full = vec![];
+            for (slot_index, new_pointer) in moved {
+                match self.repoint_slot(&mut leaf, slot_index, new_pointer) {
+                    Ok(()) => {}
+                    Err(YarddError::PageFull { .. }) => full.push((slot_index, new_pointer)),
+                    Err(error) => return Err(error),
+                }
+            }
+
+            // Taken out last first so the earlier slots stay put
+            for (slot_index, (page_id, tuple_slot_index)) in full.into_iter().rev() {
+                let removed = leaf.as_index_node_mut::<KeyType>().remove_slot(slot_index);
+                pending.push(KeyEntry::leaf(
+                    removed.key().clone(),
+                    page_id,
+                    tuple_slot_index,
+                ));
+            }
+
+            leaf_page_id = leaf.as_index_node::<KeyType>().read_next_leaf_page_id();
+        }
+
+        // Put back once every entry has been matched, so a new pointer isn't
+        // mistaken for the old pointer of an entry further on
+        for entry in pending {
+            self.reinsert(entry)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod btree_tests {
+    use std::{
+        fs::{create_dir_all, remove_dir_all},
+        ops::Bound,
+        path::Path,
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    use std::cmp::Ordering;
+
+    use crate::{
+        data_page::{DataFileScan, DataPageMut},
+        disk_btree::{
+            IndexPageMut, IndexPageRead, IndexPageReadSized, KeyEntry, SplitPolicy,
+            MAX_ENTRY_BYTES, TUPLE_HEADER_SIZE,
+        },
+        error::{DuplicateKey, YarddError},
+        page::{DbColumn, Page, PageId, PageType, NULL_PAGE_ID},
+        page_manager::{PageManager, SharedPageManager},
+        serialization_helpers::{read_u64, write_u64},
+    };
+
+    use super::{BTree, LeafRange, TreeStats};
+
+    fn setup_test_dir(base_dir: &str) {
+        let path = Path::new(base_dir);
+        create_dir_all(path).expect("Failed to create test directory.");
+    }
+
+    fn cleanup(base_dir: &str) {
+        let _ = remove_dir_all(base_dir);
+    }
+
+    fn leaf_entry(key: u64) -> KeyEntry<u64> {
+        KeyEntry::leaf(key, key * 10, key as u16)
+    }
+
+    // Builds an internal root over one leaf per key range
+    fn build_two_level_tree(manager: &SharedPageManager, leaves: &[Vec<u64>]) -> PageId {
+        let root = manager.lock().unwrap().next_free_page().unwrap();
+        let mut root = root.write().unwrap();
+        let root_page_id = root.page_id;
+        let mut root_node =
+            IndexPageMut::<u64>::init_page(PageType::IndexNode, NULL_PAGE_ID, &mut root);
+
+        for keys in leaves {
+            let leaf = manager.lock().unwrap().next_free_page().unwrap();
+            let mut leaf = leaf.write().unwrap();
+            let leaf_page_id = leaf.page_id;
+            let mut leaf_node =
+                IndexPageMut::<u64>::init_page(PageType::IndexLeaf, root_page_id, &mut leaf);
+
+            for key in keys {
+                leaf_node.append_key(leaf_entry(*key)).unwrap();
+            }
+
+            root_node
+                .append_key(KeyEntry::internal(keys[0], leaf_page_id))
+                .unwrap();
+        }
+
+        root_page_id
+    }
+
+    #[test]
+    pub fn search_single_leaf() {
+        let base_dir = "./test6";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(4, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 4);
+
+        let tree = BTree::<u64>::new(manager).unwrap();
+        for key in [5, 1, 3] {
+            tree.insert(leaf_entry(key)).unwrap();
+        }
+
+        let entry = tree.search(&3).unwrap().expect("Key not found");
+        assert_eq!(3, *entry.key());
+        assert_eq!(30, entry.page_id());
+        assert_eq!(Some(3), entry.slot_index());
+
+        assert!(tree.search(&4).unwrap().is_none());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn search_two_levels() {
+        let base_dir = "./test7";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(8, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 8);
+
+        let root_page_id = build_two_level_tree(
+            &manager,
+            &[vec![0, 1, 2], vec![100, 101, 102], vec![200, 201, 202]],
+        );
+        let tree = BTree::<u64>::open(manager, root_page_id).unwrap();
+
+        for key in [0, 2, 100, 102, 200, 202] {
+            let entry = tree.search(&key).unwrap().expect("Key not found");
+            assert_eq!(key, *entry.key());
+        }
+
+        assert!(tree.search(&50).unwrap().is_none());
+        assert!(tree.search(&300).unwrap().is_none());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn concurrent_search_and_insert() {
+        let base_dir = "./test8";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(8, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 8);
+
+        let leaves: Vec<Vec<u64>> = (0..3)
+            .map(|i| (0..10).map(|k| i * 100 + k).collect())
+            .collect();
+        let root_page_id = build_two_level_tree(&manager, &leaves);
+        let tree = Arc::new(BTree::<u64>::open(manager, root_page_id).unwrap());
+
+        let mut handles = vec![];
+
+        for _ in 0..4 {
+            let tree = tree.clone();
+            let leaves = leaves.clone();
+            handles.push(thread::spawn(move || {
+                for _ in 0..20 {
+                    for key in leaves.iter().flatten() {
+                        assert!(tree.search(key).unwrap().is_some());
+                    }
+                }
+            }));
+        }
+
+        for i in 0..3 {
+            let tree = tree.clone();
+            handles.push(thread::spawn(move || {
+                for k in 10..30 {
+                    tree.insert(leaf_entry(i * 100 + k)).unwrap();
+                }
+            }));
+        }
+
+        for handle in handles {
+            handle.join().expect("Thread panicked");
+        }
+
+        for i in 0..3 {
+            for k in 0..30 {
+                let entry = tree.search(&(i * 100 + k)).unwrap().expect("Key not found");
+                assert_eq!(i * 100 + k, *entry.key());
+            }
+        }
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn first_leaf_single_page() {
+        let base_dir = "./test14";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(4, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 4);
+
+        let tree = BTree::<u64>::new(manager).unwrap();
+        tree.insert(leaf_entry(1)).unwrap();
+
+        assert_eq!(tree.root_page_id(), tree.first_leaf().unwrap());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn first_leaf_multi_level() {
+        let base_dir = "./test15";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(8, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 8);
+
+        let root_page_id =
+            build_two_level_tree(&manager, &[vec![200, 201], vec![3, 4, 5], vec![100, 101]]);
+        let tree = BTree::<u64>::open(manager.clone(), root_page_id).unwrap();
+
+        let first_leaf = tree.first_leaf().unwrap();
+        assert_ne!(root_page_id, first_leaf);
+
+        let page = manager.lock().unwrap().find_page(first_leaf).unwrap();
+        let page = page.read().unwrap();
+        let smallest = page.as_index_node::<u64>().read_key_node(0);
+        assert_eq!(3, *smallest.key());
+
+        cleanup(base_dir);
+    }
+
+    // Keys of every leaf, following the sibling chain from the first leaf
+    fn leaf_chain(manager: &SharedPageManager, tree: &BTree<u64>) -> Vec<Vec<u64>> {
+        let mut leaves = vec![];
+        let mut prev_page_id = NULL_PAGE_ID;
+        let mut page_id = tree.first_leaf().unwrap();
+
+        while page_id != NULL_PAGE_ID {
+            let page = manager.lock().unwrap().find_page(page_id).unwrap();
+            let page = page.read().unwrap();
+            let node = page.as_index_node::<u64>();
+
+            assert_eq!(prev_page_id, node.read_prev_leaf_page_id());
+            leaves.push(node.iter().map(|entry| *entry.key()).collect());
+
+            prev_page_id = page_id;
+            page_id = node.read_next_leaf_page_id();
+        }
+
+        leaves
+    }
+
+    // Number of entries an empty leaf can hold
+    fn leaf_capacity() -> usize {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut node = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, NULL_PAGE_ID, &mut page);
+
+        let mut capacity = 0;
+        while node.can_fit(&leaf_entry(capacity)) {
+            node.append_key(leaf_entry(capacity)).unwrap();
+            capacity += 1;
+        }
+
+        capacity as usize
+    }
+
+    #[test]
+    pub fn insert_splits_leaves() {
+        let base_dir = "./test16";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(32, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 32);
+
+        let mut tree = BTree::<u64>::new(manager.clone()).unwrap();
+        let original_root = tree.root_page_id();
+
+        let keys: Vec<u64> = (0..200).map(|i| (i * 37) % 200).collect();
+        for key in keys.iter() {
+            tree.insert(leaf_entry(*key)).unwrap();
+        }
+
+        assert_ne!(original_root, tree.root_page_id());
+        assert_eq!(Ok(()), tree.validate());
+
+        for key in keys.iter() {
+            let entry = tree.search(key).unwrap().expect("Key not found");
+            assert_eq!(*key, *entry.key());
+            assert_eq!(key * 10, entry.page_id());
+        }
+
+        let leaves = leaf_chain(&manager, &tree);
+        assert!(leaves.len() > 1);
+
+        let all_keys: Vec<u64> = leaves.into_iter().flatten().collect();
+        assert_eq!((0..200).collect::<Vec<u64>>(), all_keys);
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn right_heavy_split_packs_left_leaves() {
+        let base_dir = "./test17";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(32, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 32);
+
+        let mut right_heavy = BTree::<u64>::new(manager.clone()).unwrap();
+        right_heavy.set_split_policy(SplitPolicy::RightHeavy);
+        let mut even = BTree::<u64>::new(manager.clone()).unwrap();
+
+        for key in 0..200 {
+            right_heavy.insert(leaf_entry(key)).unwrap();
+            even.insert(leaf_entry(key)).unwrap();
+        }
+
+        assert_eq!(Ok(()), right_heavy.validate());
+        assert_eq!(Ok(()), even.validate());
+
+        let capacity = leaf_capacity();
+        let right_heavy_leaves = leaf_chain(&manager, &right_heavy);
+        let even_leaves = leaf_chain(&manager, &even);
+
+        for leaf in right_heavy_leaves.iter().take(right_heavy_leaves.len() - 1) {
+            assert_eq!(capacity - 1, leaf.len());
+        }
+        for leaf in even_leaves.iter().take(even_leaves.len() - 1) {
+            assert_eq!(capacity / 2, leaf.len());
+        }
+
+        assert!(right_heavy_leaves.len() < even_leaves.len());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn remove_all_across_split() {
+        let base_dir = "./test18";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(16, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 16);
+
+        let mut tree = BTree::<u64>::new(manager.clone()).unwrap();
+        let original_root = tree.root_page_id();
+
+        // Place the duplicates in the middle of the leaf so the split lands among them
+        let capacity = leaf_capacity() as u64;
+        let small_keys: Vec<u64> = (0..capacity / 2 - 2).collect();
+        let large_keys: Vec<u64> = (2000..2000 + capacity - small_keys.len() as u64 - 4).collect();
+
+        for key in small_keys.iter() {
+            tree.insert(leaf_entry(*key)).unwrap();
+        }
+        for _ in 0..5 {
+            tree.insert(leaf_entry(1000)).unwrap();
+        }
+        for key in large_keys.iter() {
+            tree.insert(leaf_entry(*key)).unwrap();
+        }
+
+        let leaves = leaf_chain(&manager, &tree);
+        let leaves_with_key = leaves.iter().filter(|leaf| leaf.contains(&1000)).count();
+        assert_eq!(2, leaves_with_key);
+        assert_eq!(Ok(()), tree.validate());
+
+        assert_eq!(5, tree.remove_all(&1000).unwrap());
+        assert_eq!(0, tree.remove_all(&1000).unwrap());
+        assert!(tree.search(&1000).unwrap().is_none());
+
+        // The remaining entries fit in one leaf again
+        let remaining: Vec<u64> = small_keys
+            .iter()
+            .chain(large_keys.iter())
+            .cloned()
+            .collect();
+        assert_eq!(vec![remaining.clone()], leaf_chain(&manager, &tree));
+        assert_eq!(original_root, tree.root_page_id());
+        assert_eq!(Ok(()), tree.validate());
+
+        for key in remaining.iter() {
+            assert_eq!(
+                *key,
+                *tree.search(key).unwrap().expect("Key not found").key()
+            );
+        }
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn validate_reports_broken_links() {
+        let base_dir = "./test19";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(16, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 16);
+
+        let mut tree = BTree::<u64>::new(manager.clone()).unwrap();
+        for key in 0..100 {
+            tree.insert(leaf_entry(key)).unwrap();
+        }
+        assert_eq!(Ok(()), tree.validate());
+
+        let first_leaf = tree.first_leaf().unwrap();
+        let page = manager.lock().unwrap().find_page(first_leaf).unwrap();
+
+        // Break the sibling chain
+        let next_leaf = {
+            let mut page = page.write().unwrap();
+            let mut node = page.as_index_node_mut::<u64>();
+            let next_leaf = node.read_next_leaf_page_id();
+            node.write_next_leaf_page_id(NULL_PAGE_ID);
+            next_leaf
+        };
+
+        let error = tree.validate().expect_err("Expected a broken chain");
+        assert!(error.contains(&format!("Leaf {}", first_leaf)));
+
+        page.write()
+            .unwrap()
+            .as_index_node_mut::<u64>()
+            .write_next_leaf_page_id(next_leaf);
+
+        // Point the leaf at the wrong parent
+        let mut header = page.read().unwrap().read_header();
+        let parent_page_id = header.parent_page_id;
+        header.parent_page_id = 12345;
+        page.write().unwrap().write_header(header);
+
+        let error = tree.validate().expect_err("Expected a wrong parent");
+        assert!(error.contains(&format!(
+            "Page {} has parent 12345 but is a child of {}",
+            first_leaf, parent_page_id
+        )));
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn insert_key_larger_than_page() {
+        let base_dir = "./test26";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(16, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 24);
+
+        let mut tree = BTree::<String>::new(manager.clone()).unwrap();
+
+        // Both large keys share the prefix kept in the leaf
+        let large_key = "x".repeat(5000);
+        let other_large_key = "x".repeat(4999) + "y";
+
+        for (i, key) in ["b", "a", "z"].iter().enumerate() {
+            tree.insert(KeyEntry::leaf(key.to_string(), i as PageId, i as u16))
+                .unwrap();
+        }
+        tree.insert(KeyEntry::leaf(large_key.clone(), 77, 7))
+            .unwrap();
+        tree.insert(KeyEntry::leaf(other_large_key.clone(), 88, 8))
+            .unwrap();
+
+        let entry = tree.search(&large_key).unwrap().expect("Key not found");
+        assert_eq!(large_key, *entry.key());
+        assert_eq!(77, entry.page_id());
+        assert_eq!(Some(7), entry.slot_index());
+
+        let entry = tree
+            .search(&other_large_key)
+            .unwrap()
+            .expect("Key not found");
+        assert_eq!(other_large_key, *entry.key());
+        assert_eq!(88, entry.page_id());
+
+        assert_eq!(1, tree.search(&"a".to_string()).unwrap().unwrap().page_id());
+        assert!(tree.search(&"x".repeat(6000)).unwrap().is_none());
+        assert_eq!(Ok(()), tree.validate());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn remove_all_matches_full_large_keys() {
+        let base_dir = "./test73";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(16, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 24);
+
+        let mut tree = BTree::<String>::new(manager.clone()).unwrap();
+
+        // The inline key equals the prefix the leaf keeps for the large key
+        let large_key = "x".repeat(5000);
+        let prefix = BTree::<String>::stored_key(&large_key);
+        assert_ne!(large_key, prefix);
+
+        tree.insert(KeyEntry::leaf(prefix.clone(), 1, 1)).unwrap();
+        tree.insert(KeyEntry::leaf(large_key.clone(), 2, 2))
+            .unwrap();
+        let free_pages = manager.lock().unwrap().free_page_count();
+
+        assert_eq!(1, tree.remove_all(&large_key).unwrap());
+        assert!(tree.search(&large_key).unwrap().is_none());
+        assert_eq!(1, tree.search(&prefix).unwrap().unwrap().page_id());
+        assert!(manager.lock().unwrap().free_page_count() > free_pages);
+
+        tree.insert(KeyEntry::leaf(large_key.clone(), 2, 2))
+            .unwrap();
+        assert_eq!(1, tree.remove_all(&prefix).unwrap());
+        assert!(tree.search(&prefix).unwrap().is_none());
+        assert_eq!(2, tree.search(&large_key).unwrap().unwrap().page_id());
+        assert_eq!(Ok(()), tree.validate());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn named_tree_root_recovered_from_catalog() {
+        let base_dir = "./test31";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(16, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 16);
+
+        assert!(BTree::<u64>::open_named(manager.clone(), "orders")
+            .unwrap()
+            .is_none());
+
+        let tree = BTree::<u64>::new_named(manager.clone(), "orders").unwrap();
+        let original_root = tree.root_page_id();
+        assert_eq!(
+            Some(original_root),
+            manager.lock().unwrap().get_root("orders")
+        );
+
+        for key in 0..100 {
+            tree.insert(leaf_entry(key)).unwrap();
+        }
+        let root_page_id = tree.root_page_id();
+        assert_ne!(original_root, root_page_id);
+        drop(tree);
+
+        let mut reopened = BTree::<u64>::open_named(manager.clone(), "orders")
+            .unwrap()
+            .unwrap();
+        assert_eq!(root_page_id, reopened.root_page_id());
+        assert_eq!(Ok(()), reopened.validate());
+        assert_eq!(42, *reopened.search(&42).unwrap().unwrap().key());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn filter_even_keys_in_range() {
+        let base_dir = "./test33";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(16, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 16);
+
+        let mut tree = BTree::<u64>::new(manager).unwrap();
+        for key in 0..200 {
+            tree.insert(leaf_entry(key)).unwrap();
+        }
+
+        let is_even = |entry: &KeyEntry<u64>| entry.key().is_multiple_of(2);
+
+        let keys: Vec<u64> = tree
+            .filter_range(Bound::Included(20), Bound::Excluded(60), is_even)
+            .unwrap()
+            .iter()
+            .map(|entry| *entry.key())
+            .collect();
+        assert_eq!((20..60).step_by(2).collect::<Vec<u64>>(), keys);
+
+        let keys: Vec<u64> = tree
+            .filter(Bound::Excluded(190), is_even)
+            .unwrap()
+            .iter()
+            .map(|entry| *entry.key())
+            .collect();
+        assert_eq!(vec![192, 194, 196, 198], keys);
+
+        let entries = tree
+            .filter_range(Bound::Unbounded, Bound::Included(4), is_even)
+            .unwrap();
+        let keys: Vec<u64> = entries.iter().map(|entry| *entry.key()).collect();
+        assert_eq!(vec![0, 2, 4], keys);
+        assert_eq!(40, entries[2].page_id());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn stats_for_known_trees() {
+        let base_dir = "./test34";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(16, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 16);
+
+        let mut single_leaf = BTree::<u64>::new(manager.clone()).unwrap();
+        for key in [4, 2, 9] {
+            single_leaf.insert(leaf_entry(key)).unwrap();
+        }
+        assert_eq!(
+            TreeStats {
+                height: 1,
+                internal_pages: 0,
+                leaf_pages: 1,
+                total_keys: 3,
+            },
+            single_leaf.stats().unwrap()
+        );
+
+        let root_page_id =
+            build_two_level_tree(&manager, &[vec![1, 2], vec![5, 6, 7], vec![10, 11]]);
+        let mut two_levels = BTree::<u64>::open(manager.clone(), root_page_id).unwrap();
+        assert_eq!(
+            TreeStats {
+                height: 2,
+                internal_pages: 1,
+                leaf_pages: 3,
+                total_keys: 7,
+            },
+            two_levels.stats().unwrap()
+        );
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn sequential_inserts_split_internal_nodes() {
+        let base_dir = "./test37";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(256, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 256);
+
+        let mut tree = BTree::<u64>::new(manager.clone()).unwrap();
+        for key in 0..2000 {
+            tree.insert(leaf_entry(key)).unwrap();
+        }
+
+        assert_eq!(Ok(()), tree.validate());
+
+        let stats = tree.stats().unwrap();
+        assert!(
+            stats.height >= 3,
+            "Expected internal splits, got {:?}",
+            stats
+        );
+        assert_eq!(2000, stats.total_keys);
+
+        for key in [0, 999, 1999] {
+            assert_eq!(
+                key * 10,
+                tree.search(&key).unwrap().expect("Key not found").page_id()
+            );
+        }
+
+        let all_keys: Vec<u64> = leaf_chain(&manager, &tree).into_iter().flatten().collect();
+        assert_eq!((0..2000).collect::<Vec<u64>>(), all_keys);
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn len_tracks_inserts_and_removes() {
+        let base_dir = "./test38";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(32, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 32);
+
+        let mut tree = BTree::<u64>::new(manager.clone()).unwrap();
+        assert!(tree.is_empty());
+
+        for key in 0..150 {
+            tree.insert(leaf_entry(key % 100)).unwrap();
+        }
+        assert_eq!(150, tree.len());
+
+        assert_eq!(2, tree.remove_all(&10).unwrap());
+        assert_eq!(1, tree.remove_all(&99).unwrap());
+        assert_eq!(0, tree.remove_all(&1000).unwrap());
+        assert_eq!(147, tree.len());
+        assert_eq!(tree.count_entries().unwrap(), tree.len());
+
+        // Reopening recounts from the pages
+        let reopened = BTree::<u64>::open(manager.clone(), tree.root_page_id()).unwrap();
+        assert_eq!(147, reopened.len());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn reopened_len_skips_tombstones() {
+        let manager = Arc::new(Mutex::new(PageManager::in_memory(16)));
+        manager.lock().unwrap().add_empty_pages("index.db", 16);
+
+        let mut tree = BTree::<u64>::new(manager.clone()).unwrap();
+        let n_keys = leaf_capacity() as u64 * 2;
+        for key in 0..n_keys {
+            tree.insert(leaf_entry(key)).unwrap();
+        }
+        assert_eq!(1, tree.remove_all(&0).unwrap());
+
+        // Tombstone every key divisible by 3 in place
+        let mut n_tombstoned = 0;
+        let mut leaf_page_id = tree.first_leaf().unwrap();
+        while leaf_page_id != NULL_PAGE_ID {
+            let leaf = manager.lock().unwrap().find_page(leaf_page_id).unwrap();
+            let mut leaf = leaf.write().unwrap();
+            let mut node = leaf.as_index_node_mut::<u64>();
+            let keys: Vec<u64> = node.keys_iter().filter(|key| key % 3 == 0).collect();
+            for key in keys {
+                assert!(node.mark_deleted(&key));
+                n_tombstoned += 1;
+            }
+            leaf_page_id = node.read_next_leaf_page_id();
+        }
+        assert!(n_tombstoned > 0);
+
+        let live = n_keys - 1 - n_tombstoned;
+        let reopened = BTree::<u64>::open(manager.clone(), tree.root_page_id()).unwrap();
+        assert_eq!(live, reopened.len());
+        assert_eq!(live, reopened.count_entries().unwrap());
+    }
+
+    #[test]
+    pub fn vacuum_sweeps_every_leaf() {
+        let base_dir = "./test48";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(16, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 16);
+
+        let mut tree = BTree::<u64>::new(manager.clone()).unwrap();
+        let n_keys = leaf_capacity() as u64 * 2;
+        for key in 0..n_keys {
+            tree.insert(leaf_entry(key)).unwrap();
+        }
+        assert!(leaf_chain(&manager, &tree).len() > 1);
+
+        // Tombstone the odd keys in place
+        let mut leaf_page_id = tree.first_leaf().unwrap();
+        while leaf_page_id != NULL_PAGE_ID {
+            let leaf = manager.lock().unwrap().find_page(leaf_page_id).unwrap();
+            let mut leaf = leaf.write().unwrap();
+            let mut node = leaf.as_index_node_mut::<u64>();
+            let odd_keys: Vec<u64> = node.keys_iter().filter(|key| key % 2 == 1).collect();
+            for key in odd_keys {
+                assert!(node.mark_deleted(&key));
+            }
+            leaf_page_id = node.read_next_leaf_page_id();
+        }
+
+        assert_eq!(n_keys as usize / 2, tree.vacuum().unwrap());
+        assert_eq!(0, tree.vacuum().unwrap());
+        assert_eq!(n_keys / 2, tree.len());
+
+        let keys: Vec<u64> = leaf_chain(&manager, &tree).into_iter().flatten().collect();
+        let even_keys: Vec<u64> = (0..n_keys).filter(|key| key % 2 == 0).collect();
+        assert_eq!(even_keys, keys);
+        assert_eq!(Ok(()), tree.validate());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn contains_present_and_absent_keys() {
+        let base_dir = "./test50";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(16, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 16);
+
+        let tree = BTree::<u64>::new(manager.clone()).unwrap();
+        let n_keys = leaf_capacity() as u64 * 2;
+        for key in (0..n_keys).map(|key| key * 2) {
+            tree.insert(leaf_entry(key)).unwrap();
+        }
+        assert!(leaf_chain(&manager, &tree).len() > 1);
+
+        for key in 0..n_keys * 2 {
+            assert_eq!(key % 2 == 0, tree.contains(&key).unwrap(), "key {}", key);
+        }
+        assert!(!tree.contains(&(n_keys * 2)).unwrap());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn nan_keys_are_found_and_removed() {
+        let manager = Arc::new(Mutex::new(PageManager::in_memory(8)));
+        manager.lock().unwrap().add_empty_pages("index.db", 8);
+
+        let mut tree = BTree::<f64>::new(manager.clone()).unwrap();
+        for (i, key) in [1.5, f64::NAN, -2.0, 7.25].into_iter().enumerate() {
+            tree.insert(KeyEntry::leaf(key, i as u64, 0)).unwrap();
+        }
+
+        assert!(tree.contains(&f64::NAN).unwrap());
+        assert_eq!(
+            Some(1),
+            tree.search(&f64::NAN).unwrap().map(|entry| entry.page_id())
+        );
+
+        assert_eq!(1, tree.remove_all(&f64::NAN).unwrap());
+        assert!(!tree.contains(&f64::NAN).unwrap());
+        assert!(tree.search(&f64::NAN).unwrap().is_none());
+        assert_eq!(3, tree.iter().unwrap().count());
+    }
+
+    #[test]
+    pub fn insert_unique_rejects_duplicates() {
+        let base_dir = "./test51";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(8, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 8);
+
+        let mut tree = BTree::<u64>::new(manager.clone()).unwrap();
+        tree.insert(leaf_entry(1)).unwrap();
+
+        assert_eq!(Ok(()), tree.insert_unique(leaf_entry(2)).unwrap());
+        assert_eq!(
+            Err(DuplicateKey(2)),
+            tree.insert_unique(leaf_entry(2)).unwrap()
+        );
+        assert_eq!(
+            Err(DuplicateKey(1)),
+            tree.insert_unique(leaf_entry(1)).unwrap()
+        );
+
+        // The plain insert still allows duplicates
+        tree.insert(leaf_entry(2)).unwrap();
+        assert_eq!(3, tree.len());
+        assert_eq!(Ok(()), tree.validate());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn upsert_inserts_then_updates() {
+        let base_dir = "./test52";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(16, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 16);
+
+        let mut tree = BTree::<u64>::new(manager.clone()).unwrap();
+        let n_keys = leaf_capacity() as u64 * 2;
+        for key in 0..n_keys {
+            assert!(tree.upsert(leaf_entry(key)).unwrap());
+        }
+        assert!(leaf_chain(&manager, &tree).len() > 1);
+
+        assert!(tree.upsert(KeyEntry::leaf(n_keys, 5, 6)).unwrap());
+        assert!(!tree.upsert(KeyEntry::leaf(n_keys, 7, 8)).unwrap());
+        assert!(!tree.upsert(KeyEntry::leaf(3, 70, 80)).unwrap());
+
+        let entry = tree.search(&n_keys).unwrap().unwrap();
+        assert_eq!((7, Some(8)), (entry.page_id(), entry.slot_index()));
+        let entry = tree.search(&3).unwrap().unwrap();
+        assert_eq!((70, Some(80)), (entry.page_id(), entry.slot_index()));
+
+        assert_eq!(n_keys + 1, tree.len());
+        assert_eq!(n_keys + 1, tree.count_entries().unwrap());
+        assert_eq!(Ok(()), tree.validate());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn upsert_large_key() {
+        let base_dir = "./test53";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(16, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 16);
+
+        let mut tree = BTree::<String>::new(manager.clone()).unwrap();
+        let large_key = "k".repeat(600);
+        let other_large_key = format!("{}z", large_key);
+
+        assert!(tree
+            .upsert(KeyEntry::leaf(large_key.clone(), 1, 1))
+            .unwrap());
+        assert!(tree
+            .upsert(KeyEntry::leaf(other_large_key.clone(), 2, 2))
+            .unwrap());
+
+        // Updating keeps the key's overflow chain instead of writing a new one
+        let free_pages = manager.lock().unwrap().free_page_count();
+        assert!(!tree
+            .upsert(KeyEntry::leaf(other_large_key.clone(), 3, 3))
+            .unwrap());
+        assert_eq!(free_pages, manager.lock().unwrap().free_page_count());
+
+        let entry = tree.search(&large_key).unwrap().unwrap();
+        assert_eq!((1, Some(1)), (entry.page_id(), entry.slot_index()));
+        let entry = tree.search(&other_large_key).unwrap().unwrap();
+        assert_eq!((3, Some(3)), (entry.page_id(), entry.slot_index()));
+        assert_eq!(2, tree.len());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn upsert_finds_duplicates_in_next_leaf() {
+        let base_dir = "./test74";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(16, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 16);
+
+        let mut tree = BTree::<u64>::new(manager.clone()).unwrap();
+
+        // Split among the duplicates, as in remove_all_across_split
+        let capacity = leaf_capacity() as u64;
+        for key in 0..capacity / 2 - 2 {
+            tree.insert(leaf_entry(key)).unwrap();
+        }
+        for _ in 0..5 {
+            tree.insert(leaf_entry(1000)).unwrap();
+        }
+        for key in 2000..2000 + capacity - capacity / 2 - 2 {
+            tree.insert(leaf_entry(key)).unwrap();
+        }
+
+        // Tombstone the duplicates in the first leaf so only the next leaf has live ones
+        let first_leaf_page_id = tree.first_leaf().unwrap();
+        let n_first = leaf_chain(&manager, &tree)[0]
+            .iter()
+            .filter(|key| **key == 1000)
+            .count();
+        assert!(n_first > 0 && n_first < 5);
+        {
+            let leaf = manager
+                .lock()
+                .unwrap()
+                .find_page(first_leaf_page_id)
+                .unwrap();
+            let mut leaf = leaf.write().unwrap();
+            for _ in 0..n_first {
+                assert!(leaf.as_index_node_mut::<u64>().mark_deleted(&1000));
+            }
+        }
+
+        let len = tree.len();
+        assert!(!tree.upsert(KeyEntry::leaf(1000, 77, 7)).unwrap());
+        assert_eq!(len, tree.len());
+        assert_eq!(77, tree.search(&1000).unwrap().unwrap().page_id());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn root_leaf_purges_tombstones_before_splitting() {
+        let manager = Arc::new(Mutex::new(PageManager::in_memory(8)));
+        manager.lock().unwrap().add_empty_pages("index.db", 8);
+
+        let mut tree = BTree::<u64>::new(manager.clone()).unwrap();
+        let capacity = leaf_capacity() as u64;
+        for key in 0..capacity {
+            tree.insert(leaf_entry(key)).unwrap();
+        }
+
+        let root_page_id = tree.root_page_id();
+        {
+            let root = manager.lock().unwrap().find_page(root_page_id).unwrap();
+            let mut root = root.write().unwrap();
+            let mut node = root.as_index_node_mut::<u64>();
+            assert!(node.mark_deleted(&0));
+            assert!(node.mark_deleted(&1));
+        }
+
+        // The purge makes room, so the root stays a leaf
+        tree.insert(leaf_entry(capacity)).unwrap();
+        tree.insert(leaf_entry(capacity + 1)).unwrap();
+        assert_eq!(root_page_id, tree.root_page_id());
+        assert_eq!(
+            vec![(2..capacity + 2).collect::<Vec<_>>()],
+            leaf_chain(&manager, &tree)
+        );
+
+        // Once it does split, the new root's separators are live keys
+        {
+            let root = manager.lock().unwrap().find_page(root_page_id).unwrap();
+            let mut root = root.write().unwrap();
+            assert!(root.as_index_node_mut::<u64>().mark_deleted(&2));
+        }
+        tree.insert(leaf_entry(capacity + 2)).unwrap();
+        tree.insert(leaf_entry(capacity + 3)).unwrap();
+        assert_ne!(root_page_id, tree.root_page_id());
+
+        let root = manager
+            .lock()
+            .unwrap()
+            .find_page(tree.root_page_id())
+            .unwrap();
+        let root = root.read().unwrap();
+        let root = root.as_index_node::<u64>();
+        assert!(!root.is_leaf());
+        assert_eq!(3, *root.read_key_node(0).key());
+        assert_eq!(
+            (3..capacity + 4).collect::<Vec<_>>(),
+            leaf_chain(&manager, &tree).concat()
+        );
+        assert_eq!(Ok(()), tree.validate());
+    }
+
+    #[test]
+    pub fn upsert_into_full_shared_leaf() {
+        let manager = Arc::new(Mutex::new(PageManager::in_memory(8)));
+        manager.lock().unwrap().add_empty_pages("index.db", 8);
+
+        let mut tree = BTree::<u64>::new(manager.clone()).unwrap();
+        {
+            let root = manager
+                .lock()
+                .unwrap()
+                .find_page(tree.root_page_id())
+                .unwrap();
+            let mut root = root.write().unwrap();
+            root.as_index_node_mut::<u64>()
+                .set_shared_page_id(Some(9))
+                .unwrap();
+        }
+
+        let mut n_keys = 0;
+        while leaf_chain(&manager, &tree).len() == 1 {
+            tree.insert(KeyEntry::leaf(n_keys, 9, n_keys as u16))
+                .unwrap();
+            n_keys += 1;
+        }
+        tree.remove_all(&(n_keys - 1)).unwrap();
+        assert_eq!(1, leaf_chain(&manager, &tree).len());
+
+        // No room for the entry's own page id, so the leaf splits instead
+        assert!(!tree.upsert(KeyEntry::leaf(0, 5, 1)).unwrap());
+        assert_eq!(2, leaf_chain(&manager, &tree).len());
+        let entry = tree.search(&0).unwrap().unwrap();
+        assert_eq!((5, Some(1)), (entry.page_id(), entry.slot_index()));
+        assert_eq!(n_keys - 1, tree.len());
+        assert_eq!(Ok(()), tree.validate());
+    }
+
+    #[test]
+    pub fn fragmentation_report_ranks_leaves() {
+        let base_dir = "./test55";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(16, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 16);
+
+        let tree = BTree::<u64>::new(manager.clone()).unwrap();
+        for key in 0..leaf_capacity() as u64 * 2 {
+            tree.insert(leaf_entry(key)).unwrap();
+        }
+
+        // Punch more holes in the last leaf than the first
+        let first_leaf = tree.first_leaf().unwrap();
+        let last_leaf = tree.last_leaf().unwrap();
+        for (leaf_page_id, n_holes) in [(first_leaf, 1), (last_leaf, 3)] {
+            let leaf = manager.lock().unwrap().find_page(leaf_page_id).unwrap();
+            let mut leaf = leaf.write().unwrap();
+            for _ in 0..n_holes {
+                leaf.as_index_node_mut::<u64>().remove_slot_in_place(0);
+            }
+        }
+
+        let report = tree.fragmentation_report().unwrap();
+        assert_eq!(leaf_chain(&manager, &tree).len(), report.len());
+        assert_eq!(last_leaf, report[0].0);
+        assert_eq!(first_leaf, report[1].0);
+        assert!(report[0].1 > report[1].1);
+        assert!(report[2..].iter().all(|(_, ratio)| *ratio == 0.0));
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn partition_scan_covers_every_key_once() {
+        let base_dir = "./test59";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(128, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 128);
+
+        let mut tree = BTree::<u64>::new(manager.clone()).unwrap();
+        for key in 0..1000 {
+            tree.insert(leaf_entry(key)).unwrap();
+        }
+
+        let ranges = tree.partition_scan(4).unwrap();
+        assert_eq!(4, ranges.len());
+        assert_eq!(Bound::Unbounded, ranges[0].start);
+        assert_eq!(Bound::Unbounded, ranges[3].end);
+
+        let partitions: Vec<Vec<u64>> = thread::scope(|scope| {
+            let handles: Vec<_> = ranges
+                .iter()
+                .map(|range| {
+                    let tree = &tree;
+                    scope.spawn(move || {
+                        tree.scan_range(range.clone())
+                            .unwrap()
+                            .map(|entry| *entry.unwrap().key())
+                            .collect()
+                    })
+                })
+                .collect();
+
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        for (range, keys) in ranges.iter().zip(&partitions) {
+            assert!(!keys.is_empty());
+            assert!(keys.iter().all(|key| range.contains(key)));
+        }
+
+        let all_keys: Vec<u64> = partitions.into_iter().flatten().collect();
+        assert_eq!((0..1000).collect::<Vec<_>>(), all_keys);
+
+        // A single leaf can't be split
+        let mut small = BTree::<u64>::new(manager.clone()).unwrap();
+        small.insert(leaf_entry(1)).unwrap();
+        assert_eq!(
+            vec![LeafRange {
+                start: Bound::Unbounded,
+                end: Bound::Unbounded
+            }],
+            small.partition_scan(4).unwrap()
+        );
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn estimate_count_close_to_true_count() {
+        let base_dir = "./test60";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(256, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 256);
+
+        // Scattered inserts so leaves fill up evenly
+        let mut tree = BTree::<u64>::new(manager.clone()).unwrap();
+        for i in 0..3000 {
+            tree.insert(leaf_entry(i * 7919 % 3000)).unwrap();
+        }
+        assert!(tree.stats().unwrap().height > 2);
+
+        for (low, high) in [(0, 2999), (100, 2500), (1000, 1999), (40, 700)] {
+            let actual = high - low + 1;
+            let estimate = tree.estimate_count(&low, &high).unwrap();
+            let error = estimate.abs_diff(actual) as f64 / actual as f64;
+            assert!(error < 0.2, "{estimate} vs {actual} for [{low}, {high}]");
+        }
+
+        // Within one leaf the count is exact
+        assert_eq!(6, tree.estimate_count(&5, &10).unwrap());
+        assert_eq!(1, tree.estimate_count(&7, &7).unwrap());
+        assert_eq!(0, tree.estimate_count(&10, &5).unwrap());
+        assert_eq!(0, tree.estimate_count(&5000, &6000).unwrap());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn split_trace_reports_middle_separator() {
+        let manager = Arc::new(Mutex::new(PageManager::in_memory(8)));
+        manager.lock().unwrap().add_empty_pages("index.db", 8);
+
+        let splits = Arc::new(Mutex::new(vec![]));
+        let mut tree = BTree::<u64>::new(manager.clone()).unwrap();
+        tree.set_split_trace(Some(Box::new({
+            let splits = splits.clone();
+            move |split| splits.lock().unwrap().push(split.clone())
+        })));
+
+        // One more key than fits splits the full leaf in half
+        let capacity = leaf_capacity() as u64;
+        for key in 0..=capacity {
+            tree.insert(leaf_entry(key)).unwrap();
+        }
+
+        let splits = splits.lock().unwrap();
+        assert_eq!(1, splits.len());
+        assert_eq!(capacity / 2, splits[0].separator);
+        assert_eq!(capacity as usize / 2, splits[0].left_count);
+        assert_eq!(
+            capacity as usize + 1,
+            splits[0].left_count + splits[0].right_count
+        );
+        assert_eq!(tree.last_leaf().unwrap(), splits[0].right_page_id);
+    }
+
+    #[test]
+    pub fn emptied_leaf_is_reclaimed() {
+        let base_dir = "./test61";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(16, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 16);
+
+        // Full leaves, so emptying the middle one can't merge it away first
+        let mut tree = BTree::<u64>::new(manager.clone()).unwrap();
+        tree.set_split_policy(SplitPolicy::RightHeavy);
+        let capacity = leaf_capacity() as u64;
+        for key in 0..capacity * 3 {
+            tree.insert(leaf_entry(key)).unwrap();
+        }
+
+        // Top up the middle leaf's neighbours so it can't merge into either of them
+        let leaves = leaf_chain(&manager, &tree);
+        assert!(leaves.len() >= 3);
+        tree.insert(leaf_entry(leaves[0][1])).unwrap();
+        tree.insert(leaf_entry(leaves[2][1])).unwrap();
+
+        let mut leaves = leaf_chain(&manager, &tree);
+        let first_leaf = tree.first_leaf().unwrap();
+        let middle_leaf = manager
+            .lock()
+            .unwrap()
+            .find_page(first_leaf)
+            .unwrap()
+            .read()
+            .unwrap()
+            .as_index_node::<u64>()
+            .read_next_leaf_page_id();
+
+        let free_pages = manager.lock().unwrap().free_page_count();
+        for key in leaves.remove(1) {
+            assert_eq!(1, tree.remove_all(&key).unwrap());
+        }
+
+        assert_eq!(free_pages + 1, manager.lock().unwrap().free_page_count());
+        assert_eq!(leaves, leaf_chain(&manager, &tree));
+        assert_eq!(Ok(()), tree.validate());
+        assert_eq!(
+            middle_leaf,
+            manager
+                .lock()
+                .unwrap()
+                .next_free_page()
+                .unwrap()
+                .read()
+                .unwrap()
+                .page_id
+        );
+
+        // An emptied root leaf stays as the root
+        let mut single = BTree::<u64>::new(manager.clone()).unwrap();
+        let root_page_id = single.root_page_id();
+        single.insert(leaf_entry(1)).unwrap();
+        single.remove_all(&1).unwrap();
+        assert_eq!(root_page_id, single.root_page_id());
+        assert!(single.is_empty());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn presplit_creates_leaf_per_range() {
+        let manager = Arc::new(Mutex::new(PageManager::in_memory(16)));
+        manager.lock().unwrap().add_empty_pages("index.db", 16);
+
+        let mut tree = BTree::<u64>::presplit(manager.clone(), &[100, 200, 300]).unwrap();
+        assert_eq!(
+            TreeStats {
+                height: 2,
+                internal_pages: 1,
+                leaf_pages: 4,
+                total_keys: 0,
+            },
+            tree.stats().unwrap()
+        );
+
+        for key in (0..400).step_by(10) {
+            tree.insert(leaf_entry(key)).unwrap();
+        }
+
+        let leaves = leaf_chain(&manager, &tree);
+        assert_eq!(4, leaves.len());
+        for (i, leaf) in leaves.iter().enumerate() {
+            let range = i as u64 * 100..(i as u64 + 1) * 100;
+            assert_eq!(range.step_by(10).collect::<Vec<_>>(), *leaf);
+        }
+        assert_eq!(Ok(()), tree.validate());
+
+        let single = BTree::<u64>::presplit(manager.clone(), &[]).unwrap();
+        assert_eq!(1, leaf_chain(&manager, &single).len());
+    }
+
+    #[test]
+    pub fn insert_at_path_after_missed_search() {
+        let manager = Arc::new(Mutex::new(PageManager::in_memory(32)));
+        manager.lock().unwrap().add_empty_pages("index.db", 32);
+
+        let mut tree = BTree::<u64>::new(manager.clone()).unwrap();
+        let capacity = leaf_capacity() as u64;
+        for key in 0..capacity * 3 {
+            tree.insert(leaf_entry(key * 2)).unwrap();
+        }
+
+        let (found, path) = tree.search_with_path(&101).unwrap();
+        assert!(found.is_none());
+        assert_eq!(tree.stats().unwrap().height, path.page_ids().len());
+        assert_eq!(tree.root_page_id(), path.page_ids()[0]);
+
+        let leaf_page_id = path.leaf_page_id().unwrap();
+        let leaf_len = |manager: &SharedPageManager| {
+            let leaf = manager.lock().unwrap().find_page(leaf_page_id).unwrap();
+            let len = leaf.read().unwrap().as_index_node::<u64>().read_n_slots();
+            len
+        };
+        let before = leaf_len(&manager);
+
+        tree.insert_at_path(leaf_entry(101), &path).unwrap();
+        assert_eq!(before + 1, leaf_len(&manager));
+        assert_eq!(
+            Some(1010),
+            tree.search(&101).unwrap().map(|entry| entry.page_id())
+        );
+
+        // Splits after the search make the path stale, the insert still lands right
+        let (_, stale_path) = tree.search_with_path(&103).unwrap();
+        for key in 0..capacity * 2 {
+            tree.insert(leaf_entry(100 + key % 3)).unwrap();
+        }
+        tree.insert_at_path(leaf_entry(103), &stale_path).unwrap();
+        assert_eq!(
+            Some(1030),
+            tree.search(&103).unwrap().map(|entry| entry.page_id())
+        );
+
+        // Past the last key of the last leaf
+        let (_, path) = tree.search_with_path(&1_000_000).unwrap();
+        tree.insert_at_path(leaf_entry(1_000_000), &path).unwrap();
+
+        let keys: Vec<u64> = leaf_chain(&manager, &tree).into_iter().flatten().collect();
+        assert!(keys.is_sorted());
+        assert_eq!(Some(&1_000_000), keys.last());
+        assert_eq!(keys.len() as u64, tree.len());
+        assert_eq!(Ok(()), tree.validate());
+    }
+
+    // A fixed size key padded out to WIDTH bytes, which can't be truncated
+    #[derive(Clone, Debug, PartialEq, PartialOrd)]
+    struct WideKey<const WIDTH: usize>(u64);
+
+    impl<const WIDTH: usize> DbColumn for WideKey<WIDTH> {
+        fn from_bytes(bytes: &[u8], start: usize) -> Self {
+            WideKey(read_u64(bytes, start))
+        }
+
+        fn write_to(&self, buf: &mut [u8]) -> usize {
+            write_u64(buf, 0, self.0);
+            buf[8..WIDTH].fill(0);
+            WIDTH
+        }
+
+        fn len(&self) -> usize {
+            WIDTH
+        }
+
+        fn cmp_key(&self, other: &Self) -> Ordering {
+            self.0.cmp(&other.0)
+        }
+    }
+
+    const WIDEST: usize = MAX_ENTRY_BYTES - TUPLE_HEADER_SIZE;
+
+    #[test]
+    pub fn keys_filling_half_a_page_still_split() {
+        let manager = Arc::new(Mutex::new(PageManager::in_memory(128)));
+        manager.lock().unwrap().add_empty_pages("index.db", 128);
+
+        let mut tree = BTree::<WideKey<WIDEST>>::new(manager.clone()).unwrap();
+        for i in 0..40u64 {
+            let key = i * 7 % 40;
+            assert_eq!(Ok(()), tree.insert(KeyEntry::leaf(WideKey(key), key, 0)));
+        }
+
+        let keys: Vec<u64> = tree
+            .iter()
+            .unwrap()
+            .map(|entry| entry.unwrap().key().0)
+            .collect();
+        assert_eq!((0..40).collect::<Vec<_>>(), keys);
+        assert_eq!(
+            Some(13),
+            tree.search(&WideKey(13))
+                .unwrap()
+                .map(|entry| entry.page_id())
+        );
+        assert_eq!(Ok(()), tree.validate());
+    }
+
+    #[test]
+    pub fn key_too_large_for_two_per_page_is_refused() {
+        let manager = Arc::new(Mutex::new(PageManager::in_memory(16)));
+        manager.lock().unwrap().add_empty_pages("index.db", 16);
+
+        let tree = BTree::<WideKey<{ WIDEST + 1 }>>::new(manager.clone()).unwrap();
+        for key in 0..3u64 {
+            assert_eq!(
+                Err(YarddError::KeyTooLarge {
+                    len: MAX_ENTRY_BYTES + 1,
+                    max: MAX_ENTRY_BYTES,
+                }),
+                tree.insert(KeyEntry::leaf(WideKey(key), key, 0))
+            );
+        }
+
+        let (_, path) = tree.search_with_path(&WideKey(0)).unwrap();
+        assert!(matches!(
+            tree.insert_at_path(KeyEntry::leaf(WideKey(0), 0, 0), &path),
+            Err(YarddError::KeyTooLarge { .. })
+        ));
+
+        assert_eq!(0, tree.iter().unwrap().count());
+        assert!(!tree.contains(&WideKey(0)).unwrap());
+    }
+
+    #[test]
+    pub fn insert_fails_on_key_too_large() {
+        let manager = Arc::new(Mutex::new(PageManager::in_memory(4)));
+        manager.lock().unwrap().add_empty_pages("index.db", 4);
+
+        let tree = BTree::<WideKey<1000>>::new(manager.clone()).unwrap();
+        let error = tree.insert(KeyEntry::leaf(WideKey(1), 1, 0)).unwrap_err();
+        assert!(error.to_string().contains("larger than the"), "{}", error);
+    }
+
+    #[test]
+    pub fn compact_data_file_packs_live_tuples() {
+        // Free pages are handed out last in first, so the tree only takes index.db pages
+        let manager = Arc::new(Mutex::new(PageManager::in_memory(128)));
+        manager.lock().unwrap().add_empty_pages("table.db", 8);
+        manager.lock().unwrap().add_empty_pages("index.db", 96);
+
+        let mut tree = BTree::<u64>::new(manager.clone()).unwrap();
+        let table_pages = manager.lock().unwrap().pages_in_file("table.db");
+        let tuple = |key: u64| format!("tuple {key:>4} {}", "-".repeat(20)).into_bytes();
+
+        // A second index on the same file, keyed by large strings
+        let mut by_name = BTree::<String>::new(manager.clone()).unwrap();
+        let name = |key: u64| format!("{key:>4}{}", "n".repeat(300));
+
+        let keys: Vec<u64> = (0..150).collect();
+        let n_data_pages = keys.chunks(20).len();
+        for (page_id, keys) in table_pages.iter().zip(keys.chunks(20)) {
+            let page = manager.lock().unwrap().find_page(*page_id).unwrap();
+            let mut page = page.write().unwrap();
+            let mut data_page = DataPageMut::init_page(&mut page);
+
+            for key in keys {
+                let slot_index = data_page.append_tuple(&tuple(*key)).unwrap();
+                tree.insert(KeyEntry::leaf(*key, *page_id, slot_index))
+                    .unwrap();
+                if key % 4 == 0 {
+                    by_name
+                        .insert(KeyEntry::leaf(name(*key), *page_id, slot_index))
+                        .unwrap();
+                }
+            }
+        }
+
+        for key in (0..150).filter(|key| key % 2 == 1) {
+            assert_eq!(1, tree.remove_all(&key).unwrap());
+        }
+
+        let released = tree
+            .compact_data_file("table.db", &mut [&mut by_name])
+            .unwrap();
+        assert!(n_data_pages - released <= n_data_pages / 2);
+
+        let scanned: Vec<_> = DataFileScan::new(manager.clone(), "table.db")
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let used_pages: std::collections::BTreeSet<PageId> =
+            scanned.iter().map(|(page_id, _, _)| *page_id).collect();
+        assert_eq!(n_data_pages - released, used_pages.len());
+        assert_eq!(75, scanned.len());
+
+        for key in 0..150 {
+            let entry = tree.search(&key).unwrap();
+            if key % 2 == 1 {
+                assert!(entry.is_none());
+                continue;
+            }
+
+            let entry = entry.unwrap();
+            let page = manager.lock().unwrap().find_page(entry.page_id()).unwrap();
+            let page = page.read().unwrap();
+            let stored = page
+                .as_data_page()
+                .read_tuple(entry.slot_index().unwrap())
+                .to_vec();
+            assert_eq!(tuple(key), stored);
+
+            if key % 4 == 0 {
+                let moved = by_name.search(&name(key)).unwrap().unwrap();
+                assert_eq!(
+                    (entry.page_id(), entry.slot_index()),
+                    (moved.page_id(), moved.slot_index())
+                );
+            }
+        }
+        tree.validate().unwrap();
+        by_name.validate().unwrap();
+    }
+
+    #[test]
+    pub fn search_reports_unreadable_pages() {
+        let base_dir = "./test69";
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(4, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 4);
+
+        let tree = BTree::<u64>::new(manager.clone()).unwrap();
+        for key in [5, 1, 3] {
+            tree.insert(leaf_entry(key)).unwrap();
+        }
+        assert_eq!(Some(3), tree.search(&3).unwrap().map(|entry| *entry.key()));
+
+        manager.lock().unwrap().flush_all().unwrap();
+        manager.lock().unwrap().evict_n(4).unwrap();
+        std::fs::remove_file(Path::new(base_dir).join("index.db")).unwrap();
+
+        assert!(tree.search(&3).is_err());
+
+        cleanup(base_dir);
+    }
+}
@@ -0,0 +1,62 @@
+use std::{collections::BTreeMap, mem::size_of};
+
+use crate::{
+    page::{Page, PageHeader, PageId, PageType, HEADER_SIZE, NULL_PAGE_ID, PAGE_MAGIC_NUMBER},
+    serialization_helpers::*,
+};
+
+// The catalog lives at the start of its own file so it can be found on restart
+pub const CATALOG_FILE: &str = "catalog.db";
+
+pub const CATALOG_COUNT_START: usize = HEADER_SIZE;
+pub const CATALOG_ENTRIES_START: usize = CATALOG_COUNT_START + size_of::<u16>();
+
+/// Writes the named index roots to the catalog page. Each entry is the
+/// name's length, the name and the root page id.
+pub fn write_roots(page: &mut Page, roots: &BTreeMap<String, PageId>) {
+    let page_id = page.page_id;
+    page.reset();
+    page.write_header(PageHeader {
+        magic_number: PAGE_MAGIC_NUMBER,
+        page_type: PageType::Catalog,
+        log_sequence_number: 0,
+        parent_page_id: NULL_PAGE_ID,
+        page_id,
+    });
+
+    let size: usize = roots
+        .keys()
+        .map(|name| size_of::<u16>() + name.len() + size_of::<PageId>())
+        .sum();
+    assert!(
+        CATALOG_ENTRIES_START + size <= page.page_size(),
+        "Catalog doesn't fit in one page"
+    );
+
+    let mut cursor = write_u16(&mut page.data, CATALOG_COUNT_START, roots.len() as u16);
+    for (name, root) in roots {
+        cursor = write_u16(&mut page.data, cursor, name.len() as u16);
+        cursor = write_bytes(&mut page.data, cursor, name.as_bytes());
+        cursor = write_u64(&mut page.data, cursor, *root);
+    }
+}
+
+pub fn read_roots(page: &Page) -> BTreeMap<String, PageId> {
+    let mut roots = BTreeMap::new();
+
+    let count = read_u16(&page.data, CATALOG_COUNT_START);
+    let mut cursor = CATALOG_ENTRIES_START;
+    for _ in 0..count {
+        let length = read_u16(&page.data, cursor) as usize;
+        cursor += size_of::<u16>();
+
+        let name = String::from_utf8(page.data[cursor..cursor + length].to_vec())
+            .expect("Invalid index name in catalog");
+        cursor += length;
+
+        roots.insert(name, read_u64(&page.data, cursor));
+        cursor += size_of::<PageId>();
+    }
+
+    roots
+}
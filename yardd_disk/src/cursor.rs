@@ -0,0 +1,611 @@
+use std::collections::VecDeque;
+
+use crate::{
+    btree::BTree,
+    disk_btree::{IndexPageRead, IndexPageReadSized, KeyEntry},
+    error::YarddError,
+    page::{DbColumn, Page, PageId, SlotIndex, NULL_PAGE_ID},
+};
+
+/// Where a scan stopped, so it can pick up from the same leaf later without
+/// descending from the root. The key is there to find the position again if
+/// the leaf has split or merged in the meantime.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Continuation<KeyType> {
+    pub leaf_page_id: PageId,
+    pub slot_index: SlotIndex,
+    pub key: KeyType,
+}
+
+/// A re-positionable position in a tree's leaf level.
+///
+/// The cursor sits between two entries: `next` returns the entry after it and
+/// `prev` the entry before it, moving the cursor past the returned entry. So
+/// `next` followed by `prev` returns the same entry twice.
+///
+/// At most one leaf is latched at a time and only for the duration of a call.
+/// The cursor remembers the key of the entry after it; if that key isn't
+/// where the cursor left it, e.g. because the leaf split, it seeks back to it.
+/// A call that fails to read a page leaves the cursor where it was.
+pub struct Cursor<'a, KeyType>
+where
+    KeyType: DbColumn,
+{
+    tree: &'a BTree<KeyType>,
+    leaf_page_id: PageId,
+    slot_index: u16,
+    // Stored key of the entry after the cursor, None at the end of the tree
+    anchor: Option<KeyType>,
+}
+
+// What a leaf looks like from a cursor position
+struct LeafView<KeyType>
+where
+    KeyType: DbColumn,
+{
+    n_slots: u16,
+    next_leaf_page_id: PageId,
+    prev_leaf_page_id: PageId,
+    entry: Option<KeyEntry<KeyType>>,
+}
+
+impl<'a, KeyType> Cursor<'a, KeyType>
+where
+    KeyType: DbColumn,
+{
+    /// Creates a cursor before the first entry of the tree.
+    pub fn new(tree: &'a BTree<KeyType>) -> Result<Self, YarddError> {
+        let mut cursor = Cursor {
+            tree,
+            leaf_page_id: tree.first_leaf()?,
+            slot_index: 0,
+            anchor: None,
+        };
+        cursor.settle(cursor.leaf_page_id, 0)?;
+
+        Ok(cursor)
+    }
+
+    /// Creates a cursor where an earlier one left off. If the saved leaf no longer
+    /// holds the key at the saved slot, the cursor seeks to the key instead, which
+    /// can revisit entries with an equal key.
+    pub fn resume(
+        tree: &'a BTree<KeyType>,
+        continuation: Continuation<KeyType>,
+    ) -> Result<Self, YarddError> {
+        let mut cursor = Cursor {
+            tree,
+            leaf_page_id: continuation.leaf_page_id,
+            slot_index: continuation.slot_index,
+            anchor: Some(continuation.key),
+        };
+        cursor.revalidate()?;
+
+        Ok(cursor)
+    }
+
+    /// The position to resume from, or None once the cursor is past the last entry.
+    pub fn continuation(&self) -> Option<Continuation<KeyType>> {
+        self.anchor.clone().map(|key| Continuation {
+            leaf_page_id: self.leaf_page_id,
+            slot_index: self.slot_index,
+            key,
+        })
+    }
+
+    /// Positions the cursor before the first entry >= key.
+    pub fn seek(&mut self, key: &KeyType) -> Result<(), YarddError> {
+        let stored_key = BTree::stored_key(key);
+        let leaf_page_id = self.tree.first_leaf_for_key(&stored_key)?;
+
+        let slot_index = self.with_leaf(leaf_page_id, |page| {
+            let node = page.as_index_node::<KeyType>();
+            node.iter()
+                .position(|entry| entry.key().cmp_key(&stored_key).is_ge())
+                .unwrap_or(node.read_n_slots() as usize) as u16
+        })?;
+
+        self.settle(leaf_page_id, slot_index)
+    }
+
+    /// Like `next`, but moving backwards.
+    pub fn prev(&mut self) -> Option<Result<KeyEntry<KeyType>, YarddError>> {
+        self.prev_entry().transpose()
+    }
+
+    fn prev_entry(&mut self) -> Result<Option<KeyEntry<KeyType>>, YarddError> {
+        self.revalidate()?;
+
+        let mut leaf_page_id = self.leaf_page_id;
+        let mut slot_index = self.slot_index;
+
+        // Walk back over empty leaves until there's an entry before the cursor
+        while slot_index == 0 {
+            leaf_page_id = self.view(leaf_page_id, None)?.prev_leaf_page_id;
+            if leaf_page_id == NULL_PAGE_ID {
+                return Ok(None);
+            }
+
+            slot_index = self.view(leaf_page_id, None)?.n_slots;
+        }
+
+        let view = self.view(leaf_page_id, Some(slot_index - 1))?;
+        let entry = view.entry.expect("Slot vanished from leaf");
+        let full_entry = self.tree.load_large_key(entry.clone())?;
+
+        self.leaf_page_id = leaf_page_id;
+        self.slot_index = slot_index - 1;
+        self.anchor = Some(entry.key().clone());
+
+        Ok(Some(full_entry))
+    }
+
+    // Moves to the given position, skipping to the next leaf while the
+    // position is past the end of a leaf
+    fn settle(&mut self, mut leaf_page_id: PageId, mut slot_index: u16) -> Result<(), YarddError> {
+        loop {
+            let view = self.view(leaf_page_id, Some(slot_index))?;
+
+            if let Some(entry) = view.entry {
+                self.leaf_page_id = leaf_page_id;
+                self.slot_index = slot_index;
+                self.anchor = Some(entry.key().clone());
+                return Ok(());
+            }
+
+            if view.next_leaf_page_id == NULL_PAGE_ID {
+                self.leaf_page_id = leaf_page_id;
+                self.slot_index = view.n_slots;
+                self.anchor = None;
+                return Ok(());
+            }
+
+            leaf_page_id = view.next_leaf_page_id;
+            slot_index = 0;
+        }
+    }
+
+    // Finds the position again if the leaf changed since the last call
+    fn revalidate(&mut self) -> Result<(), YarddError> {
+        let is_valid = self.with_leaf(self.leaf_page_id, |page| {
+            let node = match page.try_as_index_node::<KeyType>() {
+                Ok(node) if node.is_leaf() => node,
+                _ => return false,
+            };
+
+            match &self.anchor {
+                Some(anchor) => {
+                    self.slot_index < node.read_n_slots()
+                        && node
+                            .read_key_node(self.slot_index)
+                            .key()
+                            .cmp_key(anchor)
+                            .is_eq()
+                }
+                None => {
+                    self.slot_index == node.read_n_slots()
+                        && node.read_next_leaf_page_id() == NULL_PAGE_ID
+                }
+            }
+        })?;
+
+        if is_valid {
+            return Ok(());
+        }
+
+        match self.anchor.clone() {
+            Some(anchor) => self.seek(&anchor),
+            None => {
+                let last_leaf = self.tree.last_leaf()?;
+                let n_slots = self.view(last_leaf, None)?.n_slots;
+                self.settle(last_leaf, n_slots)
+            }
+        }
+    }
+
+    fn view(
+        &self,
+        leaf_page_id: PageId,
+        slot_index: Option<u16>,
+    ) -> Result<LeafView<KeyType>, YarddError> {
+        self.with_leaf(leaf_page_id, |page| {
+            let node = page.as_index_node::<KeyType>();
+            let n_slots = node.read_n_slots();
+
+            LeafView {
+                n_slots,
+                next_leaf_page_id: node.read_next_leaf_page_id(),
+                prev_leaf_page_id: node.read_prev_leaf_page_id(),
+                entry: slot_index
+                    .filter(|slot_index| *slot_index < n_slots)
+                    .map(|slot_index| node.read_key_node(slot_index)),
+            }
+        })
+    }
+
+    fn with_leaf<T>(
+        &self,
+        leaf_page_id: PageId,
+        f: impl FnOnce(&Page) -> T,
+    ) -> Result<T, YarddError> {
+        let page = self.tree.fetch_page(leaf_page_id)?;
+        let page = page.read().unwrap();
+
+        Ok(f(&page))
+    }
+
+    fn next_entry(&mut self) -> Result<Option<KeyEntry<KeyType>>, YarddError> {
+        self.revalidate()?;
+        if self.anchor.is_none() {
+            return Ok(None);
+        }
+
+        let view = self.view(self.leaf_page_id, Some(self.slot_index))?;
+        let full_entry = view
+            .entry
+            .map(|entry| self.tree.load_large_key(entry))
+            .transpose()?;
+        self.settle(self.leaf_page_id, self.slot_index + 1)?;
+
+        Ok(full_entry)
+    }
+}
+
+impl<KeyType> Iterator for Cursor<'_, KeyType>
+where
+    KeyType: DbColumn,
+{
+    type Item = Result<KeyEntry<KeyType>, YarddError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().transpose()
+    }
+}
+
+/// Every live entry of a tree in key order, read a leaf at a time by following
+/// sibling links. A leaf is only latched while its entries are copied out, so
+/// the scan never pins more than one page, and changes to a leaf after it has
+/// been read aren't seen. If the next leaf has been merged away in the
+/// meantime, the scan finds its place again by key. A page that can't be read
+/// ends the scan with its error.
+pub struct TreeIter<'a, KeyType>
+where
+    KeyType: DbColumn,
+{
+    tree: &'a BTree<KeyType>,
+    // The leaf the buffered entries came from, NULL before the first leaf
+    // and after repositioning, when there's no link to check
+    leaf_page_id: PageId,
+    next_leaf_page_id: PageId,
+    buffer: VecDeque<KeyEntry<KeyType>>,
+    // Stored key of the last entry returned and how many with that key were returned
+    last_key: Option<(KeyType, usize)>,
+    // Entries to skip after repositioning: those before the key, and that many equal to it
+    skip: Option<(KeyType, usize)>,
+}
+
+impl<'a, KeyType> TreeIter<'a, KeyType>
+where
+    KeyType: DbColumn,
+{
+    pub fn new(tree: &'a BTree<KeyType>) -> Result<Self, YarddError> {
+        Ok(TreeIter {
+            tree,
+            leaf_page_id: NULL_PAGE_ID,
+            next_leaf_page_id: tree.first_leaf()?,
+            buffer: VecDeque::new(),
+            last_key: None,
+            skip: None,
+        })
+    }
+
+    // Copies out the entries of the next leaf with any, until the end of the tree
+    fn fill_buffer(&mut self) -> Result<(), YarddError> {
+        while self.buffer.is_empty() && self.next_leaf_page_id != NULL_PAGE_ID {
+            let page = self.tree.fetch_page(self.next_leaf_page_id)?;
+            let page = page.read().unwrap();
+
+            let node = match page.try_as_index_node::<KeyType>() {
+                Ok(node)
+                    if node.is_leaf()
+                        && (self.leaf_page_id == NULL_PAGE_ID
+                            || node.read_prev_leaf_page_id() == self.leaf_page_id) =>
+                {
+                    node
+                }
+                _ => {
+                    drop(page);
+                    self.reposition()?;
+                    continue;
+                }
+            };
+
+            self.buffer.extend(node.iter_visible());
+            self.leaf_page_id = page.page_id;
+            self.next_leaf_page_id = node.read_next_leaf_page_id();
+        }
+
+        Ok(())
+    }
+
+    // The link followed no longer leads to the next leaf, so descend to the
+    // first leaf that can hold the last key returned and skip to past it
+    fn reposition(&mut self) -> Result<(), YarddError> {
+        self.leaf_page_id = NULL_PAGE_ID;
+        self.next_leaf_page_id = match &self.last_key {
+            Some((key, _)) => self.tree.first_leaf_for_key(key)?,
+            None => self.tree.first_leaf()?,
+        };
+        self.skip = self.last_key.clone();
+
+        Ok(())
+    }
+
+    fn skipped(&mut self, entry: &KeyEntry<KeyType>) -> bool {
+        let Some((key, n_equal)) = &mut self.skip else {
+            return false;
+        };
+
+        if entry.key().cmp_key(key).is_lt() {
+            return true;
+        }
+
+        if entry.key().cmp_key(key).is_eq() && *n_equal > 0 {
+            *n_equal -= 1;
+            return true;
+        }
+
+        self.skip = None;
+        false
+    }
+
+    fn next_entry(&mut self) -> Result<Option<KeyEntry<KeyType>>, YarddError> {
+        loop {
+            self.fill_buffer()?;
+            let Some(entry) = self.buffer.pop_front() else {
+                return Ok(None);
+            };
+            if self.skipped(&entry) {
+                continue;
+            }
+
+            self.last_key = match self.last_key.take() {
+                Some((key, n_equal)) if key.cmp_key(entry.key()).is_eq() => {
+                    Some((key, n_equal + 1))
+                }
+                _ => Some((entry.key().clone(), 1)),
+            };
+
+            return self.tree.load_large_key(entry).map(Some);
+        }
+    }
+}
+
+impl<KeyType> Iterator for TreeIter<'_, KeyType>
+where
+    KeyType: DbColumn,
+{
+    type Item = Result<KeyEntry<KeyType>, YarddError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.next_entry().transpose();
+        if let Some(Err(_)) = entry {
+            self.buffer.clear();
+            self.next_leaf_page_id = NULL_PAGE_ID;
+        }
+
+        entry
+    }
+}
+
+#[cfg(test)]
+mod cursor_tests {
+    use std::{
+        fs::{create_dir_all, remove_dir_all},
+        path::Path,
+        sync::{Arc, Mutex},
+    };
+
+    use crate::{
+        btree::BTree,
+        disk_btree::{IndexPageRead, KeyEntry},
+        page_manager::PageManager,
+    };
+
+    use super::{Continuation, Cursor};
+
+    fn setup_test_dir(base_dir: &str) {
+        let path = Path::new(base_dir);
+        create_dir_all(path).expect("Failed to create test directory.");
+    }
+
+    fn cleanup(base_dir: &str) {
+        let _ = remove_dir_all(base_dir);
+    }
+
+    fn leaf_entry(key: u64) -> KeyEntry<u64> {
+        KeyEntry::leaf(key, key * 10, key as u16)
+    }
+
+    // Even keys 0..400 spread over several leaves
+    fn even_key_tree(base_dir: &str) -> BTree<u64> {
+        setup_test_dir(base_dir);
+
+        let manager = Arc::new(Mutex::new(PageManager::new(32, base_dir)));
+        manager.lock().unwrap().add_empty_pages("index.db", 32);
+
+        let tree = BTree::<u64>::new(manager).unwrap();
+        for key in (0..400).step_by(2) {
+            tree.insert(leaf_entry(key)).unwrap();
+        }
+
+        tree
+    }
+
+    #[test]
+    pub fn seek_then_next() {
+        let base_dir = "./test27";
+        let tree = even_key_tree(base_dir);
+
+        let mut cursor = Cursor::new(&tree).unwrap();
+        cursor.seek(&51).unwrap();
+
+        let keys: Vec<u64> = cursor.by_ref().map(|entry| *entry.unwrap().key()).collect();
+        assert_eq!((52..400).step_by(2).collect::<Vec<u64>>(), keys);
+        assert!(cursor.next().is_none());
+
+        cursor.seek(&100).unwrap();
+        let entry = cursor.next().expect("Expected entry").unwrap();
+        assert_eq!(100, *entry.key());
+        assert_eq!(1000, entry.page_id());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn seek_then_prev() {
+        let base_dir = "./test28";
+        let tree = even_key_tree(base_dir);
+
+        let mut cursor = Cursor::new(&tree).unwrap();
+        assert!(cursor.prev().is_none());
+
+        cursor.seek(&51).unwrap();
+        let keys: Vec<u64> = std::iter::from_fn(|| cursor.prev())
+            .map(|entry| *entry.unwrap().key())
+            .collect();
+        let mut expected: Vec<u64> = (0..51).step_by(2).collect();
+        expected.reverse();
+        assert_eq!(expected, keys);
+
+        // Past the end the cursor sits after the last entry
+        cursor.seek(&1000).unwrap();
+        assert!(cursor.next().is_none());
+        assert_eq!(398, *cursor.prev().unwrap().unwrap().key());
+        assert_eq!(398, *cursor.next().unwrap().unwrap().key());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn cursor_refinds_position_after_split() {
+        let base_dir = "./test29";
+        let tree = even_key_tree(base_dir);
+
+        let mut cursor = Cursor::new(&tree).unwrap();
+        cursor.seek(&200).unwrap();
+        assert_eq!(200, *cursor.next().unwrap().unwrap().key());
+
+        // Fill the gaps around the cursor so its leaf splits
+        for key in (151..250).step_by(2) {
+            tree.insert(leaf_entry(key)).unwrap();
+        }
+
+        assert_eq!(202, *cursor.next().unwrap().unwrap().key());
+        assert_eq!(202, *cursor.prev().unwrap().unwrap().key());
+        assert_eq!(201, *cursor.prev().unwrap().unwrap().key());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn resume_scan_mid_tree() {
+        let base_dir = "./test54";
+        let tree = even_key_tree(base_dir);
+
+        let mut cursor = Cursor::new(&tree).unwrap();
+        let first_page: Vec<u64> = cursor
+            .by_ref()
+            .take(150)
+            .map(|e| *e.unwrap().key())
+            .collect();
+        assert_eq!((0..300).step_by(2).collect::<Vec<u64>>(), first_page);
+
+        let continuation = cursor.continuation().expect("Scan isn't finished");
+        assert_eq!(300, continuation.key);
+        assert_ne!(tree.first_leaf().unwrap(), continuation.leaf_page_id);
+
+        let rest: Vec<u64> = tree
+            .scan_from_leaf(continuation.clone())
+            .unwrap()
+            .map(|entry| *entry.unwrap().key())
+            .collect();
+        assert_eq!((300..400).step_by(2).collect::<Vec<u64>>(), rest);
+
+        // Split the saved leaf so the slot no longer holds the key
+        for key in (201..400).step_by(2) {
+            tree.insert(leaf_entry(key)).unwrap();
+        }
+
+        let rest: Vec<u64> = tree
+            .scan_from_leaf(continuation)
+            .unwrap()
+            .map(|entry| *entry.unwrap().key())
+            .collect();
+        assert_eq!((300..400).collect::<Vec<u64>>(), rest);
+
+        // A finished scan has nothing to resume
+        let mut cursor = Cursor::new(&tree).unwrap();
+        cursor.by_ref().count();
+        assert_eq!(None::<Continuation<u64>>, cursor.continuation());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn tree_iter_returns_shuffled_keys_sorted() {
+        let manager = Arc::new(Mutex::new(PageManager::in_memory(64)));
+        manager.lock().unwrap().add_empty_pages("index.db", 64);
+
+        let tree = BTree::<u64>::new(manager.clone()).unwrap();
+        for i in 0..500u64 {
+            tree.insert(leaf_entry(i * 7919 % 500)).unwrap();
+        }
+
+        let keys: Vec<u64> = tree
+            .iter()
+            .unwrap()
+            .map(|entry| *entry.unwrap().key())
+            .collect();
+        assert_eq!((0..500).collect::<Vec<_>>(), keys);
+    }
+
+    #[test]
+    pub fn tree_iter_finds_its_place_after_merges() {
+        let manager = Arc::new(Mutex::new(PageManager::in_memory(64)));
+        manager.lock().unwrap().add_empty_pages("index.db", 64);
+
+        let tree = BTree::<u64>::new(manager.clone()).unwrap();
+        for key in 0..500u64 {
+            tree.insert(leaf_entry(key)).unwrap();
+            tree.insert(leaf_entry(key)).unwrap();
+        }
+
+        let mut iter = tree.iter().unwrap();
+        let first: Vec<u64> = iter
+            .by_ref()
+            .take(101)
+            .map(|entry| *entry.unwrap().key())
+            .collect();
+
+        // Another handle empties the leaf the scan is about to move to and the
+        // ones after it, so it's merged away or reclaimed before the scan gets there
+        let next_leaf = tree.fetch_page(iter.next_leaf_page_id).unwrap();
+        let next_key = next_leaf.read().unwrap().as_index_node::<u64>().read_key(0);
+        drop(next_leaf);
+
+        let mut other = BTree::<u64>::open(manager.clone(), tree.root_page_id()).unwrap();
+        for key in next_key..400 {
+            other.remove_all(&key).unwrap();
+        }
+
+        let rest: Vec<u64> = iter.map(|entry| *entry.unwrap().key()).collect();
+
+        let expected: Vec<u64> = (0..next_key)
+            .chain(400..500)
+            .flat_map(|key| [key, key])
+            .skip(first.len())
+            .collect();
+        assert_eq!(expected, rest);
+    }
+}
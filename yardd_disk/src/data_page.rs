@@ -0,0 +1,266 @@
+use std::{mem::size_of, vec};
+
+use crate::{
+    error::YarddError,
+    page::{
+        Page, PageHeader, PageId, PageType, SlotIndex, PAGE_MAGIC_NUMBER,
+        SLOTS_FRAGMENTED_SLOTS_START, SLOTS_HEADER_SIZE, SLOTS_HEADER_START,
+        SLOTS_NEXT_EMPTY_OFFSET_START, SLOTS_OCCUPIED_SLOTS_START,
+    },
+    page_manager::SharedPageManager,
+    serialization_helpers::{read_u16, write_bytes, write_u16},
+};
+
+// Data pages have no sibling links, so the slots follow the slot header directly
+pub const DATA_SLOTS_START: usize = SLOTS_HEADER_START + SLOTS_HEADER_SIZE;
+
+/// Read view of a page holding table tuples.
+pub struct DataPage<'a> {
+    inner_page: &'a Page,
+}
+
+impl<'a> DataPage<'a> {
+    pub fn read_existing_page(page: &'a Page) -> Self {
+        DataPage { inner_page: page }
+    }
+
+    pub fn inner_page(&self) -> &'a Page {
+        self.inner_page
+    }
+
+    pub fn page_id(&self) -> PageId {
+        self.inner_page.page_id
+    }
+
+    pub fn read_n_slots(&self) -> u16 {
+        read_u16(&self.inner_page.data, SLOTS_OCCUPIED_SLOTS_START)
+    }
+
+    pub fn read_next_empty_offset(&self) -> u16 {
+        read_u16(&self.inner_page.data, SLOTS_NEXT_EMPTY_OFFSET_START)
+    }
+
+    pub fn slots_end(&self) -> usize {
+        DATA_SLOTS_START + self.read_n_slots() as usize * size_of::<u16>()
+    }
+
+    // Bytes left for a tuple after making room for its slot
+    pub fn free_space(&self) -> usize {
+        let next_empty_offset = self.read_next_empty_offset() as usize;
+        next_empty_offset.saturating_sub(self.slots_end() + size_of::<u16>())
+    }
+
+    // Tuples are stored as a u16 length followed by their bytes
+    pub fn read_tuple(&self, slot_index: SlotIndex) -> &'a [u8] {
+        let slot_start = DATA_SLOTS_START + slot_index as usize * size_of::<u16>();
+        let offset = read_u16(&self.inner_page.data, slot_start) as usize;
+        let length = read_u16(&self.inner_page.data, offset) as usize;
+
+        let start = offset + size_of::<u16>();
+        &self.inner_page.data[start..start + length]
+    }
+}
+
+pub struct DataPageMut<'a> {
+    inner_page: &'a mut Page,
+}
+
+impl<'a> DataPageMut<'a> {
+    pub fn init_page(page: &'a mut Page) -> Self {
+        let page_id = page.page_id;
+        page.write_header(PageHeader {
+            magic_number: PAGE_MAGIC_NUMBER,
+            page_type: PageType::DataPage,
+            log_sequence_number: 0,
+            parent_page_id: 0,
+            page_id,
+        });
+
+        write_u16(&mut page.data, SLOTS_OCCUPIED_SLOTS_START, 0);
+        write_u16(&mut page.data, SLOTS_FRAGMENTED_SLOTS_START, 0);
+        let next_empty_offset = (page.page_size() - 1) as u16;
+        write_u16(
+            &mut page.data,
+            SLOTS_NEXT_EMPTY_OFFSET_START,
+            next_empty_offset,
+        );
+
+        DataPageMut { inner_page: page }
+    }
+
+    pub fn as_read_only(&self) -> DataPage<'_> {
+        DataPage::read_existing_page(self.inner_page)
+    }
+
+    pub fn append_tuple(&mut self, tuple: &[u8]) -> Result<SlotIndex, YarddError> {
+        let needed = size_of::<u16>() + tuple.len();
+        let available = self.as_read_only().free_space();
+        if needed > available {
+            return Err(YarddError::PageFull {
+                page_id: self.inner_page.page_id,
+                needed,
+                available,
+            });
+        }
+
+        let slot_index = self.as_read_only().read_n_slots();
+        let slots_end = self.as_read_only().slots_end();
+        let offset = self.as_read_only().read_next_empty_offset() as usize - needed;
+
+        self.inner_page.is_dirty = true;
+        let data = &mut self.inner_page.data;
+        let cursor = write_u16(data, offset, tuple.len() as u16);
+        write_bytes(data, cursor, tuple);
+
+        write_u16(data, slots_end, offset as u16);
+        write_u16(data, SLOTS_OCCUPIED_SLOTS_START, slot_index + 1);
+        write_u16(data, SLOTS_NEXT_EMPTY_OFFSET_START, (offset - 1) as u16);
+
+        Ok(slot_index)
+    }
+}
+
+/// Every tuple of the data pages in a file, in page and then slot order, for
+/// a table scan that doesn't go through an index. Other pages in the file are
+/// skipped. Pages are read through the buffer pool, loading them if needed.
+///
+/// A page's tuples are copied out so its latch is only held while it's read.
+/// A page that can't be read is returned as an error and the scan moves on.
+pub struct DataFileScan {
+    page_manager: SharedPageManager,
+    page_ids: vec::IntoIter<PageId>,
+    page_id: PageId,
+    tuples: vec::IntoIter<(SlotIndex, Vec<u8>)>,
+}
+
+impl DataFileScan {
+    pub fn new(page_manager: SharedPageManager, file_name: &str) -> Self {
+        let page_ids = page_manager.lock().unwrap().pages_in_file(file_name);
+
+        DataFileScan {
+            page_manager,
+            page_ids: page_ids.into_iter(),
+            page_id: 0,
+            tuples: vec![].into_iter(),
+        }
+    }
+
+    fn read_tuples(page: &Page) -> Vec<(SlotIndex, Vec<u8>)> {
+        if !page.is_initialized() || page.try_read_page_type() != Ok(PageType::DataPage) {
+            return vec![];
+        }
+
+        let data_page = page.as_data_page();
+        (0..data_page.read_n_slots())
+            .map(|slot_index| (slot_index, data_page.read_tuple(slot_index).to_vec()))
+            .collect()
+    }
+}
+
+impl Iterator for DataFileScan {
+    type Item = Result<(PageId, SlotIndex, Vec<u8>), YarddError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((slot_index, tuple)) = self.tuples.next() {
+                return Some(Ok((self.page_id, slot_index, tuple)));
+            }
+
+            self.page_id = self.page_ids.next()?;
+            let page = self.page_manager.lock().unwrap().find_page(self.page_id);
+            match page {
+                Ok(page) => self.tuples = Self::read_tuples(&page.read().unwrap()).into_iter(),
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod data_page_tests {
+    use std::{
+        fs::{create_dir_all, remove_dir_all},
+        sync::{Arc, Mutex},
+    };
+
+    use crate::{
+        disk_btree::IndexPageMut,
+        page::{Page, PageType},
+        page_manager::PageManager,
+    };
+
+    use super::{DataFileScan, DataPageMut};
+
+    #[test]
+    pub fn append_and_read_tuples() {
+        let mut page = Page {
+            page_id: 3,
+            data: vec![0; 1024].into(),
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        let mut data_page = DataPageMut::init_page(&mut page);
+        let free_space = data_page.as_read_only().free_space();
+
+        assert_eq!(0, data_page.append_tuple(b"hello").unwrap());
+        assert_eq!(1, data_page.append_tuple(&[7; 100]).unwrap());
+        assert!(data_page.as_read_only().free_space() < free_space - 105);
+
+        assert!(data_page.append_tuple(&[0; 1000]).is_err());
+
+        let data_page = page.as_data_page();
+        assert_eq!(2, data_page.read_n_slots());
+        assert_eq!(b"hello", data_page.read_tuple(0));
+        assert_eq!(&[7; 100], data_page.read_tuple(1));
+    }
+
+    #[test]
+    pub fn scan_returns_every_tuple_in_file() {
+        let base_dir = "./test65";
+        create_dir_all(base_dir).expect("Failed to create test directory.");
+
+        // Fewer buffer slots than pages, so the scan has to load evicted pages
+        let manager = Arc::new(Mutex::new(PageManager::new(3, base_dir)));
+        manager.lock().unwrap().add_empty_pages("table.db", 6);
+        manager.lock().unwrap().add_empty_pages("other.db", 1);
+
+        let page_ids = manager.lock().unwrap().pages_in_file("table.db");
+        let mut expected = vec![];
+
+        for (i, page_id) in page_ids.iter().enumerate() {
+            let page = manager.lock().unwrap().find_page(*page_id).unwrap();
+            let mut page = page.write().unwrap();
+
+            match i {
+                // An index page and a blank page sit among the data pages
+                1 => {
+                    IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut page);
+                }
+                4 => {}
+                _ => {
+                    let mut data_page = DataPageMut::init_page(&mut page);
+                    for n in 0..(i + 1) * 5 {
+                        let tuple = format!("tuple {n} on page {page_id}").into_bytes();
+                        let slot_index = data_page.append_tuple(&tuple).unwrap();
+                        expected.push((*page_id, slot_index, tuple));
+                    }
+                }
+            }
+        }
+
+        let other = manager.lock().unwrap().pages_in_file("other.db")[0];
+        let other = manager.lock().unwrap().find_page(other).unwrap();
+        DataPageMut::init_page(&mut other.write().unwrap())
+            .append_tuple(b"not in table.db")
+            .unwrap();
+
+        let scanned: Vec<_> = DataFileScan::new(manager.clone(), "table.db")
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(expected, scanned);
+
+        let _ = remove_dir_all(base_dir);
+    }
+}
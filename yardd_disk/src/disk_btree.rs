@@ -1,10 +1,15 @@
-use std::{io::Write, marker::PhantomData, mem::size_of};
+use std::{cmp::Ordering, marker::PhantomData, mem::size_of};
 
 use crate::{
+    bloom_filter::{self, BloomFilterParams},
+    error::YarddError,
     page::{
-        DbColumn, Page, PageHeader, PageId, PageType, SlotHeader, SlotIndex, HEADER_SIZE,
-        PAGE_MAGIC_NUMBER, PAGE_SIZE_BYTES, SLOTS_FRAGMENTED_SLOTS_START, SLOTS_HEADER_SIZE,
-        SLOTS_HEADER_START, SLOTS_NEXT_EMPTY_OFFSET_START, SLOTS_OCCUPIED_SLOTS_START, SLOTS_START,
+        DbColumn, Page, PageFlags, PageHeader, PageId, PageType, SlotHeader, SlotIndex,
+        NEXT_LEAF_PAGE_ID_START, NEXT_LEAF_PAGE_ID_V2_START, NULL_PAGE_ID, PAGE_MAGIC_NUMBER,
+        PAGE_SIZE_BYTES, PARENT_PAGE_ID_START, PREV_LEAF_PAGE_ID_START, PREV_LEAF_PAGE_ID_V2_START,
+        SLOTS_BLOOM_BYTES_START, SLOTS_BLOOM_HASHES_START, SLOTS_FLAGS_START,
+        SLOTS_FRAGMENTED_SLOTS_START, SLOTS_NEXT_EMPTY_OFFSET_START, SLOTS_OCCUPIED_SLOTS_START,
+        SLOTS_RESERVED_FIELDS, SLOTS_START, SLOTS_V2_START, SLOT_HEADER_V1, SLOT_HEADER_V2,
     },
     serialization_helpers::*,
 };
@@ -27,13 +32,90 @@ where
         read_u16(&self.inner_page().data, SLOTS_NEXT_EMPTY_OFFSET_START)
     }
 
+    fn read_next_leaf_page_id(&'a self) -> PageId {
+        read_u64(&self.inner_page().data, self.next_leaf_page_id_start())
+    }
+
+    fn read_prev_leaf_page_id(&'a self) -> PageId {
+        read_u64(&self.inner_page().data, self.prev_leaf_page_id_start())
+    }
+
+    // Everything after the first three slot header fields moves with the version
+    fn is_v1(&'a self) -> bool {
+        self.inner_page().read_slot_header_version() == SLOT_HEADER_V1
+    }
+
+    fn next_leaf_page_id_start(&'a self) -> usize {
+        if self.is_v1() {
+            NEXT_LEAF_PAGE_ID_START
+        } else {
+            NEXT_LEAF_PAGE_ID_V2_START
+        }
+    }
+
+    fn prev_leaf_page_id_start(&'a self) -> usize {
+        if self.is_v1() {
+            PREV_LEAF_PAGE_ID_START
+        } else {
+            PREV_LEAF_PAGE_ID_V2_START
+        }
+    }
+
+    fn slots_start(&'a self) -> usize {
+        if self.is_v1() {
+            SLOTS_START
+        } else {
+            SLOTS_V2_START
+        }
+    }
+
+    // Version 1 pages have no flags
+    fn read_flags(&'a self) -> u16 {
+        if self.is_v1() {
+            0
+        } else {
+            read_u16(&self.inner_page().data, SLOTS_FLAGS_START)
+        }
+    }
+
+    fn page_id(&'a self) -> PageId {
+        self.inner_page().page_id
+    }
+
+    fn parent_page_id(&'a self) -> PageId {
+        read_u64(&self.inner_page().data, PARENT_PAGE_ID_START)
+    }
+
+    fn is_leaf(&'a self) -> bool {
+        self.inner_page().read_page_type() == PageType::IndexLeaf
+    }
+
+    fn is_internal(&'a self) -> bool {
+        self.inner_page().read_page_type() == PageType::IndexNode
+    }
+
     fn read_key_node(&'a self, slot_index: SlotIndex) -> KeyEntry<KeyType> {
         let offset = self.get_entry_offset(slot_index);
 
-        let page_id = read_u64(&self.inner_page().data, offset);
+        if self.tuple_header_size(offset) == SHARED_TUPLE_HEADER_SIZE {
+            let header = read_u16(&self.inner_page().data, offset);
+
+            return KeyEntry {
+                key: KeyType::from_bytes(
+                    &self.inner_page().data,
+                    offset + SHARED_TUPLE_HEADER_SIZE,
+                ),
+                page_id: self.shared_page_id().unwrap(),
+                slot_index: Some(header & !(SHARED_TOMBSTONE_BIT | SHARED_PAGE_ID_BIT)),
+                deleted: header & SHARED_TOMBSTONE_BIT != 0,
+            };
+        }
+
+        let tagged_page_id = read_u64(&self.inner_page().data, offset);
+        let page_id = tagged_page_id & !TOMBSTONE_BIT;
 
         // Since this is a B+ tree, only leaf nodes have tuple pointers
-        let slot_index = if self.inner_page().read_page_type() == PageType::IndexLeaf {
+        let slot_index = if self.is_leaf() {
             Some(read_u16(
                 &self.inner_page().data,
                 offset + size_of::<PageId>(),
@@ -51,16 +133,167 @@ where
             key,
             page_id,
             slot_index,
+            deleted: tagged_page_id & TOMBSTONE_BIT != 0,
+        }
+    }
+
+    // Reads just the key, skipping the pointer fields in front of it
+    fn read_key(&'a self, slot_index: SlotIndex) -> KeyType {
+        let offset = self.get_entry_offset(slot_index);
+        KeyType::from_bytes(
+            &self.inner_page().data,
+            offset + self.tuple_header_size(offset),
+        )
+    }
+
+    // Compares the slot's key with `key` without decoding it
+    fn compare_key(&'a self, slot_index: SlotIndex, key: &KeyType) -> Ordering {
+        let offset = self.get_entry_offset(slot_index);
+        let key_start = offset + self.tuple_header_size(offset);
+        KeyType::compare_bytes(key, &self.inner_page().data, key_start).reverse()
+    }
+
+    // Reads just the tombstone bit, the top bit of either tuple header
+    fn is_slot_deleted(&'a self, slot_index: SlotIndex) -> bool {
+        let offset = self.get_entry_offset(slot_index);
+        self.inner_page().data[offset] & TOMBSTONE_BYTE_BIT != 0
+    }
+
+    /// The data page id the leaf's entries store once instead of each, if any.
+    fn shared_page_id(&'a self) -> Option<PageId> {
+        if !self.inner_page().has_flag(PageFlags::SharedPageId) {
+            return None;
+        }
+
+        Some(read_u64(
+            &self.inner_page().data,
+            self.shared_page_id_start(),
+        ))
+    }
+
+    // Below the bloom filter, when the leaf has a shared page id
+    fn shared_page_id_start(&'a self) -> usize {
+        self.bloom_filter_start()
+            .saturating_sub(size_of::<PageId>())
+    }
+
+    // Whether an entry pointing at the tuple can leave its page id out
+    fn can_share_page_id(&'a self, page_id: PageId, slot_index: Option<SlotIndex>) -> bool {
+        self.shared_page_id() == Some(page_id)
+            && slot_index.is_some_and(|slot_index| slot_index < SHARED_PAGE_ID_BIT)
+    }
+
+    // Size of the tuple header of the entry at `offset`
+    fn tuple_header_size(&'a self, offset: usize) -> usize {
+        let page = self.inner_page();
+        if page.has_flag(PageFlags::SharedPageId)
+            && read_u16(&page.data, offset) & SHARED_PAGE_ID_BIT != 0
+        {
+            SHARED_TUPLE_HEADER_SIZE
+        } else {
+            TUPLE_HEADER_SIZE
+        }
+    }
+
+    // Size of the entry stored at `offset`, read from its header and key length
+    fn stored_entry_size(&'a self, offset: usize) -> usize {
+        let header_size = self.tuple_header_size(offset);
+        header_size + KeyType::encoded_len(&self.inner_page().data, offset + header_size)
+    }
+
+    // Size the entry would take up on this page, not counting its slot
+    fn entry_size(&'a self, entry: &KeyEntry<KeyType>) -> usize {
+        if self.can_share_page_id(entry.page_id, entry.slot_index) {
+            entry.key.len() + SHARED_TUPLE_HEADER_SIZE
+        } else {
+            entry.size_bytes()
+        }
+    }
+
+    // The first slot whose key is >= `key`, found by binary search on the keys alone
+    fn lower_bound(&'a self, key: &KeyType) -> SlotIndex {
+        let (mut low, mut high) = (0, self.read_n_slots());
+
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.compare_key(mid, key).is_lt() {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+
+        low
+    }
+
+    /// Whether a live entry for `key` is on the page. Only keys and tombstone
+    /// bits are read, no entries are decoded.
+    fn contains_key(&'a self, key: &KeyType) -> bool {
+        self.may_contain_key(key)
+            && (self.lower_bound(key)..self.read_n_slots())
+                .take_while(|slot_index| self.compare_key(*slot_index, key).is_eq())
+                .any(|slot_index| !self.is_slot_deleted(slot_index))
+    }
+
+    fn bloom_filter_params(&'a self) -> Option<BloomFilterParams> {
+        let page = self.inner_page();
+        if !page.has_flag(PageFlags::BloomFilter) {
+            return None;
+        }
+
+        Some(BloomFilterParams {
+            n_bytes: read_u16(&page.data, SLOTS_BLOOM_BYTES_START),
+            n_hashes: read_u16(&page.data, SLOTS_BLOOM_HASHES_START),
+        })
+    }
+
+    fn bloom_filter_start(&'a self) -> usize {
+        let bloom_bytes = self
+            .bloom_filter_params()
+            .map_or(0, |params| params.n_bytes as usize);
+
+        self.inner_page().page_size() - bloom_bytes
+    }
+
+    // Entries grow down from here. A bloom filter and shared page id take the bytes above it.
+    fn entries_end(&'a self) -> usize {
+        if self.inner_page().has_flag(PageFlags::SharedPageId) {
+            self.shared_page_id_start()
+        } else {
+            self.bloom_filter_start()
         }
     }
 
+    /// False if the page's bloom filter rules `key` out, without looking at
+    /// any slot. Always true for pages without a filter. The filter hashes
+    /// encoded keys, so it assumes keys that compare equal encode the same.
+    fn may_contain_key(&'a self, key: &KeyType) -> bool {
+        let Some(params) = self.bloom_filter_params() else {
+            return true;
+        };
+
+        let key_bytes = key.to_bytes();
+        let end = self.inner_page().page_size();
+        let bits = &self.inner_page().data[end - params.n_bytes as usize..end];
+        bloom_filter::may_contain(bits, params.n_hashes, &key_bytes)
+    }
+
+    /// The serialized entry in the slot, tuple header and key, for copying
+    /// into another page of the same type without decoding it. An entry that
+    /// left its page id to the shared one only makes sense on this page.
+    fn entry_bytes(&'a self, slot_index: SlotIndex) -> &'a [u8] {
+        let offset = self.get_entry_offset(slot_index);
+        &self.inner_page().data[offset..offset + self.stored_entry_size(offset)]
+    }
+
     fn slots_end(&'a self) -> usize {
         let u16_size = size_of::<u16>();
-        SLOTS_START + (self.read_n_slots() + self.read_fragmented_slots()) as usize * u16_size
+        self.slots_start()
+            + (self.read_n_slots() + self.read_fragmented_slots()) as usize * u16_size
     }
 
     fn get_entry_offset(&'a self, slot_index: SlotIndex) -> usize {
-        let start = SLOTS_START + std::mem::size_of::<u16>() * slot_index as usize;
+        let start = self.slots_start() + std::mem::size_of::<u16>() * slot_index as usize;
         read_u16(&self.inner_page().data, start) as usize
     }
 
@@ -86,13 +319,28 @@ where
 
     fn get_fragmented_slot_offset(&'a self, slot_index: SlotIndex) -> usize {
         let u16_size = size_of::<u16>();
-        let start = SLOTS_START
+        let start = self.slots_start()
             + u16_size * self.read_n_slots() as usize
             + (slot_index as usize) * u16_size;
 
         read_u16(&self.inner_page().data, start) as usize
     }
 
+    // Holes keep the bytes of the entry that was removed, so their size is
+    // that entry's, read from its stored key length
+    fn fragmented_bytes(&'a self) -> usize {
+        self.get_fragmented_slots()
+            .into_iter()
+            .map(|offset| self.stored_entry_size(offset))
+            .sum()
+    }
+
+    /// The share of the page taken up by holes left by removed entries, which
+    /// `compact` would reclaim.
+    fn fragmentation_ratio(&'a self) -> f32 {
+        self.fragmented_bytes() as f32 / self.inner_page().page_size() as f32
+    }
+
     fn read_slots_header(&'a self) -> SlotHeader {
         SlotHeader {
             occupied_slots: self.read_n_slots(),
@@ -100,12 +348,117 @@ where
             next_empty_offset: self.read_next_empty_offset(),
         }
     }
+
+    // Bytes available for an entry's data once the slot it needs is accounted for
+    fn free_space(&'a self) -> usize {
+        let next_empty_offset = self.read_next_empty_offset() as usize;
+        next_empty_offset.saturating_sub(self.slots_end() + size_of::<u16>())
+    }
+
+    fn can_fit(&'a self, entry: &KeyEntry<KeyType>) -> bool {
+        self.entry_size(entry) <= self.free_space()
+    }
+
+    /// Checks the slot invariants and returns every violation found.
+    fn validate(&'a self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+
+        let entries_end = self.entries_end();
+        let slots_end = self.slots_end();
+
+        // (start, end, slot index) of each live entry
+        let mut extents = vec![];
+        let mut prev_key: Option<KeyType> = None;
+
+        for slot_index in 0..self.read_n_slots() {
+            let offset = self.get_entry_offset(slot_index);
+
+            // Don't decode an entry whose header lies outside the entry area
+            if offset < slots_end
+                || offset + SHARED_TUPLE_HEADER_SIZE > entries_end
+                || offset + self.tuple_header_size(offset) > entries_end
+            {
+                errors.push(format!(
+                    "Slot {} offset {} is outside [{}, {})",
+                    slot_index, offset, slots_end, entries_end
+                ));
+                continue;
+            }
+
+            let entry = self.read_key_node(slot_index);
+            let end = offset + self.stored_entry_size(offset);
+            if end > entries_end {
+                errors.push(format!(
+                    "Slot {} entry ends at {}, past the end of the entries",
+                    slot_index, end
+                ));
+            }
+
+            if !self.may_contain_key(&entry.key) {
+                errors.push(format!(
+                    "Slot {} is missing from the bloom filter",
+                    slot_index
+                ));
+            }
+
+            if prev_key
+                .as_ref()
+                .is_some_and(|prev_key| entry.key.cmp_key(prev_key).is_lt())
+            {
+                errors.push(format!("Slot {} is out of key order", slot_index));
+            }
+
+            prev_key = Some(entry.key);
+            extents.push((offset, end, slot_index));
+        }
+
+        extents.sort();
+        for pair in extents.windows(2) {
+            let (_, end, slot_index) = pair[0];
+            let (next_start, _, next_slot_index) = pair[1];
+
+            if end > next_start {
+                errors.push(format!(
+                    "Entries in slots {} and {} overlap",
+                    slot_index, next_slot_index
+                ));
+            }
+        }
+
+        let next_empty_offset = self.read_next_empty_offset() as usize;
+        let lowest_offset = extents.first().map_or(entries_end, |(start, _, _)| *start);
+        if next_empty_offset >= lowest_offset {
+            errors.push(format!(
+                "Next empty offset {} isn't below the lowest entry at {}",
+                next_empty_offset, lowest_offset
+            ));
+        }
+
+        for (i, offset) in self.get_fragmented_slots().into_iter().enumerate() {
+            let overlapping = extents
+                .iter()
+                .find(|(start, end, _)| *start <= offset && offset < *end);
+
+            if let Some((_, _, slot_index)) = overlapping {
+                errors.push(format!(
+                    "Fragmented slot {} at offset {} overlaps slot {}",
+                    i, offset, slot_index
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
 }
 
 pub trait IndexPageReadSized<'a, KeyType>
 where
     Self: IndexPageRead<'a, KeyType> + Sized,
-    KeyType: DbColumn,
+    KeyType: DbColumn + 'a,
 {
     fn iter(&'a self) -> PageIterator<'a, KeyType> {
         PageIterator {
@@ -114,9 +467,64 @@ where
             slot_index: 0,
         }
     }
+
+    // Skips entries that have been deleted but not yet vacuumed
+    fn iter_visible(&'a self) -> impl Iterator<Item = KeyEntry<KeyType>> {
+        self.iter().filter(|entry| !entry.deleted)
+    }
+
+    fn iter_indexed(&'a self) -> impl Iterator<Item = (SlotIndex, KeyEntry<KeyType>)> {
+        self.iter()
+            .enumerate()
+            .map(|(slot_index, entry)| (slot_index as SlotIndex, entry))
+    }
+
+    fn keys_iter(&'a self) -> impl Iterator<Item = KeyType> {
+        (0..self.read_n_slots()).map(move |slot_index| self.read_key(slot_index))
+    }
+
+    fn find_entry(&'a self, key: &KeyType) -> Option<KeyEntry<KeyType>> {
+        if !self.may_contain_key(key) {
+            return None;
+        }
+
+        self.iter().find(|entry| entry.key.cmp_key(key).is_eq())
+    }
+
+    /// The child of an internal page to descend into for `key`: the child of the
+    /// last entry whose key is <= `key`, or the first child if `key` sorts before
+    /// every separator. A key equal to a separator goes right of it, to the last
+    /// of any equal separators. Duplicates of `key` may also sit in the child to
+    /// its left, so a scan for every match has to start further left.
+    fn find_child(&'a self, key: &KeyType) -> PageId {
+        assert!(self.is_internal(), "Only internal pages have children");
+
+        let mut child = None;
+
+        for entry in self.iter() {
+            if child.is_some() && entry.key.cmp_key(key).is_gt() {
+                break;
+            }
+            child = Some(entry.page_id);
+        }
+
+        child.expect("Internal node has no children")
+    }
+
+    /// Returns every entry matching `key`, in insertion order.
+    fn find_all(&'a self, key: &KeyType) -> Vec<KeyEntry<KeyType>> {
+        if !self.may_contain_key(key) {
+            return vec![];
+        }
+
+        self.iter()
+            .skip_while(|entry| entry.key.cmp_key(key).is_lt())
+            .take_while(|entry| entry.key.cmp_key(key).is_eq())
+            .collect()
+    }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct KeyEntry<KeyType>
 where
     KeyType: DbColumn,
@@ -124,6 +532,98 @@ where
     key: KeyType,
     page_id: PageId,
     slot_index: Option<SlotIndex>,
+    deleted: bool,
+}
+
+impl<KeyType> KeyEntry<KeyType>
+where
+    KeyType: DbColumn,
+{
+    // Internal nodes point at a child page, so there's no tuple slot
+    pub fn internal(key: KeyType, child_page_id: PageId) -> Self {
+        KeyEntry {
+            key,
+            page_id: child_page_id,
+            slot_index: None,
+            deleted: false,
+        }
+    }
+
+    pub fn leaf(key: KeyType, page_id: PageId, slot_index: SlotIndex) -> Self {
+        KeyEntry {
+            key,
+            page_id,
+            slot_index: Some(slot_index),
+            deleted: false,
+        }
+    }
+
+    pub fn key(&self) -> &KeyType {
+        &self.key
+    }
+
+    pub fn page_id(&self) -> PageId {
+        self.page_id
+    }
+
+    pub fn slot_index(&self) -> Option<SlotIndex> {
+        self.slot_index
+    }
+
+    pub fn is_leaf_entry(&self) -> bool {
+        self.slot_index.is_some()
+    }
+
+    // A tombstone stays on the page until a vacuum removes it
+    pub fn is_deleted(&self) -> bool {
+        self.deleted
+    }
+
+    // Size of the entry's data on the page, not counting its slot
+    pub fn size_bytes(&self) -> usize {
+        self.key.len() + TUPLE_HEADER_SIZE
+    }
+}
+
+/// Where a full page is divided when it splits.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SplitPolicy {
+    // Half the entries move to the new page
+    Even,
+    // Only the last entry moves, leaving the left page full. Suits append-mostly
+    // workloads (e.g. autoincrement keys) where the left page never gets more keys.
+    RightHeavy,
+    // The percentage of entries that stay on the left page
+    FillFactor(u8),
+}
+
+impl SplitPolicy {
+    // The number of entries that stay on the left page, always leaving both pages non-empty
+    pub fn split_index(&self, n_entries: usize) -> usize {
+        assert!(
+            n_entries >= 2,
+            "Can't split a page with fewer than two entries"
+        );
+
+        let split_index = match self {
+            SplitPolicy::Even => n_entries / 2,
+            SplitPolicy::RightHeavy => n_entries - 1,
+            SplitPolicy::FillFactor(percent) => n_entries * *percent as usize / 100,
+        };
+
+        split_index.clamp(1, n_entries - 1)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SplitResult<KeyType>
+where
+    KeyType: DbColumn,
+{
+    pub separator: KeyType, // Lowest key of the right page, promoted to the parent
+    pub right_page_id: PageId,
+    pub left_count: usize,
+    pub right_count: usize,
 }
 
 pub struct IndexPage<'a, KeyType>
@@ -142,9 +642,22 @@ where
     inner_page: &'a mut Page,
 }
 
-// Regular page header + page slots
-pub const INDEX_PAGE_HEADER_SIZE: usize = HEADER_SIZE + SLOTS_HEADER_SIZE;
+// Regular page header + page slots + sibling links
+pub const INDEX_PAGE_HEADER_SIZE: usize = SLOTS_V2_START;
 pub const TUPLE_HEADER_SIZE: usize = size_of::<PageId>() + size_of::<SlotIndex>();
+// The largest entry a page can hold two of, with their slots and the gap left
+// below each entry. A split needs two entries to divide, so nothing larger fits in a tree.
+pub const MAX_ENTRY_BYTES: usize =
+    (PAGE_SIZE_BYTES as usize - INDEX_PAGE_HEADER_SIZE - 3 * size_of::<u16>()) / 2;
+// Page ids never get near 2^63, so the top bit of the tuple's page id marks a deleted entry
+pub const TOMBSTONE_BIT: PageId = 1 << 63;
+// Entries of a leaf with a shared page id that point into that page keep only
+// their tuple slot. The slot's top bit is the tombstone, like in a full header,
+// and the next one tells the short header from a page id, which never gets near 2^62.
+pub const SHARED_TUPLE_HEADER_SIZE: usize = size_of::<SlotIndex>();
+const SHARED_TOMBSTONE_BIT: SlotIndex = 1 << 15;
+const SHARED_PAGE_ID_BIT: SlotIndex = 1 << 14;
+const TOMBSTONE_BYTE_BIT: u8 = 1 << 7;
 
 impl<'a, KeyType> IndexPageMut<'a, KeyType>
 where
@@ -160,111 +673,776 @@ where
         };
 
         page.write_header(header);
+        page.write_slot_header_version(SLOT_HEADER_V2);
+        // A recycled page may still be flagged from its last use
+        write_bytes(
+            &mut page.data,
+            SLOTS_FLAGS_START,
+            &[0; size_of::<u16>() * (1 + SLOTS_RESERVED_FIELDS)],
+        );
 
         let mut node_page = Self {
             inner_page: page,
             phantom: PhantomData,
         };
 
+        node_page.clear_entries();
+        node_page.write_next_leaf_page_id(NULL_PAGE_ID);
+        node_page.write_prev_leaf_page_id(NULL_PAGE_ID);
+
+        node_page
+    }
+
+    fn clear_entries(&mut self) {
+        self.inner_page.is_dirty = true;
+
+        let entries_end = self.entries_end();
+        let bloom_filter_start = self.bloom_filter_start();
+        let page_size = self.inner_page.page_size();
+        self.inner_page.data[bloom_filter_start..page_size].fill(0);
+
         let slots_header = SlotHeader {
             occupied_slots: 0,
             fragmented_slots: 0,
-            next_empty_offset: (node_page.inner_page.page_size() - 1) as u16,
+            next_empty_offset: (entries_end - 1) as u16,
         };
 
-        node_page.write_slots_header(&slots_header);
+        self.write_slots_header(&slots_header);
+    }
 
-        node_page
+    /// Gives the leaf a bloom filter sized for `expected_keys` keys at the
+    /// given false positive rate, replacing any filter it had. The filter takes
+    /// space from the entries, so this returns `PageFull`, leaving the page
+    /// unchanged, if they would no longer fit. Splits pass the filter on to
+    /// the new leaf; removals rebuild it.
+    pub fn enable_bloom_filter(
+        &mut self,
+        expected_keys: usize,
+        false_positive_rate: f64,
+    ) -> Result<(), YarddError> {
+        assert!(self.is_leaf(), "Only leaves have bloom filters");
+        assert!(
+            !self.is_v1(),
+            "Version 1 slot headers have no room for a bloom filter"
+        );
+
+        let params = BloomFilterParams::for_rate(expected_keys, false_positive_rate);
+        self.set_bloom_filter(Some(params))
     }
 
-    pub fn as_read_only(&'a self) -> IndexPage<'a, KeyType> {
-        IndexPage {
-            inner_page: self.inner_page,
-            phantom: PhantomData,
-        }
+    /// Drops the leaf's bloom filter, handing its space back to the entries.
+    pub fn disable_bloom_filter(&mut self) {
+        self.set_bloom_filter(None)
+            .expect("Entries no longer fit without a bloom filter");
     }
 
-    fn write_entry(&mut self, new_entry: KeyEntry<KeyType>, offset: usize) {
-        let mut cursor = offset;
-        cursor = write_u64(&mut self.inner_page.data, cursor, new_entry.page_id);
+    fn set_bloom_filter(&mut self, params: Option<BloomFilterParams>) -> Result<(), YarddError> {
+        let shared_page_id = self.shared_page_id();
+        self.relayout(|node| {
+            node.write_bloom_filter_params(params)?;
+            node.write_shared_page_id(shared_page_id)
+        })
+    }
 
-        cursor = write_u16(
-            &mut self.inner_page.data,
-            cursor,
-            new_entry.slot_index.unwrap_or_default(),
+    /// Stores `page_id` once for the leaf, so entries pointing into that data
+    /// page only keep their tuple slot, 8 bytes less each. Entries pointing
+    /// anywhere else keep the full tuple header. Replaces any shared page id,
+    /// and returns `PageFull`, leaving the page unchanged, if the entries no
+    /// longer fit. Splits pass the shared page id on to the new leaf.
+    pub fn set_shared_page_id(&mut self, page_id: Option<PageId>) -> Result<(), YarddError> {
+        assert!(self.is_leaf(), "Only leaves point at data pages");
+        assert!(
+            !self.is_v1(),
+            "Version 1 slot headers have no room for flags"
         );
 
-        let bytes = new_entry.key.to_bytes();
-        write_bytes(&mut self.inner_page.data, cursor, &bytes);
+        self.relayout(|node| node.write_shared_page_id(page_id))
     }
 
-    pub fn append_key(&mut self, new_entry: KeyEntry<KeyType>) {
-        self.inner_page.is_dirty = true;
+    // Rewrites the entries after `change` has resized the space above them
+    fn relayout(
+        &mut self,
+        change: impl FnOnce(&mut Self) -> Result<(), YarddError>,
+    ) -> Result<(), YarddError> {
+        let snapshot = self.inner_page.snapshot();
+        let entries: Vec<_> = self.iter().collect();
+
+        // Clear the old filter's bytes before the entry area changes size
+        self.clear_entries();
+        let rewritten = change(self).and_then(|()| {
+            if self.entries_end() <= self.slots_start() {
+                return Err(YarddError::PageFull {
+                    page_id: self.inner_page.page_id,
+                    needed: self.inner_page.page_size() - self.entries_end(),
+                    available: self.inner_page.page_size() - self.slots_start(),
+                });
+            }
 
-        let entry_size_bytes = new_entry.key.len() + TUPLE_HEADER_SIZE;
+            self.clear_entries();
+            self.append_keys(entries)
+        });
 
-        let slots_header = self.read_slots_header();
-        let offset_start = slots_header.next_empty_offset as usize - entry_size_bytes;
+        if rewritten.is_err() {
+            self.inner_page.restore(&snapshot);
+        }
 
-        // size of entry + a new slot
-        if offset_start < self.slots_end() {
-            // TODO: Add linked pages
-            panic!("No more space left for page!")
+        rewritten
+    }
+
+    fn write_bloom_filter_params(
+        &mut self,
+        params: Option<BloomFilterParams>,
+    ) -> Result<(), YarddError> {
+        match params {
+            Some(params) => {
+                self.inner_page.set_flag(PageFlags::BloomFilter)?;
+                write_u16(
+                    &mut self.inner_page.data,
+                    SLOTS_BLOOM_BYTES_START,
+                    params.n_bytes,
+                );
+                write_u16(
+                    &mut self.inner_page.data,
+                    SLOTS_BLOOM_HASHES_START,
+                    params.n_hashes,
+                );
+            }
+            None => {
+                self.inner_page.clear_flag(PageFlags::BloomFilter)?;
+                write_bytes(&mut self.inner_page.data, SLOTS_BLOOM_BYTES_START, &[0; 4]);
+            }
         }
 
-        let mut insert_index = slots_header.occupied_slots;
-        for (slot_index, entry) in self.iter().enumerate() {
-            if new_entry.key <= entry.key {
-                insert_index = slot_index as u16;
-                break;
+        Ok(())
+    }
+
+    // Goes below the bloom filter, so it's written after any filter change
+    fn write_shared_page_id(&mut self, page_id: Option<PageId>) -> Result<(), YarddError> {
+        match page_id {
+            Some(page_id) => {
+                self.inner_page.set_flag(PageFlags::SharedPageId)?;
+                let start = self.shared_page_id_start();
+                write_u64(&mut self.inner_page.data, start, page_id);
             }
+            None => self.inner_page.clear_flag(PageFlags::SharedPageId)?,
         }
 
-        self.insert_slot(insert_index as usize, offset_start);
-        self.write_entry(new_entry, offset_start);
+        Ok(())
     }
 
-    pub fn write_slots_header(&mut self, slots_header: &SlotHeader) {
-        write_u16(
-            &mut self.inner_page.data,
-            SLOTS_OCCUPIED_SLOTS_START,
-            slots_header.occupied_slots,
-        );
-        write_u16(
-            &mut self.inner_page.data,
-            SLOTS_FRAGMENTED_SLOTS_START,
-            slots_header.fragmented_slots,
-        );
-        write_u16(
-            &mut self.inner_page.data,
-            SLOTS_NEXT_EMPTY_OFFSET_START,
-            slots_header.next_empty_offset,
-        );
+    fn add_to_bloom_filter(&mut self, key_bytes: &[u8]) {
+        if let Some(params) = self.bloom_filter_params() {
+            let end = self.inner_page.page_size();
+            let bits = &mut self.inner_page.data[end - params.n_bytes as usize..end];
+            bloom_filter::insert(bits, params.n_hashes, key_bytes);
+        }
     }
 
-    pub fn update_slots(
-        &mut self,
-        slots: Vec<usize>,
-        slots_fragmented: Vec<usize>,
-        next_empty_offset: usize,
-    ) {
-        self.inner_page.is_dirty = true;
+    /// Turns a leaf into an internal node or back. A leaf's tombstoned entries
+    /// are purged, the rest become separators for the same page ids, losing
+    /// their tuple slot index, and its sibling links and bloom filter are
+    /// dropped. Internal entries have no tuple pointer to give a leaf, so an
+    /// internal node must be empty. If the entries don't fit with their own
+    /// page ids the page is left unchanged.
+    pub fn change_page_type(&mut self, new_type: PageType) -> Result<(), YarddError> {
+        let old_type = self.inner_page.read_page_type();
+        assert!(
+            matches!(new_type, PageType::IndexLeaf | PageType::IndexNode),
+            "Index pages can only become {:?} or {:?}",
+            PageType::IndexLeaf,
+            PageType::IndexNode
+        );
 
-        let header = SlotHeader {
-            occupied_slots: slots.len() as u16,
-            fragmented_slots: slots_fragmented.len() as u16,
-            next_empty_offset: next_empty_offset as u16,
-        };
+        if old_type == new_type {
+            return Ok(());
+        }
 
-        self.write_slots_header(&header);
+        if self.is_leaf() {
+            // Dropping the filter frees space, so only the page ids can fail to fit
+            let snapshot = self.inner_page.snapshot();
+            self.vacuum();
+            if self.bloom_filter_params().is_some() {
+                self.disable_bloom_filter();
+            }
+            if self.shared_page_id().is_some() {
+                if let Err(error) = self.set_shared_page_id(None) {
+                    self.inner_page.restore(&snapshot);
+                    return Err(error);
+                }
+            }
+
+            for slot_index in 0..self.read_n_slots() {
+                let offset = self.get_entry_offset(slot_index);
+                write_u16(&mut self.inner_page.data, offset + size_of::<PageId>(), 0);
+            }
+
+            self.write_next_leaf_page_id(NULL_PAGE_ID);
+            self.write_prev_leaf_page_id(NULL_PAGE_ID);
+        } else {
+            assert_eq!(
+                0,
+                self.read_n_slots(),
+                "Only an empty internal node can become a leaf"
+            );
+        }
+
+        self.inner_page.write_page_type(new_type);
+
+        Ok(())
+    }
+
+    pub fn set_parent(&mut self, parent_page_id: PageId) {
+        self.inner_page.is_dirty = true;
+        write_u64(
+            &mut self.inner_page.data,
+            PARENT_PAGE_ID_START,
+            parent_page_id,
+        );
+    }
+
+    pub fn write_next_leaf_page_id(&mut self, page_id: PageId) {
+        self.inner_page.is_dirty = true;
+        let start = self.next_leaf_page_id_start();
+        write_u64(&mut self.inner_page.data, start, page_id);
+    }
+
+    pub fn write_prev_leaf_page_id(&mut self, page_id: PageId) {
+        self.inner_page.is_dirty = true;
+        let start = self.prev_leaf_page_id_start();
+        write_u64(&mut self.inner_page.data, start, page_id);
+    }
+
+    pub fn write_flags(&mut self, flags: u16) {
+        assert!(!self.is_v1(), "Version 1 slot headers have no flags");
+
+        self.inner_page.is_dirty = true;
+        write_u16(&mut self.inner_page.data, SLOTS_FLAGS_START, flags);
+    }
+
+    /// Moves the upper part of this page's entries into `dest`, an empty page
+    /// of the same type, and returns the first key moved as the separator.
+    /// The policy decides how many entries stay behind.
+    pub fn split_into(
+        &mut self,
+        dest: &mut IndexPageMut<KeyType>,
+        split_policy: SplitPolicy,
+    ) -> SplitResult<KeyType> {
+        let split_index = split_policy.split_index(self.read_n_slots() as usize);
+        self.move_entries_from(split_index, dest)
+            .expect("Split policies always move an entry")
+    }
+
+    /// Moves every entry with a key >= `split_key` into `dest`, an empty page
+    /// of the same type. Returns None, moving nothing, if no key is that large.
+    pub fn split_at(
+        &mut self,
+        dest: &mut IndexPageMut<KeyType>,
+        split_key: &KeyType,
+    ) -> Option<SplitResult<KeyType>> {
+        let split_index = self
+            .keys_iter()
+            .position(|key| key.cmp_key(split_key).is_ge())?;
+
+        self.move_entries_from(split_index, dest)
+    }
+
+    /// Appends every entry of `source`, a page of the same type, leaving this
+    /// page unchanged and returning `PageFull` if they don't all fit. For internal
+    /// nodes `separator` replaces the key of source's first entry.
+    ///
+    /// Merged leaves take over source's link on the far side, but the page
+    /// beyond source still points back at it and has to be updated by the caller.
+    pub fn merge_from(
+        &mut self,
+        source: &IndexPage<KeyType>,
+        separator: Option<KeyType>,
+    ) -> Result<(), YarddError> {
+        let snapshot = self.inner_page.snapshot();
+
+        let mut merged = Ok(());
+        for slot_index in 0..source.read_n_slots() {
+            merged = match (slot_index, &separator) {
+                (0, Some(separator)) => self.append_key(KeyEntry::internal(
+                    separator.clone(),
+                    source.read_key_node(0).page_id,
+                )),
+                // Short headers depend on source's shared page id
+                _ if source.shared_page_id().is_some() => {
+                    self.append_key(source.read_key_node(slot_index))
+                }
+                _ => self.append_entry_bytes(source.entry_bytes(slot_index)),
+            };
+
+            if merged.is_err() {
+                break;
+            }
+        }
+
+        if let Err(error) = merged {
+            self.inner_page.restore(&snapshot);
+            return Err(error);
+        }
+
+        if self.is_leaf() {
+            let page_id = self.inner_page.page_id;
+            if self.read_next_leaf_page_id() == source.page_id() {
+                self.write_next_leaf_page_id(source.read_next_leaf_page_id());
+            } else if source.read_next_leaf_page_id() == page_id {
+                self.write_prev_leaf_page_id(source.read_prev_leaf_page_id());
+            }
+        }
+
+        Ok(())
+    }
+
+    // Moves the entries from `split_index` on into `dest`, keeping their order
+    fn move_entries_from(
+        &mut self,
+        split_index: usize,
+        dest: &mut IndexPageMut<KeyType>,
+    ) -> Option<SplitResult<KeyType>> {
+        assert_eq!(0, dest.read_n_slots(), "Split destination must be empty");
+
+        let mut entries: Vec<_> = self.iter().collect();
+        let moved = entries.split_off(split_index);
+        let separator = moved.first()?.key.clone();
+
+        if dest.bloom_filter_params().is_none() {
+            if let Some(params) = self.bloom_filter_params() {
+                dest.set_bloom_filter(Some(params))
+                    .expect("Bloom filter doesn't fit in an empty page");
+            }
+        }
+        if dest.shared_page_id().is_none() {
+            if let Some(page_id) = self.shared_page_id() {
+                dest.set_shared_page_id(Some(page_id))
+                    .expect("Shared page id doesn't fit in an empty page");
+            }
+        }
+
+        self.rewrite_entries(entries);
+        dest.append_keys(moved)
+            .expect("Split moved more entries than fit in a page");
+
+        Some(SplitResult {
+            separator,
+            right_page_id: dest.inner_page.page_id,
+            left_count: split_index,
+            right_count: dest.read_n_slots() as usize,
+        })
+    }
+
+    // Replaces the page's entries, compacting away the space of removed ones
+    fn rewrite_entries(&mut self, entries: Vec<KeyEntry<KeyType>>) {
+        self.clear_entries();
+        for entry in entries {
+            // The entries fit before, and compacting only frees space
+            self.append_key(entry)
+                .expect("Rewritten entries no longer fit");
+        }
+    }
+
+    /// Reclaims the space of fragmented slots. Entries keep their relative
+    /// placement unless `reorder` is set, in which case they're laid out in key
+    /// order from the end of the page down, so an in-order scan reads the
+    /// page's bytes sequentially (backwards).
+    pub fn compact(&mut self, reorder: bool) {
+        if reorder {
+            let entries: Vec<_> = self.iter().collect();
+            self.rewrite_entries(entries);
+            return;
+        }
+
+        if self.read_fragmented_slots() == 0 {
+            return;
+        }
+
+        // Slide entries towards the end of the page, highest offset first so
+        // nothing is overwritten before it has moved
+        let mut slots = self.get_occupied_slots();
+        let mut by_offset: Vec<usize> = (0..slots.len()).collect();
+        by_offset.sort_by_key(|slot| std::cmp::Reverse(slots[*slot]));
+
+        // Spaced the way append_key places them
+        let mut next_empty_offset = self.entries_end() - 1;
+        for slot in by_offset {
+            let offset = slots[slot];
+            let size = self.stored_entry_size(offset);
+
+            let new_offset = next_empty_offset - size;
+            self.inner_page
+                .data
+                .copy_within(offset..offset + size, new_offset);
+            slots[slot] = new_offset;
+            next_empty_offset = new_offset - 1;
+        }
+
+        self.update_slots(slots, vec![], next_empty_offset);
+    }
+
+    /// Removes the entry in the slot without moving any others. Its bytes are
+    /// left behind as a fragmented slot until the page is compacted.
+    pub fn remove_slot_in_place(&mut self, slot_index: SlotIndex) -> KeyEntry<KeyType> {
+        let removed = self.read_key_node(slot_index);
+
+        let mut slots = self.get_occupied_slots();
+        let mut slots_fragmented = self.get_fragmented_slots();
+        slots_fragmented.push(slots.remove(slot_index as usize));
+
+        let next_empty_offset = self.read_next_empty_offset() as usize;
+        self.update_slots(slots, slots_fragmented, next_empty_offset);
+
+        removed
+    }
+
+    /// Removes every entry matching `key` and returns how many were removed.
+    pub fn remove_key(&mut self, key: &KeyType) -> usize {
+        self.remove_where(|entry| entry.key.cmp_key(key).is_eq())
+            .len()
+    }
+
+    /// Removes every entry `matches` accepts and returns them in slot order.
+    pub fn remove_where(
+        &mut self,
+        mut matches: impl FnMut(&KeyEntry<KeyType>) -> bool,
+    ) -> Vec<KeyEntry<KeyType>> {
+        let (removed, kept): (Vec<_>, Vec<_>) = self.iter().partition(|entry| matches(entry));
+
+        if !removed.is_empty() {
+            self.rewrite_entries(kept);
+        }
+
+        removed
+    }
+
+    /// Removes every tombstoned entry, keeping live entries in order. Sibling
+    /// links are untouched. Returns how many entries were removed.
+    pub fn vacuum(&mut self) -> usize {
+        let entries: Vec<_> = self.iter().collect();
+        let n_entries = entries.len();
+
+        let kept: Vec<_> = entries.into_iter().filter(|entry| !entry.deleted).collect();
+        let removed = n_entries - kept.len();
+
+        if removed > 0 {
+            self.rewrite_entries(kept);
+        }
+
+        removed
+    }
+
+    pub fn remove_slot(&mut self, slot_index: SlotIndex) -> KeyEntry<KeyType> {
+        let mut entries: Vec<_> = self.iter().collect();
+        let removed = entries.remove(slot_index as usize);
+
+        self.rewrite_entries(entries);
+
+        removed
+    }
+
+    pub fn write_existing_page(page: &'a mut Page) -> Self {
+        IndexPageMut {
+            inner_page: page,
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn as_read_only(&'a self) -> IndexPage<'a, KeyType> {
+        IndexPage {
+            inner_page: self.inner_page,
+            phantom: PhantomData,
+        }
+    }
+
+    // Leaves carry tuple pointers, internal nodes leave the slot field unused
+    fn tuple_slot_index(&self, slot_index: Option<SlotIndex>) -> SlotIndex {
+        match (self.is_leaf(), slot_index) {
+            (true, Some(slot_index)) => slot_index,
+            (false, None) => 0,
+            _ => panic!(
+                "Entry kind doesn't match page type {:?}",
+                self.inner_page.read_page_type()
+            ),
+        }
+    }
+
+    fn write_tuple_header(
+        &mut self,
+        page_id: PageId,
+        slot_index: SlotIndex,
+        offset: usize,
+    ) -> usize {
+        let cursor = write_u64(&mut self.inner_page.data, offset, page_id);
+        write_u16(&mut self.inner_page.data, cursor, slot_index)
+    }
+
+    fn write_shared_tuple_header(
+        &mut self,
+        slot_index: SlotIndex,
+        deleted: bool,
+        offset: usize,
+    ) -> usize {
+        let tombstone = if deleted { SHARED_TOMBSTONE_BIT } else { 0 };
+        let header = slot_index | SHARED_PAGE_ID_BIT | tombstone;
+        write_u16(&mut self.inner_page.data, offset, header)
+    }
+
+    fn write_entry(&mut self, new_entry: KeyEntry<KeyType>, offset: usize) {
+        let slot_index = self.tuple_slot_index(new_entry.slot_index);
+        let cursor = if self.can_share_page_id(new_entry.page_id, new_entry.slot_index) {
+            self.write_shared_tuple_header(slot_index, new_entry.deleted, offset)
+        } else {
+            let page_id = tag_deleted(new_entry.page_id, new_entry.deleted);
+            self.write_tuple_header(page_id, slot_index, offset)
+        };
+
+        let key_len = new_entry.key.write_to(&mut self.inner_page.data[cursor..]);
+        if self.bloom_filter_params().is_some() {
+            let key_bytes = self.inner_page.data[cursor..cursor + key_len].to_vec();
+            self.add_to_bloom_filter(&key_bytes);
+        }
+    }
+
+    /// Inserts the entry in key order. Duplicate keys are stable: a new entry
+    /// is placed after every existing entry with an equal key.
+    pub fn append_key(&mut self, new_entry: KeyEntry<KeyType>) -> Result<(), YarddError> {
+        let entry_size_bytes = self.entry_size(&new_entry);
+
+        // Room for the entry and its new slot, checked before any offset arithmetic
+        let available = self.free_space();
+        if entry_size_bytes > available {
+            return Err(YarddError::PageFull {
+                page_id: self.inner_page.page_id,
+                needed: entry_size_bytes,
+                available,
+            });
+        }
+
+        self.inner_page.is_dirty = true;
+
+        let offset_start = self.read_next_empty_offset() as usize - entry_size_bytes;
+        let insert_index = self.insert_index(&new_entry.key);
+
+        // Write the entry first so a rejected entry leaves the slots untouched
+        self.write_entry(new_entry, offset_start);
+        self.insert_slot(insert_index as usize, offset_start);
+
+        Ok(())
+    }
+
+    /// Inserts many entries. Runs of entries that sort after everything on the
+    /// page are appended without searching for their position, so sorted input
+    /// costs O(n) rather than O(n^2). Stops at the first entry that doesn't fit.
+    pub fn append_keys(
+        &mut self,
+        entries: impl IntoIterator<Item = KeyEntry<KeyType>>,
+    ) -> Result<(), YarddError> {
+        let mut last_key = self.read_n_slots().checked_sub(1).map(|i| self.read_key(i));
+
+        for entry in entries {
+            let in_order = last_key
+                .as_ref()
+                .is_none_or(|last_key| entry.key.cmp_key(last_key).is_ge());
+
+            // Fragmented slots sit after the occupied ones, so they'd have to move
+            if in_order && self.read_fragmented_slots() == 0 {
+                self.push_key(entry)?;
+            } else {
+                self.append_key(entry)?;
+            }
+
+            last_key = Some(self.read_key(self.read_n_slots() - 1));
+        }
+
+        Ok(())
+    }
+
+    // Appends an entry that sorts last, on a page without fragmented slots
+    fn push_key(&mut self, new_entry: KeyEntry<KeyType>) -> Result<(), YarddError> {
+        let entry_size_bytes = self.entry_size(&new_entry);
+
+        let available = self.free_space();
+        if entry_size_bytes > available {
+            return Err(YarddError::PageFull {
+                page_id: self.inner_page.page_id,
+                needed: entry_size_bytes,
+                available,
+            });
+        }
+
+        self.inner_page.is_dirty = true;
+
+        let slots_header = self.read_slots_header();
+        let offset_start = slots_header.next_empty_offset as usize - entry_size_bytes;
+        let slot_start = self.slots_end();
+
+        self.write_entry(new_entry, offset_start);
+        write_u16(&mut self.inner_page.data, slot_start, offset_start as u16);
+        self.write_slots_header(&SlotHeader {
+            occupied_slots: slots_header.occupied_slots + 1,
+            fragmented_slots: 0,
+            next_empty_offset: (offset_start - 1) as u16,
+        });
+
+        Ok(())
+    }
+
+    /// Inserts an entry serialized by `entry_bytes`, in key order like
+    /// `append_key`. The bytes must come from a page of the same type and
+    /// have a full tuple header.
+    pub fn append_entry_bytes(&mut self, entry_bytes: &[u8]) -> Result<(), YarddError> {
+        let available = self.free_space();
+        if entry_bytes.len() > available {
+            return Err(YarddError::PageFull {
+                page_id: self.inner_page.page_id,
+                needed: entry_bytes.len(),
+                available,
+            });
+        }
+
+        self.inner_page.is_dirty = true;
+
+        let offset_start = self.read_next_empty_offset() as usize - entry_bytes.len();
+        let key = KeyType::from_bytes(entry_bytes, TUPLE_HEADER_SIZE);
+        let insert_index = self.insert_index(&key);
+
+        write_bytes(&mut self.inner_page.data, offset_start, entry_bytes);
+        self.add_to_bloom_filter(&entry_bytes[TUPLE_HEADER_SIZE..]);
+        self.insert_slot(insert_index as usize, offset_start);
+
+        Ok(())
+    }
+
+    // After every entry with an equal key, so duplicates keep insertion order
+    fn insert_index(&self, key: &KeyType) -> SlotIndex {
+        self.iter_indexed()
+            .find(|(_, entry)| key.cmp_key(&entry.key).is_lt())
+            .map(|(slot_index, _)| slot_index)
+            .unwrap_or(self.read_n_slots())
+    }
+
+    /// Overwrites the tuple pointer of the first entry matching `key` without
+    /// moving the entry. Returns false if the key isn't on this page. See
+    /// `update_slot` for when the page is full.
+    pub fn update_entry(
+        &mut self,
+        key: &KeyType,
+        new_page_id: PageId,
+        new_slot_index: Option<SlotIndex>,
+    ) -> Result<bool, YarddError> {
+        let slot_index = match self
+            .iter_indexed()
+            .find(|(_, entry)| entry.key.cmp_key(key).is_eq())
+        {
+            Some((slot_index, _)) => slot_index,
+            None => return Ok(false),
+        };
+
+        self.update_slot(slot_index, new_page_id, new_slot_index)?;
+
+        Ok(true)
+    }
+
+    // Overwrites the tuple pointer in a slot, keeping its tombstone bit. An
+    // entry without its own page id that's pointed elsewhere needs room for
+    // one, so the page is rewritten. If it's full the page is left unchanged.
+    pub fn update_slot(
+        &mut self,
+        slot_index: SlotIndex,
+        new_page_id: PageId,
+        new_slot_index: Option<SlotIndex>,
+    ) -> Result<(), YarddError> {
+        let tuple_slot_index = self.tuple_slot_index(new_slot_index);
+        let deleted = self.is_slot_deleted(slot_index);
+        let offset = self.get_entry_offset(slot_index);
+
+        self.inner_page.is_dirty = true;
+
+        if self.tuple_header_size(offset) == TUPLE_HEADER_SIZE {
+            let page_id = tag_deleted(new_page_id, deleted);
+            self.write_tuple_header(page_id, tuple_slot_index, offset);
+        } else if self.can_share_page_id(new_page_id, new_slot_index) {
+            self.write_shared_tuple_header(tuple_slot_index, deleted, offset);
+        } else {
+            let snapshot = self.inner_page.snapshot();
+            let mut entries: Vec<_> = self.iter().collect();
+            let entry = &mut entries[slot_index as usize];
+            entry.page_id = new_page_id;
+            entry.slot_index = new_slot_index;
+
+            self.clear_entries();
+            let rewritten = self.append_keys(entries);
+            if rewritten.is_err() {
+                self.inner_page.restore(&snapshot);
+            }
+
+            return rewritten;
+        }
+
+        Ok(())
+    }
+
+    /// Marks the first live entry matching `key` as a tombstone, leaving it in
+    /// place. Returns false if there's no live entry for the key.
+    pub fn mark_deleted(&mut self, key: &KeyType) -> bool {
+        let Some((slot_index, _)) = self
+            .iter_indexed()
+            .find(|(_, entry)| !entry.deleted && entry.key.cmp_key(key).is_eq())
+        else {
+            return false;
+        };
+
+        let offset = self.get_entry_offset(slot_index);
+
+        self.inner_page.is_dirty = true;
+        self.inner_page.data[offset] |= TOMBSTONE_BYTE_BIT;
+
+        true
+    }
+
+    pub fn write_slots_header(&mut self, slots_header: &SlotHeader) {
+        write_u16(
+            &mut self.inner_page.data,
+            SLOTS_OCCUPIED_SLOTS_START,
+            slots_header.occupied_slots,
+        );
+        write_u16(
+            &mut self.inner_page.data,
+            SLOTS_FRAGMENTED_SLOTS_START,
+            slots_header.fragmented_slots,
+        );
+        write_u16(
+            &mut self.inner_page.data,
+            SLOTS_NEXT_EMPTY_OFFSET_START,
+            slots_header.next_empty_offset,
+        );
+    }
+
+    pub fn update_slots(
+        &mut self,
+        slots: Vec<usize>,
+        slots_fragmented: Vec<usize>,
+        next_empty_offset: usize,
+    ) {
+        self.inner_page.is_dirty = true;
+
+        let header = SlotHeader {
+            occupied_slots: slots.len() as u16,
+            fragmented_slots: slots_fragmented.len() as u16,
+            next_empty_offset: next_empty_offset as u16,
+        };
+
+        self.write_slots_header(&header);
+
+        let slots_start = self.slots_start();
 
         for (i, offset) in slots.iter().enumerate() {
-            let start = SLOTS_START + size_of::<u16>() * i;
+            let start = slots_start + size_of::<u16>() * i;
             write_u16(&mut self.inner_page.data, start, *offset as u16);
         }
 
         for (i, offset) in slots_fragmented.iter().enumerate() {
-            let start = SLOTS_START + size_of::<u16>() * (header.occupied_slots as usize + i);
+            let start = slots_start + size_of::<u16>() * (header.occupied_slots as usize + i);
             write_u16(&mut self.inner_page.data, start, *offset as u16);
         }
     }
@@ -275,15 +1453,10 @@ where
         let mut slots = self.get_occupied_slots();
         let slots_fragmented = self.get_fragmented_slots();
 
-        slots.insert(insert_index as usize, offset_start);
+        slots.insert(insert_index, offset_start);
 
         self.update_slots(slots, slots_fragmented, offset_start - 1);
     }
-
-    // TODO: figure out a better way to do this rather than duplicating it
-    fn find_entry(&self, key: &KeyType) -> Option<KeyEntry<KeyType>> {
-        self.iter().find(|entry| entry.key == *key)
-    }
 }
 
 impl<'a, KeyType> IndexPageRead<'a, KeyType> for IndexPageMut<'a, KeyType>
@@ -291,12 +1464,12 @@ where
     KeyType: DbColumn,
 {
     fn inner_page(&'a self) -> &'a Page {
-        &self.inner_page
+        self.inner_page
     }
 }
 
 impl<'a, KeyType> IndexPageReadSized<'a, KeyType> for IndexPageMut<'a, KeyType> where
-    KeyType: DbColumn
+    KeyType: DbColumn + 'a
 {
 }
 
@@ -321,7 +1494,18 @@ where
     }
 }
 
-impl<'a, KeyType> IndexPageReadSized<'a, KeyType> for IndexPage<'a, KeyType> where KeyType: DbColumn {}
+impl<'a, KeyType> IndexPageReadSized<'a, KeyType> for IndexPage<'a, KeyType> where
+    KeyType: DbColumn + 'a
+{
+}
+
+fn tag_deleted(page_id: PageId, deleted: bool) -> PageId {
+    if deleted {
+        page_id | TOMBSTONE_BIT
+    } else {
+        page_id
+    }
+}
 
 pub struct PageIterator<'a, KeyType>
 where
@@ -349,25 +1533,31 @@ where
     }
 }
 
+#[cfg(test)]
 mod test {
-    use std::{
-        marker::PhantomData,
-        sync::{Arc, RwLock},
-    };
+    use std::{marker::PhantomData, mem::size_of};
 
     use crate::{
         disk_btree::IndexPageRead,
-        page::{Page, PageType, SlotHeader},
+        error::YarddError,
+        page::{
+            Page, PageFlags, PageHeader, PageId, PageType, SlotHeader, NEXT_LEAF_PAGE_ID_START,
+            NULL_PAGE_ID, PAGE_MAGIC_NUMBER, PREV_LEAF_PAGE_ID_START, SLOTS_START, SLOTS_V2_START,
+            SLOT_HEADER_V1, SLOT_HEADER_V2,
+        },
+        serialization_helpers::{read_u16, read_u64, write_u16},
     };
 
-    use super::{IndexPageMut, IndexPageReadSized, KeyEntry};
+    use super::{IndexPageMut, IndexPageReadSized, KeyEntry, SplitPolicy, TUPLE_HEADER_SIZE};
 
     #[test]
     pub fn read_write_slots_header() {
         let mut page = Page {
-            data: vec![0; 1024],
+            data: vec![0; 1024].into(),
             page_id: 0,
             is_dirty: false,
+            write_version: 0,
+            write_through: None,
         };
 
         let mut index_page = IndexPageMut::<u64> {
@@ -396,9 +1586,11 @@ mod test {
     #[test]
     pub fn init_index_page() {
         let mut page = Page {
-            data: vec![0; 1024],
+            data: vec![0; 1024].into(),
             page_id: 0,
             is_dirty: false,
+            write_version: 0,
+            write_through: None,
         };
         let index_page = IndexPageMut::<u64>::init_page(PageType::IndexNode, 123, &mut page);
 
@@ -418,18 +1610,16 @@ mod test {
     #[test]
     pub fn add_key() {
         let mut page = Page {
-            data: vec![0; 1024],
+            data: vec![0; 1024].into(),
             page_id: 0,
             is_dirty: false,
+            write_version: 0,
+            write_through: None,
         };
 
         let mut index_page = IndexPageMut::<u64>::init_page(PageType::IndexNode, 123, &mut page);
 
-        index_page.append_key(KeyEntry {
-            key: 23,
-            page_id: 345,
-            slot_index: Some(289),
-        });
+        index_page.append_key(KeyEntry::internal(23, 345)).unwrap();
 
         assert!(index_page.inner_page.is_dirty);
 
@@ -441,35 +1631,27 @@ mod test {
         assert_eq!(23, entry.key);
         assert_eq!(345, entry.page_id);
         assert_eq!(None, entry.slot_index);
+
+        assert_eq!(Ok(()), index_page.validate());
     }
 
     #[test]
     pub fn add_key_reverse_insertion_order() {
         let mut page = Page {
-            data: vec![0; 1024],
+            data: vec![0; 1024].into(),
             page_id: 0,
             is_dirty: false,
+            write_version: 0,
+            write_through: None,
         };
 
         let mut index_page = IndexPageMut::<u64>::init_page(PageType::IndexNode, 123, &mut page);
 
-        index_page.append_key(KeyEntry {
-            key: 3,
-            page_id: 14,
-            slot_index: None,
-        });
+        index_page.append_key(KeyEntry::internal(3, 14)).unwrap();
 
-        index_page.append_key(KeyEntry {
-            key: 2,
-            page_id: 15,
-            slot_index: None,
-        });
+        index_page.append_key(KeyEntry::internal(2, 15)).unwrap();
 
-        index_page.append_key(KeyEntry {
-            key: 1,
-            page_id: 16,
-            slot_index: None,
-        });
+        index_page.append_key(KeyEntry::internal(1, 16)).unwrap();
 
         let mut iterator = index_page.iter();
         let entry1 = iterator.next().expect("Expected key");
@@ -483,35 +1665,27 @@ mod test {
         let entry3 = iterator.next().expect("Expected key");
         assert_eq!(3, entry3.key);
         assert_eq!(14, entry3.page_id);
+
+        assert_eq!(Ok(()), index_page.validate());
     }
 
     #[test]
     pub fn add_key_insertion_order() {
         let mut page = Page {
-            data: vec![0; 1024],
+            data: vec![0; 1024].into(),
             page_id: 0,
             is_dirty: false,
+            write_version: 0,
+            write_through: None,
         };
 
         let mut index_page = IndexPageMut::<u64>::init_page(PageType::IndexNode, 123, &mut page);
 
-        index_page.append_key(KeyEntry {
-            key: 1,
-            page_id: 14,
-            slot_index: None,
-        });
+        index_page.append_key(KeyEntry::internal(1, 14)).unwrap();
 
-        index_page.append_key(KeyEntry {
-            key: 2,
-            page_id: 15,
-            slot_index: None,
-        });
+        index_page.append_key(KeyEntry::internal(2, 15)).unwrap();
 
-        index_page.append_key(KeyEntry {
-            key: 3,
-            page_id: 16,
-            slot_index: None,
-        });
+        index_page.append_key(KeyEntry::internal(3, 16)).unwrap();
 
         let mut iterator = index_page.iter();
         let entry1 = iterator.next().expect("Expected key");
@@ -525,5 +1699,1348 @@ mod test {
         let entry3 = iterator.next().expect("Expected key");
         assert_eq!(3, entry3.key);
         assert_eq!(16, entry3.page_id);
+
+        assert_eq!(Ok(()), index_page.validate());
+    }
+
+    #[test]
+    pub fn add_duplicate_keys_stable() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        let mut index_page = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 123, &mut page);
+
+        index_page.append_key(KeyEntry::leaf(5, 1, 0)).unwrap();
+
+        index_page.append_key(KeyEntry::leaf(7, 9, 9)).unwrap();
+
+        for slot_index in 1..4 {
+            index_page
+                .append_key(KeyEntry::leaf(6, 20 + slot_index as u64, slot_index))
+                .unwrap();
+        }
+
+        let matches = index_page.find_all(&6);
+        assert_eq!(3, matches.len());
+        for (i, entry) in matches.iter().enumerate() {
+            assert_eq!(6, entry.key);
+            assert_eq!(21 + i as u64, entry.page_id);
+            assert_eq!(Some(i as u16 + 1), entry.slot_index);
+        }
+
+        let keys: Vec<u64> = index_page.iter().map(|entry| entry.key).collect();
+        assert_eq!(vec![5, 6, 6, 6, 7], keys);
+
+        assert!(index_page.find_all(&8).is_empty());
+
+        assert_eq!(Ok(()), index_page.validate());
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn add_leaf_entry_to_internal_page() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        let mut index_page = IndexPageMut::<u64>::init_page(PageType::IndexNode, 123, &mut page);
+        index_page.append_key(KeyEntry::leaf(1, 2, 3)).unwrap();
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn add_internal_entry_to_leaf_page() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        let mut index_page = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 123, &mut page);
+        index_page.append_key(KeyEntry::internal(1, 2)).unwrap();
+    }
+
+    #[test]
+    pub fn key_entry_accessors() {
+        let leaf = KeyEntry::leaf(5u64, 6, 7);
+        assert_eq!(5, *leaf.key());
+        assert_eq!(6, leaf.page_id());
+        assert_eq!(Some(7), leaf.slot_index());
+
+        let internal = KeyEntry::internal(8u64, 9);
+        assert_eq!(8, *internal.key());
+        assert_eq!(9, internal.page_id());
+        assert_eq!(None, internal.slot_index());
+    }
+
+    #[test]
+    pub fn leaf_and_internal_helpers() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        let leaf = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 123, &mut page);
+        assert!(leaf.is_leaf());
+        assert!(!leaf.is_internal());
+
+        let internal = IndexPageMut::<u64>::init_page(PageType::IndexNode, 123, &mut page);
+        assert!(internal.is_internal());
+        assert!(!internal.is_leaf());
+    }
+
+    #[test]
+    pub fn write_entry_matching_kind() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        let mut leaf = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 123, &mut page);
+        leaf.write_entry(KeyEntry::leaf(1, 2, 3), 500);
+        assert_eq!(
+            3,
+            read_u16(&leaf.inner_page.data, 500 + size_of::<PageId>())
+        );
+
+        let mut internal = IndexPageMut::<u64>::init_page(PageType::IndexNode, 123, &mut page);
+        internal.write_entry(KeyEntry::internal(1, 2), 500);
+        assert_eq!(
+            0,
+            read_u16(&internal.inner_page.data, 500 + size_of::<PageId>())
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn write_entry_leaf_without_slot_index() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        let mut leaf = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 123, &mut page);
+        leaf.write_entry(KeyEntry::internal(1, 2), 500);
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn write_entry_internal_with_slot_index() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        let mut internal = IndexPageMut::<u64>::init_page(PageType::IndexNode, 123, &mut page);
+        internal.write_entry(KeyEntry::leaf(1, 2, 3), 500);
+    }
+
+    #[test]
+    pub fn free_space_shrinks_until_full() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        let mut index_page = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 123, &mut page);
+
+        let entry_size = KeyEntry::leaf(0u64, 0, 0).size_bytes();
+        let initial_free_space = index_page.free_space();
+        assert_eq!(1023 - SLOTS_V2_START - size_of::<u16>(), initial_free_space);
+
+        let mut key = 0;
+        while index_page.can_fit(&KeyEntry::leaf(key, 0, 0)) {
+            let free_space = index_page.free_space();
+            index_page.append_key(KeyEntry::leaf(key, 0, 0)).unwrap();
+            assert!(index_page.free_space() <= free_space - entry_size - size_of::<u16>());
+            key += 1;
+        }
+
+        assert!(key > 0);
+        assert!(index_page.free_space() < entry_size);
+        assert!(!index_page.can_fit(&KeyEntry::leaf(key, 0, 0)));
+        assert_eq!(key as u16, index_page.read_n_slots());
+    }
+
+    #[test]
+    pub fn keys_iter_matches_iter() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        let mut index_page = IndexPageMut::<String>::init_page(PageType::IndexLeaf, 123, &mut page);
+
+        for (i, key) in ["pear", "apple", "fig", "banana"].iter().enumerate() {
+            index_page
+                .append_key(KeyEntry::leaf(key.to_string(), i as u64, i as u16))
+                .unwrap();
+        }
+
+        let keys: Vec<String> = index_page.keys_iter().collect();
+        let expected: Vec<String> = index_page.iter().map(|e| e.key).collect();
+        assert_eq!(expected, keys);
+        assert_eq!(vec!["apple", "banana", "fig", "pear"], keys);
+    }
+
+    #[test]
+    pub fn entry_bytes_copy_between_pages() {
+        let mut source_page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut dest_page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 1,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        let mut source =
+            IndexPageMut::<String>::init_page(PageType::IndexLeaf, 123, &mut source_page);
+        source
+            .append_key(KeyEntry::leaf("kiwi".to_string(), 5, 6))
+            .unwrap();
+        source
+            .append_key(KeyEntry::leaf("apple".to_string(), 7, 8))
+            .unwrap();
+
+        let mut dest = IndexPageMut::<String>::init_page(PageType::IndexLeaf, 123, &mut dest_page);
+        dest.append_key(KeyEntry::leaf("fig".to_string(), 1, 2))
+            .unwrap();
+
+        for slot_index in 0..source.read_n_slots() {
+            dest.append_entry_bytes(source.entry_bytes(slot_index))
+                .unwrap();
+        }
+
+        let entries: Vec<(String, PageId, Option<u16>)> = dest
+            .iter()
+            .map(|e| (e.key.clone(), e.page_id, e.slot_index))
+            .collect();
+        assert_eq!(
+            vec![
+                ("apple".to_string(), 7, Some(8)),
+                ("fig".to_string(), 1, Some(2)),
+                ("kiwi".to_string(), 5, Some(6)),
+            ],
+            entries
+        );
+        assert_eq!(Ok(()), dest.validate());
+    }
+
+    #[test]
+    pub fn v2_slot_header_round_trip() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 4,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        let mut node = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 1, &mut page);
+        node.write_flags(0xA5);
+        node.write_next_leaf_page_id(8);
+        node.write_prev_leaf_page_id(2);
+        for key in [3u64, 1, 2] {
+            node.append_key(KeyEntry::leaf(key, key, 0)).unwrap();
+        }
+
+        assert_eq!(SLOT_HEADER_V2, node.inner_page.read_slot_header_version());
+        assert_eq!(PageType::IndexLeaf, node.inner_page.read_page_type());
+        assert_eq!(SLOTS_V2_START, node.slots_start());
+        assert_eq!(0xA5, node.read_flags());
+        assert_eq!(8, node.read_next_leaf_page_id());
+        assert_eq!(2, node.read_prev_leaf_page_id());
+        assert_eq!(vec![1, 2, 3], node.keys_iter().collect::<Vec<u64>>());
+        assert_eq!(Ok(()), node.validate());
+        assert_eq!(Ok(()), page.validate_header());
+    }
+
+    #[test]
+    pub fn v1_slot_header_still_parses() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 4,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        // Laid out the way pages were before the header was versioned
+        page.write_header(PageHeader {
+            magic_number: PAGE_MAGIC_NUMBER,
+            page_type: PageType::IndexLeaf,
+            log_sequence_number: 0,
+            parent_page_id: 1,
+            page_id: 4,
+        });
+        let mut node = page.as_index_node_mut::<u64>();
+        node.clear_entries();
+        node.write_next_leaf_page_id(8);
+        node.write_prev_leaf_page_id(2);
+        for key in [3u64, 1, 2] {
+            node.append_key(KeyEntry::leaf(key, key, 0)).unwrap();
+        }
+
+        assert_eq!(SLOT_HEADER_V1, node.inner_page.read_slot_header_version());
+        assert_eq!(0, node.read_flags());
+        assert_eq!(8, read_u64(&node.inner_page.data, NEXT_LEAF_PAGE_ID_START));
+        assert_eq!(2, read_u64(&node.inner_page.data, PREV_LEAF_PAGE_ID_START));
+        assert_eq!(
+            node.get_entry_offset(0),
+            read_u16(&node.inner_page.data, SLOTS_START) as usize
+        );
+        assert_eq!(vec![1, 2, 3], node.keys_iter().collect::<Vec<u64>>());
+        assert_eq!(Ok(()), node.validate());
+
+        // The last leaf's next id is NULL_PAGE_ID
+        node.write_next_leaf_page_id(NULL_PAGE_ID);
+        assert_eq!(SLOT_HEADER_V1, node.inner_page.read_slot_header_version());
+        assert_eq!(NULL_PAGE_ID, node.read_next_leaf_page_id());
+        assert_eq!(Ok(()), page.validate_header());
+    }
+
+    #[test]
+    pub fn f64_keys_totally_ordered() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        let mut node = IndexPageMut::<f64>::init_page(PageType::IndexLeaf, 0, &mut page);
+        for key in [2.5, f64::NAN, -1.0, f64::INFINITY, -f64::NAN, 0.0, -0.0] {
+            node.append_key(KeyEntry::leaf(key, 0, 0)).unwrap();
+        }
+
+        let keys: Vec<u64> = node.keys_iter().map(f64::to_bits).collect();
+        let expected: Vec<u64> = [-f64::NAN, -1.0, -0.0, 0.0, 2.5, f64::INFINITY, f64::NAN]
+            .iter()
+            .map(|key| key.to_bits())
+            .collect();
+        assert_eq!(expected, keys);
+        assert_eq!(Ok(()), node.validate());
+
+        // Another NaN lands after the existing one, like any duplicate
+        node.append_key(KeyEntry::leaf(f64::NAN, 1, 0)).unwrap();
+        assert_eq!(2, node.find_all(&f64::NAN).len());
+        assert_eq!(1, node.read_key_node(7).page_id());
+    }
+
+    #[test]
+    pub fn append_keys_matches_individual_appends() {
+        let mut batch_page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut single_page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        // Sorted runs with a few stragglers that fall back to a positioned insert
+        let keys: Vec<u64> = (0..20).chain([7, 3]).chain(20..30).collect();
+        let entries = || keys.iter().map(|key| KeyEntry::leaf(*key, key * 10, 0));
+
+        let mut batch = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut batch_page);
+        batch.append_keys(entries()).unwrap();
+
+        let mut single = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut single_page);
+        for entry in entries() {
+            single.append_key(entry).unwrap();
+        }
+
+        assert_eq!(Ok(()), batch.validate());
+        assert_eq!(batch_page.data, single_page.data);
+
+        // Filling the page reports the entry that didn't fit
+        let mut batch = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut batch_page);
+        let result = batch.append_keys((0..1000).map(|key| KeyEntry::leaf(key, 0, 0)));
+        assert!(matches!(result, Err(YarddError::PageFull { .. })));
+        assert_eq!(Ok(()), batch.validate());
+    }
+
+    #[test]
+    pub fn iter_indexed_locates_slot() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        let mut index_page = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 123, &mut page);
+
+        for key in [30, 10, 20] {
+            index_page
+                .append_key(KeyEntry::leaf(key, key + 1, key as u16 + 2))
+                .unwrap();
+        }
+
+        let indices: Vec<u16> = index_page.iter_indexed().map(|(i, _)| i).collect();
+        assert_eq!(vec![0, 1, 2], indices);
+
+        let (slot_index, _) = index_page
+            .iter_indexed()
+            .find(|(_, entry)| *entry.key() == 20)
+            .expect("Key not found");
+        assert_eq!(1, slot_index);
+
+        let offset = index_page.get_entry_offset(slot_index);
+        assert_eq!(21, read_u64(&index_page.inner_page.data, offset));
+
+        let entry = index_page.read_key_node(slot_index);
+        assert_eq!(20, *entry.key());
+        assert_eq!(Some(22), entry.slot_index());
+    }
+
+    #[test]
+    pub fn update_entry_in_place() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        let mut index_page = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 123, &mut page);
+
+        for key in [1, 2, 3] {
+            index_page
+                .append_key(KeyEntry::leaf(key, key * 10, key as u16))
+                .unwrap();
+        }
+
+        let offsets = index_page.get_occupied_slots();
+        index_page.inner_page.is_dirty = false;
+
+        assert!(index_page.update_entry(&2, 99, Some(98)).unwrap());
+        assert!(index_page.inner_page.is_dirty);
+        assert_eq!(offsets, index_page.get_occupied_slots());
+
+        let entries: Vec<_> = index_page.iter().collect();
+        assert_eq!(
+            vec![1, 2, 3],
+            entries.iter().map(|e| e.key).collect::<Vec<_>>()
+        );
+        assert_eq!(99, entries[1].page_id);
+        assert_eq!(Some(98), entries[1].slot_index);
+        assert_eq!(10, entries[0].page_id);
+        assert_eq!(30, entries[2].page_id);
+
+        assert!(!index_page.update_entry(&4, 1, Some(1)).unwrap());
+    }
+
+    #[test]
+    pub fn split_policy_index() {
+        assert_eq!(5, SplitPolicy::Even.split_index(10));
+        assert_eq!(9, SplitPolicy::RightHeavy.split_index(10));
+        assert_eq!(7, SplitPolicy::FillFactor(70).split_index(10));
+        assert_eq!(1, SplitPolicy::FillFactor(0).split_index(10));
+        assert_eq!(9, SplitPolicy::FillFactor(100).split_index(10));
+    }
+
+    #[test]
+    pub fn split_into_moves_upper_entries() {
+        let mut left_page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 1,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut right_page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 2,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        let mut left = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 123, &mut left_page);
+        let mut right = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 123, &mut right_page);
+
+        for key in 0..6 {
+            left.append_key(KeyEntry::leaf(key, key, key as u16))
+                .unwrap();
+        }
+
+        let split = left.split_into(&mut right, SplitPolicy::Even);
+        assert_eq!(3, split.separator);
+        assert_eq!(2, split.right_page_id);
+        assert_eq!((3, 3), (split.left_count, split.right_count));
+
+        let left_keys: Vec<u64> = left.iter().map(|e| e.key).collect();
+        let right_keys: Vec<u64> = right.iter().map(|e| e.key).collect();
+        assert_eq!(vec![0, 1, 2], left_keys);
+        assert_eq!(vec![3, 4, 5], right_keys);
+    }
+
+    #[test]
+    pub fn split_at_moves_keys_from_split_key() {
+        let mut left_page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 1,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut right_page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 2,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        let mut left = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut left_page);
+        let mut right = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut right_page);
+
+        for key in [50u64, 10, 30, 60, 20, 40] {
+            left.append_key(KeyEntry::leaf(key, key, 0)).unwrap();
+        }
+
+        assert!(left.split_at(&mut right, &70).is_none());
+        assert_eq!(6, left.read_n_slots());
+
+        let split = left.split_at(&mut right, &35).expect("Expected a split");
+        assert_eq!(40, split.separator);
+        assert_eq!(2, split.right_page_id);
+
+        assert_eq!(vec![10, 20, 30], left.keys_iter().collect::<Vec<u64>>());
+        assert_eq!(vec![40, 50, 60], right.keys_iter().collect::<Vec<u64>>());
+        assert_eq!(Ok(()), left.validate());
+        assert_eq!(Ok(()), right.validate());
+    }
+
+    #[test]
+    pub fn merge_from_joins_sibling_leaves() {
+        let mut left_page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 1,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut right_page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 2,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        // ... <-> 1 <-> 2 <-> 3
+        let mut left = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut left_page);
+        left.write_prev_leaf_page_id(0);
+        left.write_next_leaf_page_id(2);
+        left.append_keys((1..=3).map(|key| KeyEntry::leaf(key, key, 0)))
+            .unwrap();
+
+        let mut right = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut right_page);
+        right.write_prev_leaf_page_id(1);
+        right.write_next_leaf_page_id(3);
+        right
+            .append_keys((4..=6).map(|key| KeyEntry::leaf(key, key, 0)))
+            .unwrap();
+
+        left.merge_from(&right.as_read_only(), None).unwrap();
+
+        assert_eq!(
+            vec![1, 2, 3, 4, 5, 6],
+            left.keys_iter().collect::<Vec<u64>>()
+        );
+        assert_eq!(0, left.read_prev_leaf_page_id());
+        assert_eq!(3, left.read_next_leaf_page_id());
+        assert_eq!(Ok(()), left.validate());
+    }
+
+    #[test]
+    pub fn merge_from_internal_uses_separator() {
+        let mut left_page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 1,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut right_page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 2,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        let mut left = IndexPageMut::<u64>::init_page(PageType::IndexNode, 0, &mut left_page);
+        left.append_key(KeyEntry::internal(0, 10)).unwrap();
+        let mut right = IndexPageMut::<u64>::init_page(PageType::IndexNode, 0, &mut right_page);
+        right.append_key(KeyEntry::internal(0, 20)).unwrap();
+        right.append_key(KeyEntry::internal(50, 30)).unwrap();
+
+        left.merge_from(&right.as_read_only(), Some(40)).unwrap();
+
+        let entries: Vec<(u64, PageId)> = left.iter().map(|e| (e.key, e.page_id)).collect();
+        assert_eq!(vec![(0, 10), (40, 20), (50, 30)], entries);
+
+        // A merge that doesn't fit leaves the page as it was
+        let before = left.as_read_only().inner_page().data.clone();
+        let mut full_page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 3,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut full = IndexPageMut::<u64>::init_page(PageType::IndexNode, 0, &mut full_page);
+        let mut key = 100;
+        while full.append_key(KeyEntry::internal(key, key)).is_ok() {
+            key += 1;
+        }
+
+        let result = left.merge_from(&full.as_read_only(), None);
+        assert!(matches!(result, Err(YarddError::PageFull { .. })));
+        assert_eq!(before, left.as_read_only().inner_page().data);
+    }
+
+    #[test]
+    pub fn remove_key_compacts_page() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut node = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut page);
+
+        for key in [3u64, 7, 7, 1, 7, 9] {
+            node.append_key(KeyEntry::leaf(key, key, 0)).unwrap();
+        }
+        let free_space = node.free_space();
+
+        assert_eq!(3, node.remove_key(&7));
+        assert_eq!(0, node.remove_key(&7));
+
+        let keys: Vec<u64> = node.iter().map(|entry| entry.key).collect();
+        assert_eq!(vec![1, 3, 9], keys);
+        assert!(node.free_space() > free_space);
+
+        let removed = node.remove_slot(1);
+        assert_eq!(3, removed.key);
+
+        let keys: Vec<u64> = node.iter().map(|entry| entry.key).collect();
+        assert_eq!(vec![1, 9], keys);
+
+        assert_eq!(Ok(()), node.validate());
+    }
+
+    #[test]
+    pub fn key_entry_debug() {
+        let output = format!("{:?}", KeyEntry::leaf(5u64, 12, 3));
+        assert_eq!(
+            "KeyEntry { key: 5, page_id: 12, slot_index: Some(3), deleted: false }",
+            output
+        );
+    }
+
+    #[test]
+    pub fn validate_reports_corrupt_slots() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut node = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut page);
+
+        for key in [1u64, 2, 3] {
+            node.append_key(KeyEntry::leaf(key, key, 0)).unwrap();
+        }
+        assert_eq!(Ok(()), node.validate());
+
+        // Point the first slot into the middle of the second slot's entry
+        let slots_start = node.slots_start();
+        let second_offset = node.get_entry_offset(1);
+        write_u16(
+            &mut node.inner_page.data,
+            slots_start,
+            (second_offset + 4) as u16,
+        );
+
+        let errors = node.validate().expect_err("Expected corruption");
+        assert!(errors.iter().any(|error| error.contains("overlap")));
+
+        // Point the third slot into the slot array
+        write_u16(
+            &mut node.inner_page.data,
+            slots_start + 2 * size_of::<u16>(),
+            slots_start as u16,
+        );
+
+        let errors = node.validate().expect_err("Expected corruption");
+        assert!(errors.iter().any(|error| error.contains("Slot 2 offset")));
+    }
+
+    #[test]
+    pub fn set_and_read_parent() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 42,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        let mut node = IndexPageMut::<u64>::init_page(PageType::IndexNode, 123, &mut page);
+        assert_eq!(42, node.page_id());
+        assert_eq!(123, node.parent_page_id());
+
+        node.set_parent(456);
+        assert_eq!(456, node.parent_page_id());
+
+        assert!(page.is_dirty);
+        assert_eq!(456, page.read_header().parent_page_id);
+        assert_eq!(456, page.as_index_node::<u64>().parent_page_id());
+    }
+
+    #[test]
+    pub fn append_key_larger_than_page() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 9,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut node = IndexPageMut::<String>::init_page(PageType::IndexLeaf, 0, &mut page);
+        node.append_key(KeyEntry::leaf("a".to_string(), 1, 1))
+            .unwrap();
+
+        let before = node.as_read_only().inner_page().data.clone();
+        let available = node.free_space();
+
+        let error = node
+            .append_key(KeyEntry::leaf("x".repeat(2000), 2, 2))
+            .expect_err("Expected page full");
+        assert_eq!(
+            YarddError::PageFull {
+                page_id: 9,
+                needed: 2000 + 2 + TUPLE_HEADER_SIZE,
+                available,
+            },
+            error
+        );
+
+        // The rejected entry left the page untouched
+        assert_eq!(before, node.as_read_only().inner_page().data);
+        assert_eq!(Ok(()), node.validate());
+    }
+
+    #[test]
+    pub fn iter_visible_skips_tombstones() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        let mut index_page = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 123, &mut page);
+        for key in [1, 2, 2, 3] {
+            index_page
+                .append_key(KeyEntry::leaf(key, key * 10, key as u16))
+                .unwrap();
+        }
+
+        assert!(index_page.mark_deleted(&2));
+        assert!(!index_page.mark_deleted(&7));
+
+        let keys: Vec<u64> = index_page.iter().map(|entry| entry.key).collect();
+        assert_eq!(vec![1, 2, 2, 3], keys);
+
+        let deleted: Vec<bool> = index_page.iter().map(|entry| entry.is_deleted()).collect();
+        assert_eq!(vec![false, true, false, false], deleted);
+
+        // The flag doesn't leak into the page id
+        let visible: Vec<_> = index_page.iter_visible().collect();
+        assert_eq!(
+            vec![1, 2, 3],
+            visible.iter().map(|entry| entry.key).collect::<Vec<_>>()
+        );
+        assert_eq!(20, index_page.iter().nth(1).unwrap().page_id);
+
+        // Only the remaining live duplicate can still be deleted
+        assert!(index_page.mark_deleted(&2));
+        assert!(!index_page.mark_deleted(&2));
+        assert_eq!(2, index_page.iter_visible().count());
+    }
+
+    #[test]
+    pub fn vacuum_purges_tombstones() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut node = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut page);
+        node.write_next_leaf_page_id(8);
+        node.write_prev_leaf_page_id(2);
+
+        for key in 0..10u64 {
+            node.append_key(KeyEntry::leaf(key, key * 10, key as u16))
+                .unwrap();
+        }
+        for key in (0..10u64).filter(|key| key % 2 == 1) {
+            assert!(node.mark_deleted(&key));
+        }
+        let free_space = node.free_space();
+
+        assert_eq!(5, node.vacuum());
+        assert_eq!(0, node.vacuum());
+
+        let entries: Vec<_> = node.iter().collect();
+        assert_eq!(
+            vec![0, 2, 4, 6, 8],
+            entries.iter().map(|entry| entry.key).collect::<Vec<_>>()
+        );
+        assert!(entries.iter().all(|entry| !entry.is_deleted()));
+        assert_eq!(40, entries[2].page_id);
+
+        assert!(node.free_space() > free_space);
+        assert_eq!(8, node.read_next_leaf_page_id());
+        assert_eq!(2, node.read_prev_leaf_page_id());
+        assert_eq!(Ok(()), node.validate());
+    }
+
+    #[test]
+    pub fn find_child_boundaries() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut node = IndexPageMut::<u64>::init_page(PageType::IndexNode, 0, &mut page);
+
+        for (key, child) in [(10, 100), (20, 200), (20, 201), (30, 300)] {
+            node.append_key(KeyEntry::internal(key, child)).unwrap();
+        }
+
+        // Below every separator
+        assert_eq!(100, node.find_child(&0));
+        assert_eq!(100, node.find_child(&9));
+
+        // Equal to a separator goes to that separator's child
+        assert_eq!(100, node.find_child(&10));
+        assert_eq!(300, node.find_child(&30));
+
+        // Between separators
+        assert_eq!(100, node.find_child(&19));
+        assert_eq!(300, node.find_child(&31));
+        assert_eq!(300, node.find_child(&u64::MAX));
+
+        // Equal separators resolve to the last one
+        assert_eq!(201, node.find_child(&20));
+        assert_eq!(201, node.find_child(&29));
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn find_child_on_leaf_panics() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut node = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut page);
+        node.append_key(KeyEntry::leaf(1, 1, 1)).unwrap();
+
+        node.find_child(&1);
+    }
+
+    #[test]
+    pub fn contains_key_present_and_absent() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut node = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut page);
+
+        assert!(!node.contains_key(&1));
+
+        for key in [10u64, 20, 20, 30, 40, 50] {
+            node.append_key(KeyEntry::leaf(key, key, 0)).unwrap();
+        }
+
+        for key in [10, 20, 30, 40, 50] {
+            assert!(node.contains_key(&key));
+        }
+        for key in [0, 15, 25, 45, 60, u64::MAX] {
+            assert!(!node.contains_key(&key));
+        }
+
+        // Tombstoned entries don't count, but a live duplicate does
+        node.mark_deleted(&40);
+        assert!(!node.contains_key(&40));
+        node.mark_deleted(&20);
+        assert!(node.contains_key(&20));
+        node.mark_deleted(&20);
+        assert!(!node.contains_key(&20));
+    }
+
+    #[test]
+    pub fn fragmentation_ratio_after_deletes() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut node = IndexPageMut::<String>::init_page(PageType::IndexLeaf, 0, &mut page);
+
+        for key in ["a", "bbbb", "cc", "dddddddd", "e"] {
+            node.append_key(KeyEntry::leaf(key.to_string(), 1, 1))
+                .unwrap();
+        }
+        assert_eq!(0.0, node.fragmentation_ratio());
+
+        // Variable width keys leave holes of different sizes
+        let bbbb = node.remove_slot_in_place(1);
+        let dddddddd = node.remove_slot_in_place(2);
+        assert_eq!("bbbb", bbbb.key);
+        assert_eq!("dddddddd", dddddddd.key);
+
+        let hole_bytes = bbbb.size_bytes() + dddddddd.size_bytes();
+        assert_eq!(hole_bytes as f32 / 1024.0, node.fragmentation_ratio());
+
+        let keys: Vec<String> = node.keys_iter().collect();
+        assert_eq!(vec!["a", "cc", "e"], keys);
+        assert_eq!(Ok(()), node.validate());
+
+        let free_space = node.free_space();
+        node.compact(false);
+        assert_eq!(0.0, node.fragmentation_ratio());
+        assert!(node.free_space() >= free_space + hole_bytes);
+        assert_eq!(vec!["a", "cc", "e"], node.keys_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    pub fn compact_reorder_lays_entries_out_in_key_order() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut node = IndexPageMut::<String>::init_page(PageType::IndexLeaf, 0, &mut page);
+
+        // Inserted out of order, so later slots sit at higher offsets
+        for key in ["e", "dddddddd", "cc", "bbbb", "a", "ff"] {
+            node.append_key(KeyEntry::leaf(key.to_string(), 1, 1))
+                .unwrap();
+        }
+        node.remove_slot_in_place(3);
+
+        let offsets = |node: &IndexPageMut<String>| -> Vec<usize> {
+            (0..node.read_n_slots())
+                .map(|slot| node.get_entry_offset(slot))
+                .collect()
+        };
+        let placement = |offsets: &[usize]| {
+            let mut slots: Vec<usize> = (0..offsets.len()).collect();
+            slots.sort_by_key(|slot| offsets[*slot]);
+            slots
+        };
+
+        // Without reordering the holes close up but the placement stays
+        let before = placement(&offsets(&node));
+        node.compact(false);
+        assert_eq!(0, node.read_fragmented_slots());
+        assert_eq!(before, placement(&offsets(&node)));
+        assert_eq!(Ok(()), node.validate());
+
+        node.compact(true);
+        let entries: Vec<_> = node.iter().collect();
+        let offsets = offsets(&node);
+
+        // Entries are placed one byte apart, with nothing else between them
+        assert_eq!(1023 - entries[0].size_bytes(), offsets[0]);
+        for slot in 1..entries.len() {
+            assert_eq!(
+                offsets[slot - 1],
+                offsets[slot] + entries[slot].size_bytes() + 1
+            );
+        }
+        assert_eq!(
+            offsets[entries.len() - 1] - 1,
+            node.read_next_empty_offset() as usize
+        );
+
+        let keys: Vec<String> = node.keys_iter().collect();
+        assert_eq!(vec!["a", "bbbb", "cc", "e", "ff"], keys);
+        assert_eq!(Ok(()), node.validate());
+    }
+
+    #[test]
+    pub fn bloom_filter_has_no_false_negatives() {
+        let mut page = Page {
+            data: vec![0; 4096].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut node = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut page);
+
+        for key in 0..50u64 {
+            node.append_key(KeyEntry::leaf(key * 3, key, 0)).unwrap();
+        }
+        let free_space = node.free_space();
+
+        node.enable_bloom_filter(150, 0.01).unwrap();
+        assert!(node.inner_page().has_flag(PageFlags::BloomFilter));
+        assert!(node.free_space() < free_space);
+
+        for key in 50..150u64 {
+            node.append_key(KeyEntry::leaf(key * 3, key, 0)).unwrap();
+        }
+        assert_eq!(Ok(()), node.validate());
+
+        for key in 0..150u64 {
+            assert!(node.may_contain_key(&(key * 3)));
+            assert!(node.contains_key(&(key * 3)));
+        }
+
+        // Most absent keys are turned away without a slot being read
+        let false_positives = (0..1000u64)
+            .map(|key| key * 3 + 1)
+            .filter(|key| node.may_contain_key(key))
+            .count();
+        assert!(false_positives < 50, "{} false positives", false_positives);
+
+        // Removals rebuild the filter from the remaining keys
+        for key in 0..75u64 {
+            node.remove_key(&(key * 3));
+        }
+        assert!((75..150u64).all(|key| node.may_contain_key(&(key * 3))));
+        assert!(!node.contains_key(&0));
+        assert_eq!(Ok(()), node.validate());
+
+        let mut right_page = Page {
+            data: vec![0; 4096].into(),
+            page_id: 1,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut right = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut right_page);
+        let split = node.split_into(&mut right, SplitPolicy::Even);
+        assert!(right.bloom_filter_params().is_some());
+        assert_eq!(Ok(()), right.validate());
+        assert!(right.contains_key(&split.separator));
+
+        node.disable_bloom_filter();
+        assert!(!node.inner_page().has_flag(PageFlags::BloomFilter));
+        assert_eq!(Ok(()), node.validate());
+        assert!(node.contains_key(&300));
+    }
+
+    #[test]
+    pub fn bloom_filter_that_doesnt_fit_leaves_page_unchanged() {
+        let mut page = Page {
+            data: vec![0; 256].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut node = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut page);
+        while node.append_key(KeyEntry::leaf(7, 7, 0)).is_ok() {}
+        let before = node.inner_page().snapshot();
+
+        assert!(matches!(
+            node.enable_bloom_filter(100, 0.01),
+            Err(YarddError::PageFull { .. })
+        ));
+        assert_eq!(before, node.inner_page().snapshot());
+    }
+
+    #[test]
+    pub fn change_page_type_of_emptied_leaf() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 3,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut node = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut page);
+        node.write_next_leaf_page_id(4);
+        for key in [1u64, 2, 3] {
+            node.append_key(KeyEntry::leaf(key, 10 + key, 7)).unwrap();
+            node.remove_key(&key);
+        }
+
+        node.change_page_type(PageType::IndexNode).unwrap();
+        assert!(node.is_internal());
+        assert_eq!(PageType::IndexNode, node.inner_page().read_page_type());
+        assert_eq!(SLOT_HEADER_V2, node.inner_page().read_slot_header_version());
+        assert_eq!(NULL_PAGE_ID, node.read_next_leaf_page_id());
+
+        node.append_key(KeyEntry::internal(5, 20)).unwrap();
+        assert_eq!(20, node.find_child(&6));
+
+        node.remove_key(&5);
+        node.change_page_type(PageType::IndexLeaf).unwrap();
+        assert!(node.is_leaf());
+        assert_eq!(Ok(()), node.validate());
+    }
+
+    #[test]
+    pub fn change_page_type_strips_tuple_slots() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut node = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut page);
+        for key in [10u64, 20, 30] {
+            node.append_key(KeyEntry::leaf(key, key + 1, 9)).unwrap();
+        }
+
+        node.change_page_type(PageType::IndexNode).unwrap();
+
+        let entries: Vec<_> = node.iter().collect();
+        assert_eq!(3, entries.len());
+        assert!(entries.iter().all(|entry| entry.slot_index().is_none()));
+        assert_eq!(21, node.find_child(&25));
+        for slot_index in 0..3 {
+            let offset = node.get_entry_offset(slot_index);
+            assert_eq!(
+                0,
+                read_u16(&node.inner_page().data, offset + size_of::<PageId>())
+            );
+        }
+    }
+
+    #[test]
+    pub fn change_page_type_purges_tombstones() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut node = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut page);
+        for key in [10u64, 20, 30] {
+            node.append_key(KeyEntry::leaf(key, key + 1, 9)).unwrap();
+        }
+        assert!(node.mark_deleted(&20));
+
+        node.change_page_type(PageType::IndexNode).unwrap();
+
+        let entries: Vec<_> = node.iter().collect();
+        assert_eq!(
+            vec![10, 30],
+            entries.iter().map(|entry| *entry.key()).collect::<Vec<_>>()
+        );
+        assert!(entries.iter().all(|entry| !entry.is_deleted()));
+        assert_eq!(11, node.find_child(&25));
+        assert_eq!(31, node.find_child(&35));
+        assert_eq!(Ok(()), node.validate());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only an empty internal node can become a leaf")]
+    pub fn change_page_type_of_internal_node_with_entries() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut node = IndexPageMut::<u64>::init_page(PageType::IndexNode, 0, &mut page);
+        node.append_key(KeyEntry::internal(1, 2)).unwrap();
+
+        node.change_page_type(PageType::IndexLeaf).unwrap();
+    }
+
+    #[test]
+    pub fn shared_page_id_packs_more_entries() {
+        let fill = |node: &mut IndexPageMut<u64>| {
+            let mut key = 0;
+            while node.append_key(KeyEntry::leaf(key, 9, key as u16)).is_ok() {
+                key += 1;
+            }
+            key
+        };
+
+        let mut plain_page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 0,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut plain = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut plain_page);
+        let n_plain = fill(&mut plain);
+
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 1,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut node = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut page);
+        node.set_shared_page_id(Some(9)).unwrap();
+        let n_shared = fill(&mut node);
+
+        // 13 bytes per entry with its slot and gap, instead of 21
+        assert!(n_shared * 2 > n_plain * 3, "{} vs {}", n_shared, n_plain);
+        assert_eq!(Ok(()), node.validate());
+        for (slot_index, entry) in node.iter_indexed() {
+            assert_eq!((9, Some(slot_index)), (entry.page_id(), entry.slot_index()));
+        }
+
+        // Going back to full headers doesn't fit
+        let before = node.inner_page().snapshot();
+        assert!(matches!(
+            node.set_shared_page_id(None),
+            Err(YarddError::PageFull { .. })
+        ));
+        assert_eq!(before, node.inner_page().snapshot());
+
+        for key in 0..10 {
+            node.remove_key(&key);
+        }
+
+        // Entries for other pages keep their own page id
+        node.append_key(KeyEntry::leaf(3, 5, 1)).unwrap();
+        node.update_slot(node.lower_bound(&20), 6, Some(2)).unwrap();
+        assert!(node.mark_deleted(&21));
+        node.enable_bloom_filter(n_shared as usize, 0.05).unwrap();
+
+        assert_eq!(Some(9), node.shared_page_id());
+        assert_eq!(Ok(()), node.validate());
+        let entry = node.find_entry(&3).unwrap();
+        assert_eq!((5, Some(1)), (entry.page_id(), entry.slot_index()));
+        let entry = node.find_entry(&20).unwrap();
+        assert_eq!((6, Some(2)), (entry.page_id(), entry.slot_index()));
+        assert!(node.find_entry(&21).unwrap().is_deleted());
+        let entry = node.find_entry(&22).unwrap();
+        assert_eq!((9, Some(22)), (entry.page_id(), entry.slot_index()));
+
+        let mut right_page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 2,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut right = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut right_page);
+        node.split_into(&mut right, SplitPolicy::Even);
+        assert_eq!(Some(9), right.shared_page_id());
+        assert_eq!(Ok(()), right.validate());
+
+        node.change_page_type(PageType::IndexNode).unwrap();
+        assert_eq!(None, node.shared_page_id());
+        assert_eq!(Ok(()), node.validate());
+        assert_eq!(3, node.read_key_node(0).key);
+    }
+
+    #[test]
+    pub fn full_shared_leaf_left_unchanged() {
+        let mut page = Page {
+            data: vec![0; 1024].into(),
+            page_id: 1,
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        let mut node = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut page);
+        node.set_shared_page_id(Some(9)).unwrap();
+
+        let mut key = 0;
+        while node.append_key(KeyEntry::leaf(key, 9, key as u16)).is_ok() {
+            key += 1;
+        }
+        let before = node.inner_page().snapshot();
+
+        // Neither an entry for another page nor every entry fits its own page id
+        assert!(matches!(
+            node.update_slot(0, 5, Some(1)),
+            Err(YarddError::PageFull { .. })
+        ));
+        assert_eq!(before, node.inner_page().snapshot());
+
+        assert!(matches!(
+            node.change_page_type(PageType::IndexNode),
+            Err(YarddError::PageFull { .. })
+        ));
+        assert_eq!(before, node.inner_page().snapshot());
+        assert!(node.is_leaf());
     }
 }
@@ -1,23 +1,47 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     error::Error,
-    fs::File,
-    io::{Read, Seek, SeekFrom, Write},
+    fs::{create_dir_all, read_dir, File, OpenOptions},
+    io::{ErrorKind, Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
-use crate::page::{PageId, PAGE_SIZE_BYTES};
+use crate::{
+    error::{DiskError, YarddError},
+    page::{
+        Page, PageId, MAGIC_NUMBER_START, PAGE_ALLOCATED_MAGIC, PAGE_ID_START, PAGE_MAGIC_NUMBER,
+        PAGE_SIZE_BYTES,
+    },
+    serialization_helpers::{read_u32, read_u64, write_u32, write_u64},
+};
+
+// Holds copies of the pages being saved so a torn write can be repaired
+pub const DOUBLE_WRITE_FILE: &str = "double_write.buf";
+
+/// Told about every page read from or written to a backing file, e.g. to
+/// export I/O counters. Unimplemented callbacks do nothing.
+pub trait DiskMetrics: Send + Sync {
+    fn on_read(&self, _page_id: PageId, _bytes: usize, _duration: Duration) {}
+    fn on_write(&self, _page_id: PageId, _bytes: usize, _duration: Duration) {}
+}
 
 struct DiskEntry {
     pub file_path: PathBuf, // Relative path to the base directory
     pub offset: u64,
     pub page_id: PageId,
+    pub written: bool, // Pages that were never saved may lie past the end of their file
 }
 
 pub struct DiskManager {
     page_map: HashMap<PageId, DiskEntry>,
     base_directory: PathBuf, // and maybe file handles...
     next_page_id: PageId,
+    read_only: bool,
+    in_memory: bool, // Nothing is read from or written to the filesystem
+    double_write: bool,
+    metrics: Option<Arc<dyn DiskMetrics>>, // Nothing is timed without one
 }
 
 impl DiskManager {
@@ -26,7 +50,212 @@ impl DiskManager {
             page_map: HashMap::new(),
             base_directory: PathBuf::from(base_directory),
             next_page_id: 0,
+            read_only: false,
+            in_memory: false,
+            double_write: false,
+            metrics: None,
+        }
+    }
+
+    // Hands out page ids without creating any files. Pages read back as empty
+    // and can't be saved, so the caller has to keep modified pages resident.
+    pub fn in_memory() -> Self {
+        let mut disk_manager = DiskManager::new("");
+        disk_manager.in_memory = true;
+        disk_manager
+    }
+
+    // Opens the files already under base_directory without ever writing to them.
+    // Pages are found by the id in their header.
+    pub fn open_read_only(base_directory: &str) -> Result<Self, Box<dyn Error>> {
+        let mut disk_manager = Self::scan(base_directory)?;
+        disk_manager.read_only = true;
+
+        Ok(disk_manager)
+    }
+
+    /// Reopens the files under base_directory for reading and writing. New
+    /// page ids continue past every page already in the files, so they can't
+    /// collide with pages from an earlier session.
+    pub fn open(base_directory: &str) -> Result<Self, Box<dyn Error>> {
+        Self::scan(base_directory)
+    }
+
+    // Finds every page by the id in its header, or the id stamped on it when
+    // it was allocated if it was never written
+    fn scan(base_directory: &str) -> Result<Self, Box<dyn Error>> {
+        let mut disk_manager = DiskManager::new(base_directory);
+
+        let mut file_paths = vec![];
+        collect_files(&disk_manager.base_directory, &mut file_paths)?;
+        file_paths.sort();
+
+        // The double write buffer repeats page ids that live elsewhere
+        let double_write_path = disk_manager.double_write_path();
+        file_paths.retain(|file_path| *file_path != double_write_path);
+
+        for file_path in file_paths {
+            let mut file = File::open(&file_path)?;
+            let mut buffer = vec![0; PAGE_SIZE_BYTES as usize];
+            let mut offset = 0;
+
+            // Files are one byte longer than their pages, so stop at the first partial page
+            while file.read_exact(&mut buffer).is_ok() {
+                let magic_number = read_u32(&buffer, MAGIC_NUMBER_START);
+                if magic_number == PAGE_MAGIC_NUMBER || magic_number == PAGE_ALLOCATED_MAGIC {
+                    let page_id = read_u64(&buffer, PAGE_ID_START);
+                    disk_manager.next_page_id = disk_manager.next_page_id.max(page_id + 1);
+                    disk_manager.page_map.insert(
+                        page_id,
+                        DiskEntry {
+                            file_path: file_path.clone(),
+                            offset,
+                            page_id,
+                            written: magic_number == PAGE_MAGIC_NUMBER,
+                        },
+                    );
+                }
+
+                offset += PAGE_SIZE_BYTES as u64;
+            }
+        }
+
+        Ok(disk_manager)
+    }
+
+    // Where a page lives on disk and whether it has ever been saved
+    pub(crate) fn locate(&self, page_id: PageId) -> Result<(&Path, u64, bool), YarddError> {
+        let page_entry = self
+            .page_map
+            .get(&page_id)
+            .ok_or(YarddError::UnknownPage(page_id))?;
+
+        Ok((&page_entry.file_path, page_entry.offset, page_entry.written))
+    }
+
+    pub(crate) fn mark_written(&mut self, page_id: PageId) -> Result<(), YarddError> {
+        self.page_map
+            .get_mut(&page_id)
+            .ok_or(YarddError::UnknownPage(page_id))?
+            .written = true;
+
+        Ok(())
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn is_in_memory(&self) -> bool {
+        self.in_memory
+    }
+
+    /// With double writes on, pages are first written and synced to a buffer
+    /// file and only then to their real location. A page torn by a crash
+    /// mid-write can be restored from the buffer with `recover_torn_pages`.
+    /// Every save costs an extra write and an fsync.
+    pub fn set_double_write(&mut self, double_write: bool) {
+        self.double_write = double_write;
+    }
+
+    pub fn set_metrics(&mut self, metrics: Option<Arc<dyn DiskMetrics>>) {
+        self.metrics = metrics;
+    }
+
+    // Runs a file operation, reporting it to the metrics if there are any
+    fn timed<T>(
+        &self,
+        op: impl FnOnce() -> T,
+        report: impl FnOnce(&dyn DiskMetrics, &T, Duration),
+    ) -> T {
+        let Some(metrics) = &self.metrics else {
+            return op();
+        };
+
+        let start = Instant::now();
+        let result = op();
+        report(metrics.as_ref(), &result, start.elapsed());
+
+        result
+    }
+
+    fn double_write_path(&self) -> PathBuf {
+        self.base_directory.join(DOUBLE_WRITE_FILE)
+    }
+
+    // Replaces the buffer's contents with the pages about to be saved
+    fn write_double_write_buffer(&self, pages: &[(PageId, &[u8])]) -> Result<(), YarddError> {
+        let mut file = File::options()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(self.double_write_path())?;
+
+        for (_, data) in pages {
+            file.write_all(data)?;
         }
+        file.sync_all()?;
+
+        Ok(())
+    }
+
+    /// Restores pages that fail their header or checksum check from intact
+    /// copies in the double write buffer, and returns their ids. A page that
+    /// checks out is left alone, even if the buffer holds a different version,
+    /// since it was either fully written or never touched.
+    pub fn recover_torn_pages(&mut self) -> Result<Vec<PageId>, Box<dyn Error>> {
+        let mut recovered = vec![];
+
+        let mut file = match File::open(self.double_write_path()) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(recovered),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut buffer = vec![0; PAGE_SIZE_BYTES as usize];
+        while file.read_exact(&mut buffer).is_ok() {
+            let page_id = read_u64(&buffer, PAGE_ID_START);
+            let copy = Page {
+                page_id,
+                data: buffer.clone().into(),
+                is_dirty: false,
+                write_version: 0,
+                write_through: None,
+            };
+
+            let copy_is_intact = copy.is_initialized() && copy.verify_checksum().is_ok();
+            if !copy_is_intact || !self.page_map.contains_key(&page_id) {
+                continue;
+            }
+
+            if self.load_checked_page(page_id).is_err() {
+                self.write_page(page_id, &copy.data)?;
+                recovered.push(page_id);
+            }
+        }
+
+        Ok(recovered)
+    }
+
+    // Why pages can't be saved, if they can't
+    fn check_writable(&self) -> Result<(), DiskError> {
+        if self.read_only {
+            Err(DiskError::ReadOnly)
+        } else if self.in_memory {
+            Err(DiskError::InMemory)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.page_map.len()
+    }
+
+    pub fn page_ids(&self) -> Vec<PageId> {
+        let mut page_ids: Vec<PageId> = self.page_map.keys().copied().collect();
+        page_ids.sort();
+        page_ids
     }
 
     fn next_page_id(&mut self) -> u64 {
@@ -39,20 +268,61 @@ impl DiskManager {
         &mut self,
         pages: usize,
         file_name: &str,
-    ) -> Result<Vec<PageId>, Box<dyn Error>> {
+    ) -> Result<Vec<PageId>, YarddError> {
+        if self.read_only {
+            return Err(DiskError::ReadOnly.into());
+        }
+
         let path = self.base_directory.join(Path::new(file_name));
-        let mut file = File::create(&path)?;
 
-        // Create a file with the size to fill all the pages
-        file.seek(SeekFrom::Start((pages * PAGE_SIZE_BYTES as usize) as u64))?;
-        file.write(&[0])?;
+        // Pages added to a file this manager already knows go after its last page
+        let first_offset = self
+            .page_map
+            .values()
+            .filter(|entry| entry.file_path == path)
+            .map(|entry| entry.offset + PAGE_SIZE_BYTES as u64)
+            .max();
+
+        if self.in_memory {
+            return Ok(self.register_pages(pages, &path, first_offset.unwrap_or(0)));
+        }
+
+        // The base directory (and any directories in file_name) are created on first use
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent).map_err(|source| DiskError::DirectoryCreation {
+                path: parent.to_path_buf(),
+                source,
+            })?;
+        }
+
+        let mut file = match first_offset {
+            Some(_) => OpenOptions::new().write(true).open(&path)?,
+            None => File::create(&path)?,
+        };
+        let first_offset = first_offset.unwrap_or(0);
+
+        // Stamp each page with the id it's about to get, and size the file to fill them all
+        let page_size = PAGE_SIZE_BYTES as usize;
+        let mut buffer = vec![0; pages * page_size + 1];
+        for (i, page) in buffer.chunks_exact_mut(page_size).enumerate() {
+            write_u32(page, MAGIC_NUMBER_START, PAGE_ALLOCATED_MAGIC);
+            write_u64(page, PAGE_ID_START, self.next_page_id + i as PageId);
+        }
+
+        file.seek(SeekFrom::Start(first_offset))?;
+        file.write_all(&buffer)?;
+
+        Ok(self.register_pages(pages, &path, first_offset))
+    }
 
+    fn register_pages(&mut self, pages: usize, path: &Path, first_offset: u64) -> Vec<PageId> {
         let mut page_ids = vec![];
         for i in 0..pages {
             let entry = DiskEntry {
-                file_path: path.clone(),
-                offset: (i as u16 * PAGE_SIZE_BYTES) as u64,
+                file_path: path.to_path_buf(),
+                offset: first_offset + (i * PAGE_SIZE_BYTES as usize) as u64,
                 page_id: self.next_page_id(),
+                written: false,
             };
 
             page_ids.push(entry.page_id);
@@ -60,38 +330,764 @@ impl DiskManager {
             self.page_map.insert(entry.page_id, entry);
         }
 
-        Ok(page_ids)
+        page_ids
     }
 
-    pub fn load_page(&mut self, page_id: PageId) -> Result<Vec<u8>, Box<dyn Error>> {
-        let page_entry = self
-            .page_map
-            .get(&page_id)
-            .expect("Attempt to load page with unknown id");
-
-        let mut file = File::open(&page_entry.file_path)?;
-
-        file.seek(SeekFrom::Start(page_entry.offset))?;
+    pub fn load_page(&self, page_id: PageId) -> Result<Vec<u8>, YarddError> {
         let mut buffer: Vec<u8> = vec![0; PAGE_SIZE_BYTES as usize];
-        file.read_exact(&mut buffer);
+        self.load_page_into(page_id, &mut buffer)?;
 
         Ok(buffer)
     }
 
-    pub fn save_page(&mut self, page_id: PageId, data: &[u8]) -> Result<(), Box<dyn Error>> {
-        let page_entry = self
+    // Like load_page but reads into a caller-provided buffer
+    pub fn load_page_into(&self, page_id: PageId, buffer: &mut [u8]) -> Result<(), YarddError> {
+        let Some((file_path, offset)) = self.read_location(page_id, buffer)? else {
+            return Ok(());
+        };
+
+        let mut file = File::open(file_path)?;
+
+        file.seek(SeekFrom::Start(offset))?;
+
+        let got = self.timed(
+            || read_full(&mut file, buffer),
+            |metrics, got, duration| {
+                if let Ok(got) = got {
+                    metrics.on_read(page_id, *got, duration);
+                }
+            },
+        )?;
+
+        check_full_read(page_id, buffer, got)?;
+
+        Ok(())
+    }
+
+    // Where a page is read from, or None if it reads as zeroes, which `buffer`
+    // is then filled with. Shared with AsyncDiskManager.
+    pub(crate) fn read_location(
+        &self,
+        page_id: PageId,
+        buffer: &mut [u8],
+    ) -> Result<Option<(&Path, u64)>, YarddError> {
+        let (file_path, offset, written) = self.locate(page_id)?;
+
+        // A page that was allocated but never saved reads as empty
+        if self.in_memory || !written {
+            buffer.fill(0);
+            return Ok(None);
+        }
+
+        Ok(Some((file_path, offset)))
+    }
+
+    /// Reads every page of a backing file in offset order without going through
+    /// a buffer pool, checking each page's header and checksum. For repair tools
+    /// that need to find the corrupt pages in a file.
+    pub fn iter_file_pages(
+        &self,
+        file_name: &str,
+    ) -> impl Iterator<Item = (PageId, Result<Page, Box<dyn Error>>)> + '_ {
+        self.pages_in_file(file_name)
+            .into_iter()
+            .map(|page_id| (page_id, self.load_checked_page(page_id)))
+    }
+
+    // The pages stored in a file under base_directory, in offset order
+    pub fn pages_in_file(&self, file_name: &str) -> Vec<PageId> {
+        let path = self.base_directory.join(Path::new(file_name));
+
+        let mut entries: Vec<&DiskEntry> = self
             .page_map
-            .get(&page_id)
-            .expect("Attempt to save a page with unknown id");
+            .values()
+            .filter(|entry| entry.file_path == path)
+            .collect();
+        entries.sort_by_key(|entry| entry.offset);
+
+        entries.into_iter().map(|entry| entry.page_id).collect()
+    }
+
+    /// Forgets every page in the file and deletes it, returning the dropped
+    /// page ids. Their ids aren't handed out again. Callers must make sure no
+    /// pages of the file are still in use, e.g. resident in a buffer pool.
+    pub fn drop_file(&mut self, file_name: &str) -> Result<Vec<PageId>, Box<dyn Error>> {
+        self.check_writable()?;
+
+        let page_ids = self.pages_in_file(file_name);
+        for page_id in &page_ids {
+            self.page_map.remove(page_id);
+        }
+
+        if !self.in_memory {
+            let path = self.base_directory.join(Path::new(file_name));
+            match std::fs::remove_file(path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+
+        Ok(page_ids)
+    }
+
+    /// Shrinks the file to end after its last page that isn't in `free`, so
+    /// the space of free pages at its end goes back to the filesystem. The
+    /// pages cut off are forgotten like in `drop_file` and returned.
+    pub fn truncate_file(
+        &mut self,
+        file_name: &str,
+        free: &HashSet<PageId>,
+    ) -> Result<Vec<PageId>, Box<dyn Error>> {
+        if self.read_only {
+            return Err(DiskError::ReadOnly.into());
+        }
+
+        let mut page_ids = self.pages_in_file(file_name);
+        let n_kept = page_ids
+            .iter()
+            .rposition(|page_id| !free.contains(page_id))
+            .map_or(0, |i| i + 1);
+        let truncated = page_ids.split_off(n_kept);
+        if truncated.is_empty() {
+            return Ok(truncated);
+        }
+
+        let end = page_ids.last().map_or(0, |page_id| {
+            self.page_map[page_id].offset + PAGE_SIZE_BYTES as u64
+        });
+
+        // Files are one byte longer than their pages
+        if !self.in_memory {
+            let path = self.base_directory.join(Path::new(file_name));
+            OpenOptions::new()
+                .write(true)
+                .open(path)?
+                .set_len(end + 1)?;
+        }
+
+        for page_id in &truncated {
+            self.page_map.remove(page_id);
+        }
+
+        Ok(truncated)
+    }
+
+    fn load_checked_page(&self, page_id: PageId) -> Result<Page, Box<dyn Error>> {
+        let page = Page {
+            page_id,
+            data: self.load_page(page_id)?.into(),
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        page.validate_header()?;
+        page.verify_checksum()?;
+
+        Ok(page)
+    }
+
+    pub fn save_page(&mut self, page_id: PageId, data: &[u8]) -> Result<(), YarddError> {
+        self.check_writable()?;
+
+        if self.double_write {
+            self.write_double_write_buffer(&[(page_id, data)])?;
+        }
+
+        self.write_page(page_id, data)
+    }
+
+    fn write_page(&mut self, page_id: PageId, data: &[u8]) -> Result<(), YarddError> {
+        self.mark_written(page_id)?;
+        let (file_path, offset, _) = self.locate(page_id)?;
+
+        let mut file = File::options().write(true).open(file_path)?;
+
+        file.seek(SeekFrom::Start(offset))?;
+        self.timed_write(page_id, || file.write_all(data), data.len())?;
+
+        Ok(())
+    }
+
+    fn timed_write(
+        &self,
+        page_id: PageId,
+        write: impl FnOnce() -> std::io::Result<()>,
+        bytes: usize,
+    ) -> std::io::Result<()> {
+        self.timed(write, |metrics, result, duration| {
+            if result.is_ok() {
+                metrics.on_write(page_id, bytes, duration);
+            }
+        })
+    }
+
+    // Flushes every backing file to stable storage
+    pub fn sync(&mut self) -> Result<(), Box<dyn Error>> {
+        // Nothing can have been written
+        if self.read_only || self.in_memory {
+            return Ok(());
+        }
+
+        let files: HashSet<&PathBuf> = self.page_map.values().map(|e| &e.file_path).collect();
+
+        for file_path in files {
+            File::options().write(true).open(file_path)?.sync_all()?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the page only if its stored checksum matches its contents, so a
+    /// page corrupted in memory isn't persisted.
+    pub fn save_page_checked(&mut self, page: &Page) -> Result<(), YarddError> {
+        page.verify_checksum()?;
+        self.save_page(page.page_id, &page.data)
+    }
+
+    // Writes many pages, opening each backing file once and writing its pages in offset order
+    pub fn save_pages(&mut self, pages: &[(PageId, &[u8])]) -> Result<(), YarddError> {
+        self.check_writable()?;
+
+        if self.double_write {
+            self.write_double_write_buffer(pages)?;
+        }
+
+        for (page_id, _) in pages {
+            self.mark_written(*page_id)?;
+        }
+
+        let mut writes_by_file: HashMap<&PathBuf, Vec<(&DiskEntry, &[u8])>> = HashMap::new();
+
+        for (page_id, data) in pages {
+            let page_entry = self
+                .page_map
+                .get(page_id)
+                .ok_or(YarddError::UnknownPage(*page_id))?;
+
+            writes_by_file
+                .entry(&page_entry.file_path)
+                .or_default()
+                .push((page_entry, data));
+        }
+
+        for (file_path, mut writes) in writes_by_file {
+            writes.sort_by_key(|(page_entry, _)| page_entry.offset);
+
+            let mut file = File::options().write(true).open(file_path)?;
+
+            for (page_entry, data) in writes {
+                file.seek(SeekFrom::Start(page_entry.offset))?;
+                self.timed_write(page_entry.page_id, || file.write_all(data), data.len())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// A saved page that ends early was cut off, e.g. by a crash mid-write
+pub(crate) fn check_full_read(page_id: PageId, buffer: &[u8], got: usize) -> Result<(), DiskError> {
+    if got < buffer.len() {
+        return Err(DiskError::Truncated {
+            page_id,
+            expected: buffer.len(),
+            got,
+        });
+    }
+
+    Ok(())
+}
+
+// Reads until the buffer is full or the file ends, returning the bytes read
+fn read_full(file: &mut File, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut got = 0;
+    while got < buffer.len() {
+        match file.read(&mut buffer[got..]) {
+            Ok(0) => break,
+            Ok(n) => got += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(got)
+}
+
+fn collect_files(directory: &Path, file_paths: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    for entry in read_dir(directory)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, file_paths)?;
+        } else {
+            file_paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod disk_manager_tests {
+    use std::{
+        fs::{create_dir_all, remove_dir_all, File},
+        io::{Seek, SeekFrom, Write},
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use crate::{
+        error::YarddError,
+        page::{
+            Page, PageHeader, PageId, PageType, HEADER_SIZE, PAGE_MAGIC_NUMBER, PAGE_SIZE_BYTES,
+        },
+    };
+
+    use super::{DiskManager, DiskMetrics};
+
+    fn setup_test_dir(base_dir: &str) {
+        create_dir_all(base_dir).expect("Failed to create test directory.");
+    }
+
+    #[test]
+    pub fn checked_write_rejects_corrupt_page() {
+        let base_dir = "./test30";
+        setup_test_dir(base_dir);
+
+        let mut disk_manager = DiskManager::new(base_dir);
+        let page_id = disk_manager.allocate_pages(1, "pages.db").unwrap()[0];
+
+        let mut page = Page {
+            page_id,
+            data: vec![0; 1024].into(),
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        page.write_header(PageHeader {
+            magic_number: PAGE_MAGIC_NUMBER,
+            page_type: PageType::DataPage,
+            log_sequence_number: 0,
+            parent_page_id: 0,
+            page_id,
+        });
+        page.data[HEADER_SIZE..].fill(7);
+        page.update_checksum();
+
+        disk_manager.save_page_checked(&page).unwrap();
+
+        // Flip a bit in the body without restamping the checksum
+        page.data[HEADER_SIZE + 10] ^= 1;
+        assert!(disk_manager.save_page_checked(&page).is_err());
+
+        let on_disk = disk_manager.load_page(page_id).unwrap();
+        assert_eq!(7, on_disk[HEADER_SIZE + 10]);
+
+        let _ = remove_dir_all(base_dir);
+    }
+
+    #[test]
+    pub fn allocate_creates_missing_directories() {
+        let base_dir = "./test75/nested/dir";
+        let _ = remove_dir_all("./test75");
+
+        let mut disk_manager = DiskManager::new(base_dir);
+        let page_ids = disk_manager.allocate_pages(2, "sub/pages.db").unwrap();
+
+        assert_eq!(vec![0, 1], page_ids);
+        assert!(std::path::Path::new(base_dir).join("sub/pages.db").exists());
+
+        let _ = remove_dir_all("./test75");
+    }
+
+    #[test]
+    pub fn directory_creation_failure_is_reported() {
+        let base_dir = "./test35";
+        setup_test_dir(base_dir);
+        std::fs::write("./test35/blocker", b"").unwrap();
+
+        // A regular file sits where a directory should be
+        let mut disk_manager = DiskManager::new("./test35/blocker/db");
+        let err = disk_manager.allocate_pages(1, "pages.db").unwrap_err();
+
+        assert!(matches!(err, YarddError::Io(_)));
+        assert!(
+            err.to_string().contains("failed to create directory"),
+            "{}",
+            err
+        );
+
+        let _ = remove_dir_all(base_dir);
+    }
+
+    fn truncate(path: &str, len: u64) {
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_len(len)
+            .unwrap();
+    }
+
+    #[test]
+    pub fn truncated_page_is_reported() {
+        let base_dir = "./test41";
+        setup_test_dir(base_dir);
+
+        let mut disk_manager = DiskManager::new(base_dir);
+        let page_ids = disk_manager.allocate_pages(2, "pages.db").unwrap();
+        disk_manager.save_page(page_ids[1], &[9; 1024]).unwrap();
+
+        truncate("./test41/pages.db", 1024 + 512);
+
+        assert_eq!(
+            Err(YarddError::Corruption(page_ids[1])),
+            disk_manager.load_page(page_ids[1])
+        );
+
+        let _ = remove_dir_all(base_dir);
+    }
 
+    #[test]
+    pub fn unwritten_page_past_file_end_reads_as_zeroes() {
+        let base_dir = "./test42";
+        setup_test_dir(base_dir);
+
+        let mut disk_manager = DiskManager::new(base_dir);
+        let page_ids = disk_manager.allocate_pages(2, "pages.db").unwrap();
+        disk_manager.save_page(page_ids[0], &[9; 1024]).unwrap();
+
+        // The tail page never made it into the file
+        truncate("./test42/pages.db", 1024);
+
+        assert_eq!(vec![0; 1024], disk_manager.load_page(page_ids[1]).unwrap());
+        assert_eq!(vec![9; 1024], disk_manager.load_page(page_ids[0]).unwrap());
+
+        let _ = remove_dir_all(base_dir);
+    }
+
+    #[test]
+    pub fn iter_file_pages_flags_corrupt_page() {
+        let base_dir = "./test56";
+        setup_test_dir(base_dir);
+
+        let mut disk_manager = DiskManager::new(base_dir);
+        disk_manager.allocate_pages(2, "other.db").unwrap();
+        let page_ids = disk_manager.allocate_pages(4, "pages.db").unwrap();
+
+        for page_id in page_ids.iter().take(3) {
+            let mut page = Page {
+                page_id: *page_id,
+                data: vec![0; 1024].into(),
+                is_dirty: false,
+                write_version: 0,
+                write_through: None,
+            };
+            page.write_header(PageHeader {
+                magic_number: PAGE_MAGIC_NUMBER,
+                page_type: PageType::DataPage,
+                log_sequence_number: 0,
+                parent_page_id: 0,
+                page_id: *page_id,
+            });
+            page.update_checksum();
+
+            // Flip a byte after the checksum was taken
+            if *page_id == page_ids[1] {
+                page.data[HEADER_SIZE] ^= 0xFF;
+            }
+
+            disk_manager.save_page(*page_id, &page.data).unwrap();
+        }
+
+        let results: Vec<_> = disk_manager.iter_file_pages("pages.db").collect();
+        assert_eq!(
+            page_ids,
+            results
+                .iter()
+                .map(|(page_id, _)| *page_id)
+                .collect::<Vec<_>>()
+        );
+
+        let corrupt: Vec<_> = results
+            .iter()
+            .filter(|(_, page)| page.is_err())
+            .map(|(page_id, _)| *page_id)
+            .collect();
+        assert_eq!(vec![page_ids[1]], corrupt);
+
+        let error = results[1].1.as_ref().unwrap_err();
+        assert_eq!(
+            Some(&YarddError::Corruption(page_ids[1])),
+            error.downcast_ref::<YarddError>()
+        );
+
+        // The page that was never written reads as empty
+        let unwritten = results[3].1.as_ref().unwrap();
+        assert!(!unwritten.is_initialized());
+
+        let _ = remove_dir_all(base_dir);
+    }
+
+    #[test]
+    pub fn torn_write_recovered_from_double_write_buffer() {
+        let base_dir = "./test57";
+        setup_test_dir(base_dir);
+
+        let mut disk_manager = DiskManager::new(base_dir);
+        disk_manager.set_double_write(true);
+        let page_ids = disk_manager.allocate_pages(2, "pages.db").unwrap();
+
+        let versions: Vec<Vec<u8>> = (1..=2)
+            .map(|version| {
+                let mut page = Page {
+                    page_id: page_ids[1],
+                    data: vec![version; 1024].into(),
+                    is_dirty: false,
+                    write_version: 0,
+                    write_through: None,
+                };
+                page.write_header(PageHeader {
+                    magic_number: PAGE_MAGIC_NUMBER,
+                    page_type: PageType::DataPage,
+                    log_sequence_number: version as u64,
+                    parent_page_id: 0,
+                    page_id: page_ids[1],
+                });
+                page.update_checksum();
+                page.data.to_vec()
+            })
+            .collect();
+
+        disk_manager.save_page(page_ids[1], &versions[0]).unwrap();
+        disk_manager.save_page(page_ids[1], &versions[1]).unwrap();
+        assert!(disk_manager.recover_torn_pages().unwrap().is_empty());
+
+        // Tear the last write: only its first half reached the file
+        let mut torn = versions[1].clone();
+        torn[512..].copy_from_slice(&versions[0][512..]);
         let mut file = File::options()
             .write(true)
-            .open(&page_entry.file_path)
-            .expect("Failed to open file.");
+            .open("./test57/pages.db")
+            .unwrap();
+        file.seek(SeekFrom::Start(1024)).unwrap();
+        file.write_all(&torn).unwrap();
+        drop(file);
 
-        file.seek(SeekFrom::Start(page_entry.offset))?;
-        file.write_all(data)?;
+        assert_eq!(
+            vec![page_ids[1]],
+            disk_manager.recover_torn_pages().unwrap()
+        );
+        assert_eq!(versions[1], disk_manager.load_page(page_ids[1]).unwrap());
 
-        Ok(())
+        // The buffer isn't mistaken for a data file
+        let read_only = DiskManager::open_read_only(base_dir).unwrap();
+        assert_eq!(page_ids, read_only.page_ids());
+
+        let _ = remove_dir_all(base_dir);
+    }
+
+    #[derive(Default)]
+    struct RecordingMetrics {
+        ops: Mutex<Vec<(&'static str, PageId, usize)>>,
+    }
+
+    impl DiskMetrics for RecordingMetrics {
+        fn on_read(&self, page_id: PageId, bytes: usize, _duration: Duration) {
+            self.ops.lock().unwrap().push(("read", page_id, bytes));
+        }
+
+        fn on_write(&self, page_id: PageId, bytes: usize, _duration: Duration) {
+            self.ops.lock().unwrap().push(("write", page_id, bytes));
+        }
+    }
+
+    #[test]
+    pub fn metrics_see_every_read_and_write() {
+        let base_dir = "./test62";
+        setup_test_dir(base_dir);
+
+        let metrics = Arc::new(RecordingMetrics::default());
+        let mut disk_manager = DiskManager::new(base_dir);
+        disk_manager.set_metrics(Some(metrics.clone()));
+
+        let page_ids = disk_manager.allocate_pages(3, "pages.db").unwrap();
+        let data = vec![5; PAGE_SIZE_BYTES as usize];
+
+        disk_manager.save_page(page_ids[0], &data).unwrap();
+        disk_manager
+            .save_pages(&[(page_ids[2], &data), (page_ids[1], &data)])
+            .unwrap();
+        disk_manager.load_page(page_ids[1]).unwrap();
+
+        // A short read reports the bytes actually in the file
+        disk_manager
+            .load_page_into(page_ids[2], &mut [0; 100])
+            .unwrap();
+
+        let page_size = PAGE_SIZE_BYTES as usize;
+        assert_eq!(
+            vec![
+                ("write", page_ids[0], page_size),
+                ("write", page_ids[1], page_size),
+                ("write", page_ids[2], page_size),
+                ("read", page_ids[1], page_size),
+                ("read", page_ids[2], 100),
+            ],
+            *metrics.ops.lock().unwrap()
+        );
+
+        // Without metrics nothing is reported
+        disk_manager.set_metrics(None);
+        disk_manager.load_page(page_ids[0]).unwrap();
+        assert_eq!(5, metrics.ops.lock().unwrap().len());
+
+        let _ = remove_dir_all(base_dir);
+    }
+
+    fn save_with_header(disk_manager: &mut DiskManager, page_id: PageId) {
+        let mut page = Page {
+            page_id,
+            data: vec![0; PAGE_SIZE_BYTES as usize].into(),
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        page.write_header(PageHeader {
+            magic_number: PAGE_MAGIC_NUMBER,
+            page_type: PageType::DataPage,
+            log_sequence_number: 0,
+            parent_page_id: 0,
+            page_id,
+        });
+        page.update_checksum();
+
+        disk_manager.save_page(page_id, &page.data).unwrap();
+    }
+
+    #[test]
+    pub fn reopened_manager_allocates_past_existing_pages() {
+        let base_dir = "./test63";
+        let _ = remove_dir_all(base_dir);
+
+        let mut disk_manager = DiskManager::new(base_dir);
+        let mut first = disk_manager.allocate_pages(3, "a.db").unwrap();
+        let second = disk_manager.allocate_pages(2, "b.db").unwrap();
+        first.extend(disk_manager.allocate_pages(2, "a.db").unwrap());
+        save_with_header(&mut disk_manager, first[0]);
+        save_with_header(&mut disk_manager, first[2]);
+        drop(disk_manager);
+
+        let mut disk_manager = DiskManager::open(base_dir).unwrap();
+
+        // Unwritten pages are found by the id stamped on them, even in a file
+        // grown more than once or one with no written pages
+        assert_eq!(first, disk_manager.pages_in_file("a.db"));
+        assert_eq!(second, disk_manager.pages_in_file("b.db"));
+        assert!(disk_manager
+            .load_page(first[1])
+            .unwrap()
+            .iter()
+            .all(|b| *b == 0));
+        assert_eq!(
+            first[2],
+            Page {
+                page_id: first[2],
+                data: disk_manager.load_page(first[2]).unwrap().into(),
+                is_dirty: false,
+                write_version: 0,
+                write_through: None,
+            }
+            .read_header()
+            .page_id
+        );
+
+        let third = disk_manager.allocate_pages(2, "c.db").unwrap();
+        for page_id in &third {
+            assert!(!first.contains(page_id));
+            assert!(!second.contains(page_id));
+        }
+
+        save_with_header(&mut disk_manager, first[1]);
+        assert!(!disk_manager.is_read_only());
+
+        let _ = remove_dir_all(base_dir);
+    }
+
+    #[test]
+    pub fn pages_grouped_by_file() {
+        let base_dir = "./test64";
+        let _ = remove_dir_all(base_dir);
+
+        let mut disk_manager = DiskManager::new(base_dir);
+        let users = disk_manager.allocate_pages(3, "users.db").unwrap();
+        let orders = disk_manager.allocate_pages(2, "orders.db").unwrap();
+        let more_users = disk_manager.allocate_pages(1, "tables/users.db").unwrap();
+
+        assert_eq!(users, disk_manager.pages_in_file("users.db"));
+        assert_eq!(orders, disk_manager.pages_in_file("orders.db"));
+        assert_eq!(more_users, disk_manager.pages_in_file("tables/users.db"));
+        assert!(disk_manager.pages_in_file("missing.db").is_empty());
+
+        assert_eq!(orders, disk_manager.drop_file("orders.db").unwrap());
+        assert!(disk_manager.pages_in_file("orders.db").is_empty());
+        assert!(!std::path::Path::new(base_dir).join("orders.db").exists());
+        assert!(disk_manager.load_page(orders[0]).is_err());
+        assert_eq!(4, disk_manager.page_count());
+
+        // Dropped ids stay retired
+        let new_pages = disk_manager.allocate_pages(2, "orders.db").unwrap();
+        assert!(new_pages.iter().all(|page_id| !orders.contains(page_id)));
+
+        let _ = remove_dir_all(base_dir);
+    }
+
+    #[test]
+    pub fn allocating_again_grows_the_file() {
+        let base_dir = "./test70";
+        setup_test_dir(base_dir);
+
+        let mut disk_manager = DiskManager::new(base_dir);
+        let mut page_ids = disk_manager.allocate_pages(2, "pages.db").unwrap();
+        disk_manager.allocate_pages(1, "other.db").unwrap();
+        page_ids.extend(disk_manager.allocate_pages(2, "pages.db").unwrap());
+
+        assert_eq!(page_ids, disk_manager.pages_in_file("pages.db"));
+        let len = std::fs::metadata(format!("{base_dir}/pages.db"))
+            .unwrap()
+            .len();
+        assert_eq!(4 * PAGE_SIZE_BYTES as u64 + 1, len);
+
+        for (i, page_id) in page_ids.iter().enumerate() {
+            disk_manager
+                .save_page(*page_id, &[i as u8 + 1; 1024])
+                .unwrap();
+        }
+        for (i, page_id) in page_ids.iter().enumerate() {
+            assert_eq!(
+                vec![i as u8 + 1; 1024],
+                disk_manager.load_page(*page_id).unwrap()
+            );
+        }
+
+        let _ = remove_dir_all(base_dir);
+    }
+
+    #[test]
+    pub fn pages_past_u16_offsets_keep_their_own_data() {
+        let base_dir = "./test77";
+        let _ = remove_dir_all(base_dir);
+
+        // Page 64 starts at byte 65536, which doesn't fit a u16
+        let mut disk_manager = DiskManager::new(base_dir);
+        let page_ids = disk_manager.allocate_pages(66, "pages.db").unwrap();
+
+        disk_manager.save_page(page_ids[0], &[1; 1024]).unwrap();
+        disk_manager.save_page(page_ids[64], &[2; 1024]).unwrap();
+
+        assert_eq!(vec![1; 1024], disk_manager.load_page(page_ids[0]).unwrap());
+        assert_eq!(vec![2; 1024], disk_manager.load_page(page_ids[64]).unwrap());
+
+        let _ = remove_dir_all(base_dir);
     }
 }
@@ -0,0 +1,338 @@
+use std::{error::Error, fmt, io, path::PathBuf};
+
+use crate::page::{PageId, PageType};
+
+#[derive(Debug)]
+pub enum YarddError {
+    Io(io::Error),
+    UnknownPage(PageId),
+    Corruption(PageId),
+    // Written by an older version with a different header layout
+    UnsupportedLayout(PageId),
+    PageFull {
+        page_id: PageId,
+        needed: usize,
+        available: usize,
+    },
+    ReadOnly,
+    InMemory,
+    // Every page in the buffer pool is in use, or there are no free pages to hand out
+    PoolExhausted,
+    // An index entry too large for a page to hold two of, which splitting needs.
+    // Only keys that can't be truncated into an overflow chain get this large.
+    KeyTooLarge {
+        len: usize,
+        max: usize,
+    },
+    // A page was read as a type it doesn't have
+    WrongPageType {
+        page_id: PageId,
+        actual: PageType,
+    },
+}
+
+impl fmt::Display for YarddError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            YarddError::Io(error) => write!(f, "i/o error: {}", error),
+            YarddError::UnknownPage(page_id) => write!(f, "unknown page id {}", page_id),
+            YarddError::Corruption(page_id) => write!(f, "page {} is corrupt", page_id),
+            YarddError::UnsupportedLayout(page_id) => {
+                write!(f, "page {} uses an unsupported header layout", page_id)
+            }
+            YarddError::PageFull {
+                page_id,
+                needed,
+                available,
+            } => write!(
+                f,
+                "page {} full: need {} bytes, have {}",
+                page_id, needed, available
+            ),
+            YarddError::ReadOnly => write!(f, "database was opened read-only"),
+            YarddError::InMemory => write!(f, "database has no disk backing"),
+            YarddError::PoolExhausted => write!(f, "no page available in the buffer pool"),
+            YarddError::KeyTooLarge { len, max } => write!(
+                f,
+                "index entry of {} bytes is larger than the {} byte maximum",
+                len, max
+            ),
+            YarddError::WrongPageType { page_id, actual } => {
+                write!(f, "page {} has unexpected type {:?}", page_id, actual)
+            }
+        }
+    }
+}
+
+// io::Error isn't comparable, so I/O errors are equal when their kinds are
+impl PartialEq for YarddError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (YarddError::Io(a), YarddError::Io(b)) => a.kind() == b.kind(),
+            (YarddError::UnknownPage(a), YarddError::UnknownPage(b)) => a == b,
+            (YarddError::Corruption(a), YarddError::Corruption(b)) => a == b,
+            (YarddError::UnsupportedLayout(a), YarddError::UnsupportedLayout(b)) => a == b,
+            (
+                YarddError::PageFull {
+                    page_id,
+                    needed,
+                    available,
+                },
+                YarddError::PageFull {
+                    page_id: other_page_id,
+                    needed: other_needed,
+                    available: other_available,
+                },
+            ) => page_id == other_page_id && needed == other_needed && available == other_available,
+            (YarddError::ReadOnly, YarddError::ReadOnly) => true,
+            (YarddError::InMemory, YarddError::InMemory) => true,
+            (YarddError::PoolExhausted, YarddError::PoolExhausted) => true,
+            (
+                YarddError::KeyTooLarge { len, max },
+                YarddError::KeyTooLarge {
+                    len: other_len,
+                    max: other_max,
+                },
+            ) => len == other_len && max == other_max,
+            (
+                YarddError::WrongPageType { page_id, actual },
+                YarddError::WrongPageType {
+                    page_id: other_page_id,
+                    actual: other_actual,
+                },
+            ) => page_id == other_page_id && actual == other_actual,
+            _ => false,
+        }
+    }
+}
+
+impl Error for YarddError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            YarddError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for YarddError {
+    fn from(error: io::Error) -> Self {
+        YarddError::Io(error)
+    }
+}
+
+// A short read of a page that was written is a torn page
+impl From<DiskError> for YarddError {
+    fn from(error: DiskError) -> Self {
+        match error {
+            DiskError::Truncated { page_id, .. } => YarddError::Corruption(page_id),
+            DiskError::ReadOnly => YarddError::ReadOnly,
+            DiskError::InMemory => YarddError::InMemory,
+            DiskError::DirectoryCreation { ref source, .. } => {
+                YarddError::Io(io::Error::new(source.kind(), error.to_string()))
+            }
+        }
+    }
+}
+
+// An insert into a unique index found the key already present
+#[derive(Debug, PartialEq)]
+pub struct DuplicateKey<KeyType>(pub KeyType);
+
+// A page type byte that doesn't match any PageType
+#[derive(Debug, PartialEq)]
+pub struct UnknownPageType(pub u8);
+
+// A PageManagerBuilder was given settings that can't be combined
+#[derive(Debug, PartialEq)]
+pub struct InvalidConfig(pub &'static str);
+
+impl fmt::Display for InvalidConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid page manager configuration: {}", self.0)
+    }
+}
+
+impl Error for InvalidConfig {}
+
+#[derive(Debug)]
+pub enum DiskError {
+    DirectoryCreation {
+        path: PathBuf,
+        source: io::Error,
+    },
+    ReadOnly,
+    InMemory,
+    Truncated {
+        page_id: PageId,
+        expected: usize,
+        got: usize,
+    },
+}
+
+impl fmt::Display for DiskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiskError::DirectoryCreation { path, source } => {
+                write!(
+                    f,
+                    "failed to create directory {}: {}",
+                    path.display(),
+                    source
+                )
+            }
+            DiskError::ReadOnly => write!(f, "database was opened read-only"),
+            DiskError::InMemory => write!(f, "database has no disk backing"),
+            DiskError::Truncated {
+                page_id,
+                expected,
+                got,
+            } => write!(
+                f,
+                "page {} truncated: expected {} bytes, got {}",
+                page_id, expected, got
+            ),
+        }
+    }
+}
+
+impl Error for DiskError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DiskError::DirectoryCreation { source, .. } => Some(source),
+            DiskError::ReadOnly | DiskError::InMemory | DiskError::Truncated { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod error_tests {
+    use std::{error::Error, io, path::PathBuf};
+
+    use crate::page::PageType;
+
+    use super::{DiskError, YarddError};
+
+    #[test]
+    pub fn yardd_error_display() {
+        let cases = [
+            (
+                YarddError::Io(io::Error::new(io::ErrorKind::NotFound, "no file")),
+                "i/o error: no file",
+            ),
+            (YarddError::UnknownPage(42), "unknown page id 42"),
+            (YarddError::Corruption(3), "page 3 is corrupt"),
+            (
+                YarddError::UnsupportedLayout(4),
+                "page 4 uses an unsupported header layout",
+            ),
+            (
+                YarddError::PageFull {
+                    page_id: 7,
+                    needed: 900,
+                    available: 200,
+                },
+                "page 7 full: need 900 bytes, have 200",
+            ),
+            (YarddError::ReadOnly, "database was opened read-only"),
+            (YarddError::InMemory, "database has no disk backing"),
+            (
+                YarddError::PoolExhausted,
+                "no page available in the buffer pool",
+            ),
+            (
+                YarddError::KeyTooLarge {
+                    len: 5000,
+                    max: 4000,
+                },
+                "index entry of 5000 bytes is larger than the 4000 byte maximum",
+            ),
+            (
+                YarddError::WrongPageType {
+                    page_id: 8,
+                    actual: PageType::DataPage,
+                },
+                "page 8 has unexpected type DataPage",
+            ),
+        ];
+
+        for (error, message) in cases {
+            assert_eq!(message, error.to_string());
+        }
+    }
+
+    #[test]
+    pub fn disk_error_display() {
+        let cases = [
+            (
+                DiskError::DirectoryCreation {
+                    path: PathBuf::from("db/sub"),
+                    source: io::Error::new(io::ErrorKind::PermissionDenied, "denied"),
+                },
+                "failed to create directory db/sub: denied",
+            ),
+            (DiskError::ReadOnly, "database was opened read-only"),
+            (DiskError::InMemory, "database has no disk backing"),
+            (
+                DiskError::Truncated {
+                    page_id: 2,
+                    expected: 1024,
+                    got: 10,
+                },
+                "page 2 truncated: expected 1024 bytes, got 10",
+            ),
+        ];
+
+        for (error, message) in cases {
+            assert_eq!(message, error.to_string());
+        }
+    }
+
+    #[test]
+    pub fn io_errors_convert_with_question_mark() {
+        fn open() -> Result<(), YarddError> {
+            Err(io::Error::new(io::ErrorKind::NotFound, "no file"))?
+        }
+
+        let error = open().unwrap_err();
+        assert_eq!(YarddError::Io(io::ErrorKind::NotFound.into()), error);
+        assert_eq!("no file", error.source().unwrap().to_string());
+    }
+
+    #[test]
+    pub fn disk_errors_convert_to_matching_variants() {
+        let cases: [(DiskError, YarddError); 4] = [
+            (DiskError::ReadOnly, YarddError::ReadOnly),
+            (DiskError::InMemory, YarddError::InMemory),
+            (
+                DiskError::Truncated {
+                    page_id: 2,
+                    expected: 1024,
+                    got: 10,
+                },
+                YarddError::Corruption(2),
+            ),
+            (
+                DiskError::DirectoryCreation {
+                    path: PathBuf::from("db/sub"),
+                    source: io::Error::new(io::ErrorKind::PermissionDenied, "denied"),
+                },
+                YarddError::Io(io::ErrorKind::PermissionDenied.into()),
+            ),
+        ];
+
+        for (error, expected) in cases {
+            assert_eq!(expected, YarddError::from(error));
+        }
+
+        // The path that couldn't be created stays in the message
+        let error = YarddError::from(DiskError::DirectoryCreation {
+            path: PathBuf::from("db/sub"),
+            source: io::Error::new(io::ErrorKind::PermissionDenied, "denied"),
+        });
+        assert_eq!(
+            "i/o error: failed to create directory db/sub: denied",
+            error.to_string()
+        );
+    }
+}
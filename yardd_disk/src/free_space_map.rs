@@ -0,0 +1,86 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::page::{Page, PageId, PageType};
+
+// Approximate free bytes per data page, so an inserter can pick a page with
+// room without reading any. Hints are refreshed whenever the page manager
+// loads or writes a page, and can lag behind changes still in memory.
+#[derive(Default)]
+pub struct FreeSpaceMap {
+    hints: HashMap<PageId, usize>,
+    ranked: BTreeSet<(usize, PageId)>,
+}
+
+impl FreeSpaceMap {
+    pub fn new() -> Self {
+        FreeSpaceMap::default()
+    }
+
+    pub fn record(&mut self, page_id: PageId, free_bytes: usize) {
+        if let Some(old) = self.hints.insert(page_id, free_bytes) {
+            self.ranked.remove(&(old, page_id));
+        }
+        self.ranked.insert((free_bytes, page_id));
+    }
+
+    // Only data pages have a hint, other pages are ignored
+    pub fn record_page(&mut self, page: &Page) {
+        if !page.is_initialized() || page.try_read_page_type() != Ok(PageType::DataPage) {
+            return;
+        }
+
+        let free_bytes = page.as_data_page().free_space();
+        self.record(page.page_id, free_bytes);
+    }
+
+    pub fn remove(&mut self, page_id: PageId) {
+        if let Some(old) = self.hints.remove(&page_id) {
+            self.ranked.remove(&(old, page_id));
+        }
+    }
+
+    pub fn get(&self, page_id: PageId) -> Option<usize> {
+        self.hints.get(&page_id).copied()
+    }
+
+    // The emptiest page, if it has at least `needed` bytes
+    pub fn page_with_room(&self, needed: usize) -> Option<PageId> {
+        self.ranked
+            .last()
+            .filter(|(free_bytes, _)| *free_bytes >= needed)
+            .map(|(_, page_id)| *page_id)
+    }
+
+    // Most free space first
+    pub fn ranked(&self) -> impl Iterator<Item = (PageId, usize)> + '_ {
+        self.ranked
+            .iter()
+            .rev()
+            .map(|(free_bytes, page_id)| (*page_id, *free_bytes))
+    }
+
+    pub fn len(&self) -> usize {
+        self.hints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hints.is_empty()
+    }
+}
+
+#[test]
+fn record_replaces_old_hint() {
+    let mut map = FreeSpaceMap::new();
+    map.record(1, 100);
+    map.record(2, 50);
+    map.record(1, 10);
+
+    assert_eq!(Some(10), map.get(1));
+    assert_eq!(vec![(2, 50), (1, 10)], map.ranked().collect::<Vec<_>>());
+    assert_eq!(Some(2), map.page_with_room(50));
+    assert_eq!(None, map.page_with_room(51));
+
+    map.remove(2);
+    assert_eq!(1, map.len());
+    assert_eq!(Some(1), map.page_with_room(0));
+}
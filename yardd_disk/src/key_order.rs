@@ -0,0 +1,214 @@
+use std::{cmp::Ordering, fmt, marker::PhantomData};
+
+use crate::page::DbColumn;
+
+/// The logical order of an index's keys. Implement this for a type of your own
+/// to index with a custom collation.
+pub trait KeyOrder<KeyType> {
+    fn compare(a: &KeyType, b: &KeyType) -> Ordering;
+
+    // See `DbColumn::compare_bytes`
+    fn compare_bytes(search_key: &KeyType, page_bytes: &[u8], start: usize) -> Ordering
+    where
+        KeyType: DbColumn,
+    {
+        Self::compare(search_key, &KeyType::from_bytes(page_bytes, start))
+    }
+}
+
+pub struct Ascending;
+
+pub struct Descending;
+
+impl<KeyType: DbColumn> KeyOrder<KeyType> for Ascending {
+    fn compare(a: &KeyType, b: &KeyType) -> Ordering {
+        a.cmp_key(b)
+    }
+
+    fn compare_bytes(search_key: &KeyType, page_bytes: &[u8], start: usize) -> Ordering {
+        KeyType::compare_bytes(search_key, page_bytes, start)
+    }
+}
+
+impl<KeyType: DbColumn> KeyOrder<KeyType> for Descending {
+    fn compare(a: &KeyType, b: &KeyType) -> Ordering {
+        b.cmp_key(a)
+    }
+
+    fn compare_bytes(search_key: &KeyType, page_bytes: &[u8], start: usize) -> Ordering {
+        KeyType::compare_bytes(search_key, page_bytes, start).reverse()
+    }
+}
+
+/// A key that sorts by `Order` instead of its own ordering. It's stored exactly
+/// like the plain key, so e.g. `BTree<OrderedKey<u64, Descending>>` keeps the
+/// same page layout as `BTree<u64>` but scans largest first.
+pub struct OrderedKey<KeyType, Order> {
+    key: KeyType,
+    order: PhantomData<Order>,
+}
+
+pub type DescendingKey<KeyType> = OrderedKey<KeyType, Descending>;
+
+impl<KeyType, Order> OrderedKey<KeyType, Order> {
+    pub fn new(key: KeyType) -> Self {
+        OrderedKey {
+            key,
+            order: PhantomData,
+        }
+    }
+
+    pub fn inner(&self) -> &KeyType {
+        &self.key
+    }
+
+    pub fn into_inner(self) -> KeyType {
+        self.key
+    }
+}
+
+impl<KeyType, Order> From<KeyType> for OrderedKey<KeyType, Order> {
+    fn from(key: KeyType) -> Self {
+        Self::new(key)
+    }
+}
+
+impl<KeyType: Clone, Order> Clone for OrderedKey<KeyType, Order> {
+    fn clone(&self) -> Self {
+        Self::new(self.key.clone())
+    }
+}
+
+impl<KeyType: fmt::Debug, Order> fmt::Debug for OrderedKey<KeyType, Order> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.key.fmt(f)
+    }
+}
+
+// Keys the order considers equal are equal, so e.g. a case insensitive
+// collation finds "abc" when searching for "ABC"
+impl<KeyType, Order: KeyOrder<KeyType>> PartialEq for OrderedKey<KeyType, Order> {
+    fn eq(&self, other: &Self) -> bool {
+        Order::compare(&self.key, &other.key).is_eq()
+    }
+}
+
+impl<KeyType, Order: KeyOrder<KeyType>> Eq for OrderedKey<KeyType, Order> {}
+
+impl<KeyType, Order: KeyOrder<KeyType>> PartialOrd for OrderedKey<KeyType, Order> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<KeyType, Order: KeyOrder<KeyType>> Ord for OrderedKey<KeyType, Order> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        Order::compare(&self.key, &other.key)
+    }
+}
+
+impl<KeyType: DbColumn, Order: KeyOrder<KeyType>> DbColumn for OrderedKey<KeyType, Order> {
+    fn from_bytes(bytes: &[u8], start: usize) -> Self {
+        Self::new(KeyType::from_bytes(bytes, start))
+    }
+
+    fn write_to(&self, buf: &mut [u8]) -> usize {
+        self.key.write_to(buf)
+    }
+
+    fn len(&self) -> usize {
+        self.key.len()
+    }
+
+    fn cmp_key(&self, other: &Self) -> Ordering {
+        Order::compare(&self.key, &other.key)
+    }
+
+    fn compare_bytes(search_key: &Self, page_bytes: &[u8], start: usize) -> Ordering {
+        Order::compare_bytes(&search_key.key, page_bytes, start)
+    }
+
+    fn encoded_len(bytes: &[u8], start: usize) -> usize {
+        KeyType::encoded_len(bytes, start)
+    }
+
+    fn truncated(&self, max_len: usize) -> Option<Self> {
+        self.key.truncated(max_len).map(Self::new)
+    }
+}
+
+#[cfg(test)]
+mod key_order_tests {
+    use std::{
+        cmp::Ordering,
+        sync::{Arc, Mutex},
+    };
+
+    use crate::{
+        btree::BTree, cursor::Cursor, disk_btree::KeyEntry, page::DbColumn,
+        page_manager::PageManager,
+    };
+
+    use super::{DescendingKey, KeyOrder, OrderedKey};
+
+    #[test]
+    pub fn descending_index_scans_largest_first() {
+        let manager = Arc::new(Mutex::new(PageManager::in_memory(16)));
+        manager.lock().unwrap().add_empty_pages("index.db", 16);
+
+        let tree = BTree::<DescendingKey<u64>>::new(manager.clone()).unwrap();
+        for key in [5, 300, 42, 0, 299] {
+            tree.insert(KeyEntry::leaf(key.into(), key, 0)).unwrap();
+        }
+        for key in 100..250 {
+            tree.insert(KeyEntry::leaf(key.into(), key, 0)).unwrap();
+        }
+
+        let keys: Vec<u64> = Cursor::new(&tree)
+            .unwrap()
+            .map(|entry| *entry.unwrap().key().inner())
+            .collect();
+
+        let mut expected: Vec<u64> = [5, 300, 42, 0, 299].into_iter().chain(100..250).collect();
+        expected.sort_by(|a, b| b.cmp(a));
+        assert_eq!(expected, keys);
+
+        assert_eq!(
+            Some(42),
+            tree.search(&42.into())
+                .unwrap()
+                .map(|entry| entry.page_id())
+        );
+        assert!(!tree.contains(&43.into()).unwrap());
+    }
+
+    // Even keys first, each half ascending
+    struct EvensFirst;
+
+    impl KeyOrder<u64> for EvensFirst {
+        fn compare(a: &u64, b: &u64) -> Ordering {
+            (a % 2, a).cmp(&(b % 2, b))
+        }
+    }
+
+    #[test]
+    pub fn custom_order_places_keys() {
+        let keys: Vec<OrderedKey<u64, EvensFirst>> = (0..6).map(OrderedKey::new).collect();
+        assert!(keys[4] < keys[1]);
+        assert_eq!(Ordering::Greater, keys[3].cmp_key(&keys[2]));
+
+        let manager = Arc::new(Mutex::new(PageManager::in_memory(4)));
+        manager.lock().unwrap().add_empty_pages("index.db", 4);
+
+        let tree = BTree::<OrderedKey<u64, EvensFirst>>::new(manager.clone()).unwrap();
+        for key in keys {
+            tree.insert(KeyEntry::leaf(key, 0, 0)).unwrap();
+        }
+
+        let order: Vec<u64> = Cursor::new(&tree)
+            .unwrap()
+            .map(|entry| entry.unwrap().key().clone().into_inner())
+            .collect();
+        assert_eq!(vec![0, 2, 4, 1, 3, 5], order);
+    }
+}
@@ -1,6 +1,19 @@
-mod disk_btree;
-mod disk_manager;
-mod page;
-mod page_manager;
-mod serialization_helpers;
-mod usage_tracker;
+#[cfg(feature = "async")]
+pub mod async_disk_manager;
+pub mod bloom_filter;
+pub mod btree;
+pub mod catalog;
+pub mod cursor;
+pub mod data_page;
+pub mod disk_btree;
+pub mod disk_manager;
+pub mod error;
+pub mod free_space_map;
+pub mod key_order;
+pub mod overflow;
+pub mod page;
+pub mod page_arena;
+pub mod page_guard;
+pub mod page_manager;
+pub mod serialization_helpers;
+pub mod usage_tracker;
@@ -0,0 +1,156 @@
+use std::mem::size_of;
+
+use crate::{
+    error::YarddError,
+    page::{
+        Page, PageHeader, PageId, PageType, HEADER_SIZE, NULL_PAGE_ID, PAGE_MAGIC_NUMBER,
+        PAGE_SIZE_BYTES,
+    },
+    page_manager::SharedPageManager,
+    serialization_helpers::*,
+};
+
+// Overflow pages hold a chunk of a value too large for the page that refers to it
+pub const OVERFLOW_NEXT_PAGE_ID_START: usize = HEADER_SIZE;
+pub const OVERFLOW_LENGTH_START: usize = OVERFLOW_NEXT_PAGE_ID_START + size_of::<PageId>();
+pub const OVERFLOW_DATA_START: usize = OVERFLOW_LENGTH_START + size_of::<u16>();
+
+/// Stores the bytes in a chain of overflow pages and returns the first page's id.
+pub fn write_overflow_chain(
+    page_manager: &SharedPageManager,
+    bytes: &[u8],
+) -> Result<PageId, YarddError> {
+    let chunk_size = PAGE_SIZE_BYTES as usize - OVERFLOW_DATA_START;
+    let chunks: Vec<&[u8]> = bytes.chunks(chunk_size).collect();
+
+    // Write back to front so each page knows its successor
+    let mut next_page_id = NULL_PAGE_ID;
+    for chunk in chunks.iter().rev() {
+        let page = page_manager.lock().unwrap().next_free_page()?;
+        let mut page = page.write().unwrap();
+
+        write_overflow_page(&mut page, next_page_id, chunk);
+        next_page_id = page.page_id;
+    }
+
+    Ok(next_page_id)
+}
+
+fn write_overflow_page(page: &mut Page, next_page_id: PageId, chunk: &[u8]) {
+    let page_id = page.page_id;
+    page.write_header(PageHeader {
+        magic_number: PAGE_MAGIC_NUMBER,
+        page_type: PageType::Overflow,
+        log_sequence_number: 0,
+        parent_page_id: NULL_PAGE_ID,
+        page_id,
+    });
+
+    write_u64(&mut page.data, OVERFLOW_NEXT_PAGE_ID_START, next_page_id);
+    write_u16(&mut page.data, OVERFLOW_LENGTH_START, chunk.len() as u16);
+    write_bytes(&mut page.data, OVERFLOW_DATA_START, chunk);
+}
+
+/// Overwrites the first bytes stored in the chain, which must fit in its first page.
+pub fn overwrite_overflow_chain_start(
+    page_manager: &SharedPageManager,
+    first_page_id: PageId,
+    bytes: &[u8],
+) -> Result<(), YarddError> {
+    let page = page_manager.lock().unwrap().find_page(first_page_id)?;
+    let mut page = page.write().unwrap();
+
+    let length = read_u16(&page.data, OVERFLOW_LENGTH_START) as usize;
+    assert!(
+        bytes.len() <= length,
+        "Overwrite runs past the first overflow page"
+    );
+
+    write_bytes(&mut page.data, OVERFLOW_DATA_START, bytes);
+    page.is_dirty = true;
+
+    Ok(())
+}
+
+/// Hands every page of the chain starting at `first_page_id` back to the free list.
+pub fn free_overflow_chain(
+    page_manager: &SharedPageManager,
+    first_page_id: PageId,
+) -> Result<(), YarddError> {
+    let mut page_id = first_page_id;
+
+    while page_id != NULL_PAGE_ID {
+        let mut page_manager = page_manager.lock().unwrap();
+        let page = page_manager.find_page(page_id)?;
+        let next_page_id = read_u64(&page.read().unwrap().data, OVERFLOW_NEXT_PAGE_ID_START);
+
+        page_manager.release_page(page_id);
+        page_id = next_page_id;
+    }
+
+    Ok(())
+}
+
+/// Reassembles the bytes stored in the chain starting at `first_page_id`.
+pub fn read_overflow_chain(
+    page_manager: &SharedPageManager,
+    first_page_id: PageId,
+) -> Result<Vec<u8>, YarddError> {
+    let mut bytes = vec![];
+    let mut page_id = first_page_id;
+
+    while page_id != NULL_PAGE_ID {
+        let page = page_manager.lock().unwrap().find_page(page_id)?;
+        let page = page.read().unwrap();
+
+        let page_type = page.read_page_type();
+        assert_eq!(
+            PageType::Overflow,
+            page_type,
+            "Page {} in an overflow chain has type {:?}",
+            page_id,
+            page_type
+        );
+
+        let length = read_u16(&page.data, OVERFLOW_LENGTH_START) as usize;
+        bytes.extend_from_slice(&page.data[OVERFLOW_DATA_START..OVERFLOW_DATA_START + length]);
+
+        page_id = read_u64(&page.data, OVERFLOW_NEXT_PAGE_ID_START);
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod overflow_tests {
+    use std::{
+        fs::{create_dir_all, remove_dir_all},
+        path::Path,
+        sync::{Arc, Mutex},
+    };
+
+    use crate::page_manager::PageManager;
+
+    use super::{free_overflow_chain, read_overflow_chain, write_overflow_chain};
+
+    #[test]
+    pub fn overflow_chain_round_trip() {
+        let base_dir = "./test25";
+        create_dir_all(Path::new(base_dir)).expect("Failed to create test directory.");
+
+        let manager = Arc::new(Mutex::new(PageManager::new(4, base_dir)));
+        manager.lock().unwrap().add_empty_pages("overflow.db", 8);
+
+        let bytes: Vec<u8> = (0..3000).map(|i| (i % 251) as u8).collect();
+        let first_page_id = write_overflow_chain(&manager, &bytes).unwrap();
+
+        // The pool is smaller than the chain, so some pages come back from disk
+        assert_eq!(bytes, read_overflow_chain(&manager, first_page_id).unwrap());
+        assert_eq!(4, manager.lock().unwrap().free_page_count());
+
+        free_overflow_chain(&manager, first_page_id).unwrap();
+        assert_eq!(8, manager.lock().unwrap().free_page_count());
+
+        let _ = remove_dir_all(base_dir);
+    }
+}
@@ -1,32 +1,68 @@
-use crate::{disk_btree::IndexPage, serialization_helpers::*};
-use std::mem::size_of;
+use crate::{
+    catalog,
+    data_page::DataPage,
+    disk_btree::{IndexPage, IndexPageMut, IndexPageRead, IndexPageReadSized},
+    error::{UnknownPageType, YarddError},
+    overflow::{OVERFLOW_LENGTH_START, OVERFLOW_NEXT_PAGE_ID_START},
+    page_arena::PageBuffer,
+    page_manager::WriteThrough,
+    serialization_helpers::*,
+};
+use std::{
+    cmp::Ordering,
+    fmt::{self, Write},
+    mem::size_of,
+};
 
 pub const PAGE_SIZE_BYTES: u16 = 1024;
-pub const PAGE_MAGIC_NUMBER: u32 = 0xFBEA82B9;
+pub const PAGE_MAGIC_NUMBER: u32 = 0xFBEA82BA;
+// Pages written before the LSN was widened to 64 bits. Their header fields are
+// at different offsets, so they're reported rather than misread.
+pub const PAGE_MAGIC_NUMBER_V1: u32 = 0xFBEA82B9;
+// Freshly allocated pages are all zeros, so their magic number reads as this
+pub const PAGE_UNINITIALIZED_MAGIC: u32 = 0;
+// On disk, a page that was allocated but never saved holds only this and its
+// id, so reopening the file still finds it. It's read back as all zeros.
+pub const PAGE_ALLOCATED_MAGIC: u32 = 0xFBEA82A0;
 
 pub type PageId = u64;
 pub type SlotIndex = u16;
 
+// Used wherever a page reference is absent, e.g. the parent of a root page
+pub const NULL_PAGE_ID: PageId = PageId::MAX;
+
+// How much of the raw page data Page's Debug output dumps
+pub const PAGE_DEBUG_DUMP_BYTES: usize = 64;
+
 pub struct Page {
-    pub data: Vec<u8>,
+    pub data: PageBuffer,
     pub is_dirty: bool,
     pub page_id: PageId,
+    // Bumped each time a PageWriteGuard on the page is released
+    pub write_version: u64,
+    pub write_through: Option<WriteThrough>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum PageType {
     IndexNode = 1,
     IndexLeaf = 2,
     DataPage = 3,
+    Overflow = 4,
+    Catalog = 5,
 }
 
-impl From<u8> for PageType {
-    fn from(value: u8) -> Self {
+impl TryFrom<u8> for PageType {
+    type Error = UnknownPageType;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
-            1 => PageType::IndexNode,
-            2 => PageType::IndexLeaf,
-            3 => PageType::DataPage,
-            _ => panic!("Unknown page type"),
+            1 => Ok(PageType::IndexNode),
+            2 => Ok(PageType::IndexLeaf),
+            3 => Ok(PageType::DataPage),
+            4 => Ok(PageType::Overflow),
+            5 => Ok(PageType::Catalog),
+            _ => Err(UnknownPageType(value)),
         }
     }
 }
@@ -34,27 +70,68 @@ impl From<u8> for PageType {
 pub const MAGIC_NUMBER_START: usize = 0;
 pub const PAGE_TYPE_START: usize = MAGIC_NUMBER_START + size_of::<u32>();
 pub const LOG_SEQUENCE_NUMBER_START: usize = PAGE_TYPE_START + size_of::<u8>();
-pub const PARENT_PAGE_ID_START: usize = LOG_SEQUENCE_NUMBER_START + size_of::<u32>();
+pub const PARENT_PAGE_ID_START: usize = LOG_SEQUENCE_NUMBER_START + size_of::<u64>();
 pub const PAGE_ID_START: usize = PARENT_PAGE_ID_START + size_of::<PageId>();
-pub const HEADER_SIZE: usize = PAGE_ID_START + size_of::<PageId>();
+pub const CHECKSUM_START: usize = PAGE_ID_START + size_of::<PageId>();
+pub const HEADER_SIZE: usize = CHECKSUM_START + size_of::<u32>();
+
+/// Boolean page state, one bit each in the flags field of the version 2 slot
+/// header. Only index pages have one. Other pages read as having no flags,
+/// and setting or clearing a flag on them is an error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PageFlags {
+    IsRoot = 1 << 0,
+    HasOverflow = 1 << 1,
+    PrefixCompressed = 1 << 2,
+    BloomFilter = 1 << 3,
+    SharedPageId = 1 << 4,
+}
 
 pub const SLOTS_HEADER_START: usize = HEADER_SIZE;
 pub const SLOTS_OCCUPIED_SLOTS_START: usize = SLOTS_HEADER_START;
 pub const SLOTS_FRAGMENTED_SLOTS_START: usize = SLOTS_OCCUPIED_SLOTS_START + size_of::<u16>();
 pub const SLOTS_NEXT_EMPTY_OFFSET_START: usize = SLOTS_FRAGMENTED_SLOTS_START + size_of::<u16>();
 pub const SLOTS_HEADER_SIZE: usize = size_of::<u16>() * 3;
-pub const SLOTS_START: usize = SLOTS_HEADER_START + SLOTS_HEADER_SIZE;
 
+// Sibling links, only meaningful for index leaves
+pub const NEXT_LEAF_PAGE_ID_START: usize = SLOTS_HEADER_START + SLOTS_HEADER_SIZE;
+pub const PREV_LEAF_PAGE_ID_START: usize = NEXT_LEAF_PAGE_ID_START + size_of::<PageId>();
+
+pub const SLOTS_START: usize = PREV_LEAF_PAGE_ID_START + size_of::<PageId>();
+
+// Index pages written before the header was versioned have the high byte of
+// the next leaf id here, which is 0 or 0xFF (NULL_PAGE_ID). Both read as version 1.
+pub const SLOTS_VERSION_START: usize = SLOTS_NEXT_EMPTY_OFFSET_START + size_of::<u16>();
+pub const SLOT_HEADER_V1: u8 = 1;
+pub const SLOT_HEADER_V2: u8 = 2;
+
+// Version 2 adds flags and reserved fields, so new fields don't move the slots
+pub const SLOTS_FLAGS_START: usize = SLOTS_VERSION_START + size_of::<u8>();
+pub const SLOTS_RESERVED_START: usize = SLOTS_FLAGS_START + size_of::<u16>();
+pub const SLOTS_RESERVED_FIELDS: usize = 2;
+// Taken by the size of a leaf's bloom filter, when it has one
+pub const SLOTS_BLOOM_BYTES_START: usize = SLOTS_RESERVED_START;
+pub const SLOTS_BLOOM_HASHES_START: usize = SLOTS_BLOOM_BYTES_START + size_of::<u16>();
+pub const SLOTS_HEADER_V2_SIZE: usize =
+    SLOTS_HEADER_SIZE + size_of::<u8>() + size_of::<u16>() * (1 + SLOTS_RESERVED_FIELDS);
+
+pub const NEXT_LEAF_PAGE_ID_V2_START: usize = SLOTS_HEADER_START + SLOTS_HEADER_V2_SIZE;
+pub const PREV_LEAF_PAGE_ID_V2_START: usize = NEXT_LEAF_PAGE_ID_V2_START + size_of::<PageId>();
+
+pub const SLOTS_V2_START: usize = PREV_LEAF_PAGE_ID_V2_START + size_of::<PageId>();
+
+#[derive(Debug)]
 pub struct SlotHeader {
     pub occupied_slots: u16,
     pub fragmented_slots: u16,
     pub next_empty_offset: u16,
 }
 
+#[derive(Debug)]
 pub struct PageHeader {
     pub magic_number: u32,
     pub page_type: PageType,
-    pub log_sequence_number: u32,
+    pub log_sequence_number: u64,
     pub parent_page_id: PageId,
     pub page_id: PageId,
 }
@@ -62,9 +139,9 @@ pub struct PageHeader {
 impl Page {
     pub fn read_header(&self) -> PageHeader {
         PageHeader {
-            magic_number: read_u32(&self.data, MAGIC_NUMBER_START),
+            magic_number: self.read_magic_number(),
             page_type: self.read_page_type(),
-            log_sequence_number: read_u32(&self.data, LOG_SEQUENCE_NUMBER_START),
+            log_sequence_number: self.read_lsn(),
             parent_page_id: read_u64(&self.data, PARENT_PAGE_ID_START),
             page_id: self.read_page_id(),
         }
@@ -77,7 +154,7 @@ impl Page {
 
         write_u32(&mut self.data, MAGIC_NUMBER_START, header.magic_number);
         self.data[4] = header.page_type as u8;
-        write_u32(
+        write_u64(
             &mut self.data,
             LOG_SEQUENCE_NUMBER_START,
             header.log_sequence_number,
@@ -86,38 +163,453 @@ impl Page {
         write_u64(&mut self.data, PAGE_ID_START, header.page_id);
     }
 
+    pub fn read_lsn(&self) -> u64 {
+        read_u64(&self.data, LOG_SEQUENCE_NUMBER_START)
+    }
+
+    // The LSN of the last log record describing a change to this page.
+    // Leaves the rest of the header alone.
+    pub fn write_lsn(&mut self, lsn: u64) {
+        self.is_dirty = true;
+        write_u64(&mut self.data, LOG_SEQUENCE_NUMBER_START, lsn);
+    }
+
+    pub fn page_flags(&self) -> u16 {
+        if self.has_flags_field() {
+            read_u16(&self.data, SLOTS_FLAGS_START)
+        } else {
+            0
+        }
+    }
+
+    pub fn has_flag(&self, flag: PageFlags) -> bool {
+        self.page_flags() & flag as u16 != 0
+    }
+
+    pub fn set_flag(&mut self, flag: PageFlags) -> Result<(), YarddError> {
+        self.write_page_flags(self.page_flags() | flag as u16)
+    }
+
+    pub fn clear_flag(&mut self, flag: PageFlags) -> Result<(), YarddError> {
+        self.write_page_flags(self.page_flags() & !(flag as u16))
+    }
+
+    fn write_page_flags(&mut self, flags: u16) -> Result<(), YarddError> {
+        self.check_flags_field()?;
+
+        self.is_dirty = true;
+        write_u16(&mut self.data, SLOTS_FLAGS_START, flags);
+
+        Ok(())
+    }
+
+    fn has_flags_field(&self) -> bool {
+        self.check_flags_field().is_ok()
+    }
+
+    // Version 1 index pages have no room for flags, other page types never do
+    fn check_flags_field(&self) -> Result<(), YarddError> {
+        match self.checked_page_type()? {
+            PageType::IndexLeaf | PageType::IndexNode
+                if self.read_slot_header_version() >= SLOT_HEADER_V2 =>
+            {
+                Ok(())
+            }
+            PageType::IndexLeaf | PageType::IndexNode => {
+                Err(YarddError::UnsupportedLayout(self.page_id))
+            }
+            actual => Err(YarddError::WrongPageType {
+                page_id: self.page_id,
+                actual,
+            }),
+        }
+    }
+
     pub fn read_page_id(&self) -> PageId {
         read_u64(&self.data, PAGE_ID_START)
     }
 
+    pub fn read_magic_number(&self) -> u32 {
+        read_u32(&self.data, MAGIC_NUMBER_START)
+    }
+
+    /// A zeroed buffer that isn't backed by the database. It has no magic
+    /// number, so it's never mistaken for a real page.
+    pub fn scratch(size: usize) -> Page {
+        Page {
+            page_id: NULL_PAGE_ID,
+            data: vec![0; size].into(),
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        }
+    }
+
+    // Whether the page has been given a header, as opposed to being blank or scratch space
+    pub fn is_initialized(&self) -> bool {
+        self.read_magic_number() == PAGE_MAGIC_NUMBER
+    }
+
+    /// Checks that the page is either uninitialized (all zeros) or carries
+    /// a valid magic number and page type.
+    pub fn validate_header(&self) -> Result<(), YarddError> {
+        let magic_number = self.read_magic_number();
+
+        if magic_number == PAGE_UNINITIALIZED_MAGIC && self.data.iter().all(|b| *b == 0) {
+            return Ok(());
+        }
+
+        if magic_number == PAGE_MAGIC_NUMBER_V1 {
+            return Err(YarddError::UnsupportedLayout(self.page_id));
+        }
+
+        if magic_number != PAGE_MAGIC_NUMBER {
+            return Err(YarddError::Corruption(self.page_id));
+        }
+
+        let Ok(page_type) = self.try_read_page_type() else {
+            return Err(YarddError::Corruption(self.page_id));
+        };
+
+        let is_index = matches!(page_type, PageType::IndexLeaf | PageType::IndexNode);
+        if is_index && self.read_slot_header_version() > SLOT_HEADER_V2 {
+            return Err(YarddError::Corruption(self.page_id));
+        }
+
+        Ok(())
+    }
+
+    pub fn read_checksum(&self) -> u32 {
+        read_u32(&self.data, CHECKSUM_START)
+    }
+
+    // CRC of everything but the checksum field itself
+    pub fn compute_checksum(&self) -> u32 {
+        let checksum_end = CHECKSUM_START + size_of::<u32>();
+        crc32(&[&self.data[..CHECKSUM_START], &self.data[checksum_end..]])
+    }
+
+    // Stamps the page with the checksum of its current contents
+    pub fn update_checksum(&mut self) {
+        let checksum = self.compute_checksum();
+        write_u32(&mut self.data, CHECKSUM_START, checksum);
+    }
+
+    /// Checks the stored checksum against the page contents. Uninitialized
+    /// (all zero) pages have never been stamped and always pass.
+    pub fn verify_checksum(&self) -> Result<(), YarddError> {
+        if self.data.iter().all(|b| *b == 0) {
+            return Ok(());
+        }
+
+        if self.read_checksum() != self.compute_checksum() {
+            return Err(YarddError::Corruption(self.page_id));
+        }
+
+        Ok(())
+    }
+
     pub fn read_page_type(&self) -> PageType {
-        self.data[PAGE_TYPE_START].into()
+        self.try_read_page_type()
+            .unwrap_or_else(|error| panic!("Unknown page type {}", error.0))
+    }
+
+    pub fn try_read_page_type(&self) -> Result<PageType, UnknownPageType> {
+        let byte = self.data[PAGE_TYPE_START];
+        PageType::try_from(byte).map_err(|_| UnknownPageType(byte))
+    }
+
+    // Only index pages have a versioned slot header
+    pub fn read_slot_header_version(&self) -> u8 {
+        match self.data[SLOTS_VERSION_START] {
+            0 | 0xFF => SLOT_HEADER_V1,
+            version => version,
+        }
+    }
+
+    pub fn write_page_type(&mut self, page_type: PageType) {
+        self.is_dirty = true;
+        self.data[PAGE_TYPE_START] = page_type as u8;
+    }
+
+    pub fn write_slot_header_version(&mut self, version: u8) {
+        assert!(
+            version > SLOT_HEADER_V1,
+            "Version 1 slot headers have no version byte"
+        );
+
+        self.is_dirty = true;
+        self.data[SLOTS_VERSION_START] = version;
     }
 
     pub fn as_index_node<'a, KeyType>(&'a self) -> IndexPage<'a, KeyType>
+    where
+        KeyType: DbColumn,
+    {
+        self.try_as_index_node()
+            .unwrap_or_else(|error| panic!("Can't read page as index page: {}", error))
+    }
+
+    // Fails with Corruption if the page type byte isn't a type at all
+    pub fn try_as_index_node<'a, KeyType>(&'a self) -> Result<IndexPage<'a, KeyType>, YarddError>
+    where
+        KeyType: DbColumn,
+    {
+        match self.checked_page_type()? {
+            PageType::IndexNode | PageType::IndexLeaf => Ok(IndexPage::read_existing_page(self)),
+            actual => Err(YarddError::WrongPageType {
+                page_id: self.page_id,
+                actual,
+            }),
+        }
+    }
+
+    pub fn as_data_page(&self) -> DataPage<'_> {
+        self.try_as_data_page()
+            .unwrap_or_else(|error| panic!("Can't read page as data page: {}", error))
+    }
+
+    pub fn try_as_data_page(&self) -> Result<DataPage<'_>, YarddError> {
+        match self.checked_page_type()? {
+            PageType::DataPage => Ok(DataPage::read_existing_page(self)),
+            actual => Err(YarddError::WrongPageType {
+                page_id: self.page_id,
+                actual,
+            }),
+        }
+    }
+
+    fn checked_page_type(&self) -> Result<PageType, YarddError> {
+        self.try_read_page_type()
+            .map_err(|_| YarddError::Corruption(self.page_id))
+    }
+
+    pub fn as_index_node_mut<'a, KeyType>(&'a mut self) -> IndexPageMut<'a, KeyType>
     where
         KeyType: DbColumn,
     {
         let page_type = self.read_page_type();
         if page_type != PageType::IndexNode && page_type != PageType::IndexLeaf {
-            panic!("Can't read page as index page. Type = {:?}", page_type)
+            panic!("Can't write page as index page. Type = {:?}", page_type)
         }
 
-        IndexPage::read_existing_page(self)
+        IndexPageMut::write_existing_page(self)
+    }
+
+    // Wipes the page so a recycled page can't be mistaken for its previous contents
+    pub fn reset(&mut self) {
+        self.data.fill(0);
+        self.is_dirty = false;
     }
 
     pub fn page_size(&self) -> usize {
         self.data.len()
     }
+
+    // Captures the page image, e.g. as a before-image for undo
+    pub fn snapshot(&self) -> Vec<u8> {
+        self.data.to_vec()
+    }
+
+    pub fn restore(&mut self, bytes: &[u8]) {
+        assert_eq!(
+            self.data.len(),
+            bytes.len(),
+            "Snapshot size doesn't match the page size"
+        );
+
+        self.data.copy_from_slice(bytes);
+        self.is_dirty = true;
+    }
+}
+
+/// A clone is an independent copy of the page image. It keeps `is_dirty`,
+/// since the copy differs from what's on disk exactly when the original does.
+impl Clone for Page {
+    fn clone(&self) -> Self {
+        Page {
+            data: self.data.clone(),
+            is_dirty: self.is_dirty,
+            page_id: self.page_id,
+            write_version: self.write_version,
+            // A copy isn't the page in the pool, so it mustn't write over it
+            write_through: None,
+        }
+    }
+}
+
+impl fmt::Debug for Page {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("Page");
+        debug
+            .field("page_id", &self.page_id)
+            .field("is_dirty", &self.is_dirty);
+
+        // Decoding the page type of an uninitialized or corrupt page would panic
+        if self.validate_header().is_ok() && self.is_initialized() {
+            let header = self.read_header();
+            let is_index_page =
+                matches!(header.page_type, PageType::IndexNode | PageType::IndexLeaf);
+            debug.field("header", &header);
+
+            if is_index_page {
+                let slots_header = SlotHeader {
+                    occupied_slots: read_u16(&self.data, SLOTS_OCCUPIED_SLOTS_START),
+                    fragmented_slots: read_u16(&self.data, SLOTS_FRAGMENTED_SLOTS_START),
+                    next_empty_offset: read_u16(&self.data, SLOTS_NEXT_EMPTY_OFFSET_START),
+                };
+                debug.field("slots_header", &slots_header);
+            }
+        } else {
+            debug.field("header", &format_args!("<invalid>"));
+        }
+
+        let dump_len = self.data.len().min(PAGE_DEBUG_DUMP_BYTES);
+        debug.field("data", &HexDump(&self.data[..dump_len]));
+
+        if dump_len < self.data.len() {
+            debug.field("data_omitted_bytes", &(self.data.len() - dump_len));
+        }
+
+        debug.finish()
+    }
+}
+
+impl Page {
+    /// A multi-line description of the page for inspection tools: the header,
+    /// then whatever the page type holds. Index entries are decoded as `KeyType`.
+    pub fn describe<KeyType>(&self) -> String
+    where
+        KeyType: DbColumn + fmt::Debug,
+    {
+        let mut out = String::new();
+        self.describe_into::<KeyType>(&mut out)
+            .expect("Writing to a string can't fail");
+        out
+    }
+
+    fn describe_into<KeyType>(&self, out: &mut String) -> fmt::Result
+    where
+        KeyType: DbColumn + fmt::Debug,
+    {
+        if self.validate_header().is_err() || !self.is_initialized() {
+            return writeln!(out, "page {}: no valid header", self.page_id);
+        }
+
+        let header = self.read_header();
+        writeln!(out, "page {} ({:?})", header.page_id, header.page_type)?;
+        writeln!(
+            out,
+            "  lsn {}, parent {}, checksum {:#010x}",
+            header.log_sequence_number,
+            header.parent_page_id,
+            self.read_checksum()
+        )?;
+
+        match header.page_type {
+            PageType::IndexNode | PageType::IndexLeaf => {
+                let node = self.as_index_node::<KeyType>();
+                let slots_header = node.read_slots_header();
+                writeln!(
+                    out,
+                    "  slots: {} occupied, {} fragmented, next empty offset {}",
+                    slots_header.occupied_slots,
+                    slots_header.fragmented_slots,
+                    slots_header.next_empty_offset
+                )?;
+
+                if node.is_leaf() {
+                    writeln!(
+                        out,
+                        "  prev leaf {}, next leaf {}",
+                        node.read_prev_leaf_page_id(),
+                        node.read_next_leaf_page_id()
+                    )?;
+                }
+
+                for (slot_index, entry) in node.iter_indexed() {
+                    writeln!(
+                        out,
+                        "  [{}] key {:?}, page_id {}, slot_index {:?}",
+                        slot_index,
+                        entry.key(),
+                        entry.page_id(),
+                        entry.slot_index()
+                    )?;
+                }
+            }
+            PageType::Overflow => writeln!(
+                out,
+                "  next page {}, {} bytes",
+                read_u64(&self.data, OVERFLOW_NEXT_PAGE_ID_START),
+                read_u16(&self.data, OVERFLOW_LENGTH_START)
+            )?,
+            PageType::Catalog => {
+                for (name, root) in catalog::read_roots(self) {
+                    writeln!(out, "  index {:?}, root {}", name, root)?;
+                }
+            }
+            PageType::DataPage => {}
+        }
+
+        Ok(())
+    }
+}
+
+struct HexDump<'a>(&'a [u8]);
+
+impl fmt::Debug for HexDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+
+        Ok(())
+    }
 }
 
+#[allow(clippy::len_without_is_empty)]
 pub trait DbColumn
 where
     Self: PartialEq + PartialOrd + Clone + Sized,
 {
     fn from_bytes(bytes: &[u8], start: usize) -> Self;
-    fn to_bytes(&self) -> Vec<u8>;
+
+    // Serializes into the start of `buf`, which must hold at least `len` bytes.
+    // Returns the number of bytes written.
+    fn write_to(&self, buf: &mut [u8]) -> usize;
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0; self.len()];
+        self.write_to(&mut bytes);
+        bytes
+    }
+
     fn len(&self) -> usize;
+
+    // A total order for placing keys, even for types whose PartialOrd isn't (e.g. f64 NaN)
+    fn cmp_key(&self, other: &Self) -> Ordering;
+
+    // Compares `search_key` with the value serialized at `start`, like
+    // `search_key.cmp_key(&stored)`. Variable length types override this to
+    // compare without decoding the stored value.
+    fn compare_bytes(search_key: &Self, page_bytes: &[u8], start: usize) -> Ordering {
+        search_key.cmp_key(&Self::from_bytes(page_bytes, start))
+    }
+
+    // The serialized length of the value at `start`, without keeping the value
+    fn encoded_len(bytes: &[u8], start: usize) -> usize {
+        Self::from_bytes(bytes, start).len()
+    }
+
+    // A prefix of the value that serializes to at most `max_len` bytes, for
+    // values too large to store inline. Fixed size columns never need one.
+    fn truncated(&self, _max_len: usize) -> Option<Self> {
+        None
+    }
 }
 
 impl DbColumn for u64 {
@@ -125,27 +617,118 @@ impl DbColumn for u64 {
         read_u64(bytes, start)
     }
 
-    fn to_bytes(&self) -> Vec<u8> {
-        u64::to_be_bytes(*self).into_iter().collect()
+    fn write_to(&self, buf: &mut [u8]) -> usize {
+        write_u64(buf, 0, *self)
     }
 
     fn len(&self) -> usize {
         size_of::<u64>()
     }
+
+    fn encoded_len(_bytes: &[u8], _start: usize) -> usize {
+        size_of::<u64>()
+    }
+
+    fn cmp_key(&self, other: &Self) -> Ordering {
+        self.cmp(other)
+    }
+}
+
+impl DbColumn for f64 {
+    fn from_bytes(bytes: &[u8], start: usize) -> Self {
+        f64::from_bits(read_u64(bytes, start))
+    }
+
+    fn write_to(&self, buf: &mut [u8]) -> usize {
+        write_u64(buf, 0, self.to_bits())
+    }
+
+    fn len(&self) -> usize {
+        size_of::<f64>()
+    }
+
+    fn encoded_len(_bytes: &[u8], _start: usize) -> usize {
+        size_of::<f64>()
+    }
+
+    // Negative NaNs sort first and positive NaNs last
+    fn cmp_key(&self, other: &Self) -> Ordering {
+        self.total_cmp(other)
+    }
+}
+
+// Serialized as a u16 byte length followed by the UTF-8 bytes
+impl DbColumn for String {
+    fn from_bytes(bytes: &[u8], start: usize) -> Self {
+        let length = read_u16(bytes, start) as usize;
+        let start = start + size_of::<u16>();
+
+        String::from_utf8(bytes[start..start + length].to_vec()).expect("Invalid UTF-8 in key")
+    }
+
+    fn write_to(&self, buf: &mut [u8]) -> usize {
+        let cursor = write_u16(buf, 0, String::len(self) as u16);
+        write_bytes(buf, cursor, self.as_bytes())
+    }
+
+    fn len(&self) -> usize {
+        size_of::<u16>() + String::len(self)
+    }
+
+    fn encoded_len(bytes: &[u8], start: usize) -> usize {
+        size_of::<u16>() + read_u16(bytes, start) as usize
+    }
+
+    fn cmp_key(&self, other: &Self) -> Ordering {
+        self.cmp(other)
+    }
+
+    // Strings order by their UTF-8 bytes, so the stored bytes compare as they are
+    fn compare_bytes(search_key: &Self, page_bytes: &[u8], start: usize) -> Ordering {
+        let length = read_u16(page_bytes, start) as usize;
+        let start = start + size_of::<u16>();
+
+        search_key
+            .as_bytes()
+            .cmp(&page_bytes[start..start + length])
+    }
+
+    fn truncated(&self, max_len: usize) -> Option<Self> {
+        let mut end = max_len
+            .saturating_sub(size_of::<u16>())
+            .min(String::len(self));
+        while !self.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        Some(self[..end].to_string())
+    }
 }
 
 #[cfg(test)]
-mod PageTest {
-    use crate::page::{PageType, HEADER_SIZE};
+mod page_test {
+    use std::collections::HashSet;
+
+    use crate::{
+        disk_btree::{IndexPageMut, KeyEntry},
+        error::{UnknownPageType, YarddError},
+        page::{PageType, HEADER_SIZE, MAGIC_NUMBER_START, PAGE_TYPE_START},
+        serialization_helpers::write_u32,
+    };
 
-    use super::{Page, PageHeader, PAGE_MAGIC_NUMBER};
+    use super::{
+        crc32, DbColumn, Page, PageFlags, PageHeader, PAGE_MAGIC_NUMBER, PAGE_MAGIC_NUMBER_V1,
+        SLOT_HEADER_V1, SLOT_HEADER_V2,
+    };
 
     #[test]
     pub fn test_read_write_header() {
         let mut page = Page {
             page_id: 0xABCDEF,
-            data: vec![0; 1024],
+            data: vec![0; 1024].into(),
             is_dirty: false,
+            write_version: 0,
+            write_through: None,
         };
 
         let header = PageHeader {
@@ -167,4 +750,516 @@ mod PageTest {
         assert_eq!(0xABCDEF, header.page_id);
         assert_eq!(0xFEDCBA, header.parent_page_id);
     }
+
+    #[test]
+    pub fn lsn_round_trip() {
+        let mut page = Page {
+            page_id: 0xABCDEF,
+            data: vec![0; 1024].into(),
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        page.write_header(PageHeader {
+            log_sequence_number: 1,
+            magic_number: PAGE_MAGIC_NUMBER,
+            page_type: PageType::DataPage,
+            page_id: 0xABCDEF,
+            parent_page_id: 0xFEDCBA,
+        });
+        page.is_dirty = false;
+
+        page.write_lsn(0xDEADBEEF);
+
+        assert!(page.is_dirty);
+        assert_eq!(0xDEADBEEF, page.read_lsn());
+
+        // Past what 32 bits can hold, so wraparound would show
+        let large_lsn = u32::MAX as u64 * 3 + 7;
+        page.write_lsn(large_lsn);
+        assert_eq!(large_lsn, page.read_lsn());
+        assert_eq!(large_lsn, page.read_header().log_sequence_number);
+        page.write_lsn(0xDEADBEEF);
+
+        let header = page.read_header();
+        assert_eq!(0xDEADBEEF, header.log_sequence_number);
+        assert_eq!(PAGE_MAGIC_NUMBER, header.magic_number);
+        assert_eq!(PageType::DataPage, header.page_type);
+        assert_eq!(0xABCDEF, header.page_id);
+        assert_eq!(0xFEDCBA, header.parent_page_id);
+    }
+
+    #[test]
+    pub fn validate_detects_old_header_layout() {
+        let mut page = Page {
+            page_id: 7,
+            data: vec![0; 1024].into(),
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        write_u32(&mut page.data, MAGIC_NUMBER_START, PAGE_MAGIC_NUMBER_V1);
+        page.data[PAGE_TYPE_START] = PageType::DataPage as u8;
+
+        assert_eq!(
+            Err(YarddError::UnsupportedLayout(7)),
+            page.validate_header()
+        );
+    }
+
+    #[test]
+    pub fn validate_uninitialized_page() {
+        let page = Page {
+            page_id: 7,
+            data: vec![0; 1024].into(),
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        assert_eq!(Ok(()), page.validate_header());
+    }
+
+    #[test]
+    pub fn validate_bad_magic_number() {
+        let mut page = Page {
+            page_id: 7,
+            data: vec![0; 1024].into(),
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        page.write_header(PageHeader {
+            log_sequence_number: 0,
+            magic_number: PAGE_MAGIC_NUMBER,
+            page_type: PageType::IndexLeaf,
+            page_id: 7,
+            parent_page_id: 0,
+        });
+        assert_eq!(Ok(()), page.validate_header());
+
+        page.data[MAGIC_NUMBER_START] ^= 0xFF;
+        assert_eq!(Err(YarddError::Corruption(7)), page.validate_header());
+    }
+
+    #[test]
+    pub fn validate_bad_page_type() {
+        let mut page = Page {
+            page_id: 7,
+            data: vec![0; 1024].into(),
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        page.write_header(PageHeader {
+            log_sequence_number: 0,
+            magic_number: PAGE_MAGIC_NUMBER,
+            page_type: PageType::IndexLeaf,
+            page_id: 7,
+            parent_page_id: 0,
+        });
+        page.data[PAGE_TYPE_START] = 0xEE;
+
+        assert_eq!(Err(YarddError::Corruption(7)), page.validate_header());
+    }
+
+    #[test]
+    pub fn snapshot_and_restore() {
+        let mut page = Page {
+            page_id: 7,
+            data: vec![0; 1024].into(),
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        page.write_header(PageHeader {
+            log_sequence_number: 1,
+            magic_number: PAGE_MAGIC_NUMBER,
+            page_type: PageType::IndexLeaf,
+            page_id: 7,
+            parent_page_id: 2,
+        });
+
+        let snapshot = page.snapshot();
+
+        page.write_header(PageHeader {
+            log_sequence_number: 5,
+            magic_number: PAGE_MAGIC_NUMBER,
+            page_type: PageType::DataPage,
+            page_id: 7,
+            parent_page_id: 3,
+        });
+
+        page.is_dirty = false;
+        page.restore(&snapshot);
+
+        let header = page.read_header();
+        assert!(page.is_dirty);
+        assert_eq!(1, header.log_sequence_number);
+        assert_eq!(PageType::IndexLeaf, header.page_type);
+        assert_eq!(2, header.parent_page_id);
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn restore_wrong_size() {
+        let mut page = Page {
+            page_id: 7,
+            data: vec![0; 1024].into(),
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        page.restore(&[0; 512]);
+    }
+
+    #[test]
+    pub fn debug_decodes_header_and_truncates_data() {
+        let mut page = Page {
+            page_id: 7,
+            data: vec![0; 1024].into(),
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        page.write_header(PageHeader {
+            log_sequence_number: 0,
+            magic_number: PAGE_MAGIC_NUMBER,
+            page_type: PageType::IndexLeaf,
+            page_id: 7,
+            parent_page_id: 3,
+        });
+
+        let output = format!("{:?}", page);
+        assert!(output.contains("IndexLeaf"));
+        assert!(output.contains("parent_page_id: 3"));
+        assert!(output.contains("slots_header"));
+        assert!(output.contains("data: fb ea 82 ba 02"));
+        assert!(output.contains("data_omitted_bytes: 960"));
+
+        page.data[MAGIC_NUMBER_START] = 0xFF;
+        let output = format!("{:?}", page);
+        assert!(output.contains("header: <invalid>"));
+    }
+
+    fn page_of_type(page_type: PageType) -> Page {
+        let mut page = Page {
+            page_id: 7,
+            data: vec![0; 1024].into(),
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        page.write_header(PageHeader {
+            log_sequence_number: 0,
+            magic_number: PAGE_MAGIC_NUMBER,
+            page_type,
+            page_id: 7,
+            parent_page_id: 0,
+        });
+
+        page
+    }
+
+    #[test]
+    pub fn try_as_index_node() {
+        let page = page_of_type(PageType::IndexLeaf);
+        assert!(page.try_as_index_node::<u64>().is_ok());
+
+        let page = page_of_type(PageType::DataPage);
+        let error = page
+            .try_as_index_node::<u64>()
+            .err()
+            .expect("Expected error");
+        assert_eq!(
+            YarddError::WrongPageType {
+                page_id: 7,
+                actual: PageType::DataPage
+            },
+            error
+        );
+    }
+
+    #[test]
+    pub fn try_as_data_page() {
+        let page = page_of_type(PageType::DataPage);
+        let data_page = page.try_as_data_page().expect("Expected data page");
+        assert_eq!(7, data_page.page_id());
+
+        let page = page_of_type(PageType::IndexNode);
+        let error = page.try_as_data_page().err().expect("Expected error");
+        assert_eq!(
+            YarddError::WrongPageType {
+                page_id: 7,
+                actual: PageType::IndexNode
+            },
+            error
+        );
+    }
+
+    #[test]
+    pub fn unknown_page_type_is_corruption() {
+        let mut page = page_of_type(PageType::DataPage);
+        page.data[PAGE_TYPE_START] = 0xFF;
+
+        assert_eq!(
+            YarddError::Corruption(7),
+            page.try_as_index_node::<u64>().err().unwrap()
+        );
+        assert_eq!(
+            YarddError::Corruption(7),
+            page.try_as_data_page().err().unwrap()
+        );
+    }
+
+    #[test]
+    pub fn reset_zeroes_page() {
+        let mut page = page_of_type(PageType::IndexLeaf);
+        assert!(page.is_dirty);
+
+        page.reset();
+
+        assert!(!page.is_dirty);
+        assert_eq!(1024, page.page_size());
+        assert!(page.data.iter().all(|b| *b == 0));
+        assert_eq!(Ok(()), page.validate_header());
+    }
+
+    #[test]
+    pub fn checksum_detects_changes() {
+        assert_eq!(0xCBF43926, crc32(&[b"1234", b"56789"]));
+
+        let mut page = page_of_type(PageType::DataPage);
+        assert!(page.verify_checksum().is_err());
+
+        page.update_checksum();
+        assert_eq!(Ok(()), page.verify_checksum());
+
+        page.data[1000] = 1;
+        assert_eq!(Err(YarddError::Corruption(7)), page.verify_checksum());
+
+        page.update_checksum();
+        assert_eq!(Ok(()), page.verify_checksum());
+
+        page.reset();
+        assert_eq!(Ok(()), page.verify_checksum());
+    }
+
+    #[test]
+    pub fn clone_is_deep_copy() {
+        let mut page = page_of_type(PageType::DataPage);
+        page.is_dirty = false;
+
+        let clone = page.clone();
+        assert_eq!(page.page_id, clone.page_id);
+        assert_eq!(page.data, clone.data);
+        assert!(!clone.is_dirty);
+
+        page.data[HEADER_SIZE] = 0xFF;
+        page.is_dirty = true;
+
+        assert_eq!(0, clone.data[HEADER_SIZE]);
+        assert!(!clone.is_dirty);
+        assert!(page.clone().is_dirty);
+    }
+
+    #[test]
+    pub fn page_type_from_byte() {
+        assert_eq!(Ok(PageType::Overflow), PageType::try_from(4));
+        assert_eq!(Err(UnknownPageType(0)), PageType::try_from(0));
+        assert_eq!(Err(UnknownPageType(42)), PageType::try_from(42));
+
+        let mut page = page_of_type(PageType::DataPage);
+        page.data[PAGE_TYPE_START] = 42;
+        assert_eq!(Err(UnknownPageType(42)), page.try_read_page_type());
+        assert_eq!(Err(YarddError::Corruption(7)), page.validate_header());
+
+        let mut types = vec![PageType::Catalog, PageType::IndexNode, PageType::DataPage];
+        types.sort();
+        assert_eq!(
+            vec![PageType::IndexNode, PageType::DataPage, PageType::Catalog],
+            types
+        );
+
+        let set: HashSet<PageType> = types.iter().copied().collect();
+        assert!(set.contains(&PageType::DataPage));
+        assert!(!set.contains(&PageType::Overflow));
+    }
+
+    #[test]
+    pub fn describe_index_page_lists_entries() {
+        let mut page = Page {
+            page_id: 3,
+            data: vec![0; 1024].into(),
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+
+        let mut node = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 9, &mut page);
+        for key in [20u64, 5, 12] {
+            node.append_key(KeyEntry::leaf(key, key * 10, 1)).unwrap();
+        }
+
+        let description = page.describe::<u64>();
+        assert!(description.starts_with("page 3 (IndexLeaf)"));
+        assert!(description.contains("parent 9"));
+        assert!(description.contains("3 occupied"));
+
+        let positions: Vec<usize> = [
+            "key 5, page_id 50",
+            "key 12, page_id 120",
+            "key 20, page_id 200",
+        ]
+        .iter()
+        .map(|entry| description.find(entry).expect("Entry missing"))
+        .collect();
+        assert!(positions.is_sorted());
+
+        let blank = Page {
+            page_id: 4,
+            data: vec![0; 1024].into(),
+            is_dirty: false,
+            write_version: 0,
+            write_through: None,
+        };
+        assert_eq!("page 4: no valid header\n", blank.describe::<u64>());
+    }
+
+    #[test]
+    pub fn scratch_page_is_not_initialized() {
+        let scratch = Page::scratch(256);
+        assert_eq!(256, scratch.data.len());
+        assert!(!scratch.is_initialized());
+        assert!(!scratch.is_dirty);
+        assert_eq!(Ok(()), scratch.validate_header());
+
+        assert!(page_of_type(PageType::DataPage).is_initialized());
+    }
+
+    fn assert_write_to_matches<KeyType: DbColumn + std::fmt::Debug>(key: KeyType) {
+        let mut buf = vec![0xAA; key.len() + 8];
+        let written = key.write_to(&mut buf[3..]);
+
+        assert_eq!(key.len(), written);
+        assert_eq!(key.to_bytes(), buf[3..3 + written]);
+        assert_eq!(key.len(), KeyType::encoded_len(&buf, 3));
+        assert!(KeyType::from_bytes(&buf, 3).cmp_key(&key).is_eq());
+
+        // Bytes past the key are untouched
+        assert!(buf[3 + written..].iter().all(|b| *b == 0xAA));
+    }
+
+    #[test]
+    pub fn write_to_agrees_with_to_bytes() {
+        assert_write_to_matches(0u64);
+        assert_write_to_matches(0x0102030405060708u64);
+        assert_write_to_matches(-1.5f64);
+        assert_write_to_matches(f64::NAN);
+        assert_write_to_matches(String::new());
+        assert_write_to_matches("héllo".to_string());
+    }
+
+    fn assert_compare_bytes_matches<KeyType: DbColumn>(keys: &[KeyType]) {
+        for stored in keys {
+            let mut buf = vec![0; stored.len() + 5];
+            stored.write_to(&mut buf[5..]);
+
+            for search_key in keys {
+                assert_eq!(
+                    search_key.cmp_key(&KeyType::from_bytes(&buf, 5)),
+                    KeyType::compare_bytes(search_key, &buf, 5)
+                );
+            }
+        }
+    }
+
+    #[test]
+    pub fn compare_bytes_agrees_with_cmp_key() {
+        assert_compare_bytes_matches(&[0u64, 1, 255, 256, u64::MAX]);
+        assert_compare_bytes_matches(&[-1.5f64, 0.0, -0.0, 2.5, f64::NAN, -f64::NAN]);
+        assert_compare_bytes_matches(
+            &[
+                "",
+                "a",
+                "ab",
+                "abc",
+                "b",
+                "B",
+                "héllo",
+                "hello",
+                "\u{10FFFF}",
+            ]
+            .map(String::from),
+        );
+    }
+
+    #[test]
+    pub fn flags_set_and_clear_independently() {
+        let mut page = page_of_type(PageType::IndexLeaf);
+        page.write_slot_header_version(SLOT_HEADER_V2);
+        page.is_dirty = false;
+        let bytes = page.data.clone();
+
+        page.set_flag(PageFlags::IsRoot).unwrap();
+        page.set_flag(PageFlags::PrefixCompressed).unwrap();
+        assert!(page.is_dirty);
+        assert!(page.has_flag(PageFlags::IsRoot));
+        assert!(!page.has_flag(PageFlags::HasOverflow));
+        assert!(page.has_flag(PageFlags::PrefixCompressed));
+
+        page.clear_flag(PageFlags::IsRoot).unwrap();
+        page.clear_flag(PageFlags::HasOverflow).unwrap();
+        assert!(!page.has_flag(PageFlags::IsRoot));
+        assert!(!page.has_flag(PageFlags::HasOverflow));
+        assert!(page.has_flag(PageFlags::PrefixCompressed));
+        assert_eq!(PageFlags::PrefixCompressed as u16, page.page_flags());
+
+        // Nothing but the flags changed
+        page.clear_flag(PageFlags::PrefixCompressed).unwrap();
+        assert_eq!(bytes, page.data);
+        assert_eq!(Ok(()), page.validate_header());
+    }
+
+    #[test]
+    pub fn flagging_pages_without_flags_fails() {
+        let mut page = page_of_type(PageType::DataPage);
+        page.is_dirty = false;
+        let bytes = page.data.clone();
+
+        assert_eq!(0, page.page_flags());
+        assert_eq!(
+            Err(YarddError::WrongPageType {
+                page_id: 7,
+                actual: PageType::DataPage
+            }),
+            page.set_flag(PageFlags::IsRoot)
+        );
+        assert_eq!(
+            Err(YarddError::WrongPageType {
+                page_id: 7,
+                actual: PageType::DataPage
+            }),
+            page.clear_flag(PageFlags::IsRoot)
+        );
+        assert!(!page.is_dirty);
+        assert_eq!(bytes, page.data);
+
+        // An index page from before the slot header was versioned
+        let mut page = page_of_type(PageType::IndexLeaf);
+        assert_eq!(SLOT_HEADER_V1, page.read_slot_header_version());
+        assert_eq!(
+            Err(YarddError::UnsupportedLayout(7)),
+            page.set_flag(PageFlags::IsRoot)
+        );
+        assert!(!page.has_flag(PageFlags::IsRoot));
+    }
 }
@@ -0,0 +1,210 @@
+use std::{
+    cell::UnsafeCell,
+    fmt,
+    ops::{Deref, DerefMut},
+    slice,
+    sync::Arc,
+};
+
+use crate::page::PAGE_SIZE_BYTES;
+
+const PAGE_SIZE: usize = PAGE_SIZE_BYTES as usize;
+
+/// Pool of page buffers for the buffer pool, carved out of one contiguous
+/// block allocated up front. Evicted pages hand their buffer back so loading a
+/// page doesn't allocate.
+pub struct PageArena {
+    free_buffers: Vec<PageBuffer>,
+    allocations: usize,
+}
+
+// The arena's memory. Every page-sized slot is owned by exactly one
+// PageBuffer, which is the only thing that reads or writes it.
+struct ArenaBlock {
+    bytes: Box<[UnsafeCell<u8>]>,
+}
+
+// Slots are never shared between buffers, see ArenaBlock
+unsafe impl Sync for ArenaBlock {}
+
+impl ArenaBlock {
+    fn slot(&self, start: usize) -> *mut u8 {
+        assert!(start + PAGE_SIZE <= self.bytes.len());
+        UnsafeCell::raw_get(self.bytes[start..].as_ptr())
+    }
+}
+
+/// A page's bytes: a slot in a `PageArena`, or a heap allocation for pages
+/// made outside the buffer pool.
+pub struct PageBuffer {
+    storage: Storage,
+}
+
+enum Storage {
+    Arena {
+        block: Arc<ArenaBlock>,
+        start: usize,
+    },
+    Heap(Vec<u8>),
+}
+
+impl PageArena {
+    pub fn new(n_pages: usize) -> Self {
+        let block = Arc::new(ArenaBlock {
+            bytes: (0..n_pages * PAGE_SIZE)
+                .map(|_| UnsafeCell::new(0))
+                .collect(),
+        });
+
+        let free_buffers = (0..n_pages)
+            .map(|i| PageBuffer {
+                storage: Storage::Arena {
+                    block: block.clone(),
+                    start: i * PAGE_SIZE,
+                },
+            })
+            .collect();
+
+        PageArena {
+            free_buffers,
+            allocations: 1,
+        }
+    }
+
+    // Hands out a zeroed buffer, only allocating if every slot is in use
+    pub fn allocate(&mut self) -> PageBuffer {
+        self.free_buffers.pop().unwrap_or_else(|| {
+            self.allocations += 1;
+            vec![0; PAGE_SIZE].into()
+        })
+    }
+
+    pub fn release(&mut self, mut buffer: PageBuffer) {
+        buffer.fill(0);
+        self.free_buffers.push(buffer);
+    }
+
+    // Number of heap allocations made over the arena's lifetime, counting the block
+    pub fn allocations(&self) -> usize {
+        self.allocations
+    }
+
+    pub fn available(&self) -> usize {
+        self.free_buffers.len()
+    }
+}
+
+impl PageBuffer {
+    pub fn is_in_arena(&self) -> bool {
+        matches!(self.storage, Storage::Arena { .. })
+    }
+}
+
+impl Deref for PageBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.storage {
+            Storage::Arena { block, start } => unsafe {
+                slice::from_raw_parts(block.slot(*start), PAGE_SIZE)
+            },
+            Storage::Heap(bytes) => bytes,
+        }
+    }
+}
+
+impl DerefMut for PageBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match &mut self.storage {
+            Storage::Arena { block, start } => unsafe {
+                slice::from_raw_parts_mut(block.slot(*start), PAGE_SIZE)
+            },
+            Storage::Heap(bytes) => bytes,
+        }
+    }
+}
+
+impl From<Vec<u8>> for PageBuffer {
+    fn from(bytes: Vec<u8>) -> Self {
+        PageBuffer {
+            storage: Storage::Heap(bytes),
+        }
+    }
+}
+
+// A copy can't share the slot, so it goes on the heap
+impl Clone for PageBuffer {
+    fn clone(&self) -> Self {
+        self.to_vec().into()
+    }
+}
+
+impl PartialEq for PageBuffer {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl fmt::Debug for PageBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod page_arena_tests {
+    use super::PageArena;
+    use crate::page::PAGE_SIZE_BYTES;
+
+    #[test]
+    pub fn released_buffers_are_reused() {
+        let mut arena = PageArena::new(2);
+
+        let mut first = arena.allocate();
+        let second = arena.allocate();
+        assert_eq!(0, arena.available());
+
+        first.fill(0xAB);
+        arena.release(first);
+        arena.release(second);
+
+        for _ in 0..10 {
+            let buffer = arena.allocate();
+            assert!(buffer.iter().all(|b| *b == 0));
+            arena.release(buffer);
+        }
+
+        assert_eq!(1, arena.allocations());
+
+        // Running out falls back to allocating
+        let buffers: Vec<_> = (0..3).map(|_| arena.allocate()).collect();
+        assert_eq!(3, buffers.len());
+        assert_eq!(2, arena.allocations());
+        assert!(!buffers[2].is_in_arena());
+    }
+
+    #[test]
+    pub fn buffers_are_adjacent_slices_of_one_block() {
+        let mut arena = PageArena::new(3);
+
+        let mut buffers: Vec<_> = (0..3).map(|_| arena.allocate()).collect();
+        for (i, buffer) in buffers.iter_mut().enumerate() {
+            assert!(buffer.is_in_arena());
+            buffer.fill(i as u8 + 1);
+        }
+
+        let mut starts: Vec<_> = buffers
+            .iter()
+            .map(|buffer| buffer.as_ptr() as usize)
+            .collect();
+        starts.sort();
+        for pair in starts.windows(2) {
+            assert_eq!(PAGE_SIZE_BYTES as usize, pair[1] - pair[0]);
+        }
+
+        // Writing one slot leaves its neighbours alone
+        for (i, buffer) in buffers.iter().enumerate() {
+            assert!(buffer.iter().all(|b| *b == i as u8 + 1));
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError},
+};
+
+use crate::page::Page;
+
+/// A write latch on a page that marks the page dirty when released, so
+/// changes made straight to `data` aren't lost when a "clean" page is evicted.
+pub struct PageWriteGuard<'a> {
+    guard: RwLockWriteGuard<'a, Page>,
+}
+
+impl Deref for PageWriteGuard<'_> {
+    type Target = Page;
+
+    fn deref(&self) -> &Page {
+        &self.guard
+    }
+}
+
+impl DerefMut for PageWriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Page {
+        &mut self.guard
+    }
+}
+
+// Taking the latch for writing counts as a change, whether or not anything was written.
+// A page that fails to write through stays dirty and is written back later instead.
+impl Drop for PageWriteGuard<'_> {
+    fn drop(&mut self) {
+        self.guard.is_dirty = true;
+        self.guard.write_version = self.guard.write_version.wrapping_add(1);
+
+        if let Some(write_through) = self.guard.write_through.clone() {
+            let _ = write_through.write(&mut self.guard);
+        }
+    }
+}
+
+/// A read latch on a page that can be traded for a write latch, for a reader
+/// that finds it needs to change the page after all.
+pub struct PageReadGuard<'a> {
+    latch: &'a RwLock<Page>,
+    guard: RwLockReadGuard<'a, Page>,
+}
+
+impl Deref for PageReadGuard<'_> {
+    type Target = Page;
+
+    fn deref(&self) -> &Page {
+        &self.guard
+    }
+}
+
+impl<'a> PageReadGuard<'a> {
+    /// Trades the read latch for a write latch on the same page, unchanged
+    /// since it was read. Fails if another thread holds the latch or wrote the
+    /// page in between, and then hands back a read latch on the page as it is
+    /// now, so the caller has to look at it again, e.g. by descending again.
+    ///
+    /// `RwLock` can't upgrade in place, so the read latch is released first and
+    /// the page's write version tells whether anyone got in. Only writers that
+    /// go through a `PageWriteGuard` bump it.
+    pub fn try_upgrade(self) -> Result<PageWriteGuard<'a>, Self> {
+        let PageReadGuard { latch, guard } = self;
+        let seen = guard.write_version;
+        drop(guard);
+
+        let guard = match latch.try_write() {
+            Ok(guard) => guard,
+            Err(TryLockError::WouldBlock) => return Err(latch.read_guard()),
+            Err(TryLockError::Poisoned(error)) => panic!("{}", error),
+        };
+
+        if guard.write_version != seen {
+            drop(guard);
+            return Err(latch.read_guard());
+        }
+
+        Ok(PageWriteGuard { guard })
+    }
+}
+
+pub trait PageLatch {
+    fn read_guard(&self) -> PageReadGuard<'_>;
+
+    fn write_guard(&self) -> PageWriteGuard<'_>;
+}
+
+// Also covers PagePointer through Arc's Deref
+impl PageLatch for RwLock<Page> {
+    fn read_guard(&self) -> PageReadGuard<'_> {
+        PageReadGuard {
+            latch: self,
+            guard: self.read().unwrap(),
+        }
+    }
+
+    fn write_guard(&self) -> PageWriteGuard<'_> {
+        PageWriteGuard {
+            guard: self.write().unwrap(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod page_guard_tests {
+    use std::{sync::mpsc, thread};
+
+    use crate::page_manager::PageManager;
+
+    use super::PageLatch;
+
+    #[test]
+    pub fn raw_writes_through_guard_mark_page_dirty() {
+        let mut manager = PageManager::in_memory(2);
+        manager.add_empty_pages("memory.db", 2);
+
+        let page = manager.find_page(0).unwrap();
+        assert_eq!(Some(false), manager.is_dirty(0));
+
+        {
+            let mut guard = page.write_guard();
+            guard.data.fill(88);
+            assert!(!guard.is_dirty);
+        }
+
+        assert_eq!(Some(true), manager.is_dirty(0));
+        assert!(page.read().unwrap().data.iter().all(|b| *b == 88));
+
+        // A dirty page isn't dropped to make room
+        drop(page);
+        assert_eq!(1, manager.evict_n(2).unwrap());
+        assert!(manager.resident_pages().any(|(page_id, _)| page_id == 0));
+    }
+
+    #[test]
+    pub fn upgraded_read_guard_is_exclusive() {
+        let mut manager = PageManager::in_memory(2);
+        manager.add_empty_pages("memory.db", 2);
+        let page = manager.find_page(0).unwrap();
+
+        let reader = page.read_guard();
+        let mut writer = match reader.try_upgrade() {
+            Ok(writer) => writer,
+            Err(_) => panic!("Uncontended upgrade failed"),
+        };
+        assert!(page.try_read().is_err());
+
+        // Another thread can't read the page until the write is done
+        let (sender, receiver) = mpsc::channel();
+        let (released_sender, released) = mpsc::channel();
+        thread::scope(|scope| {
+            let page = &page;
+            scope.spawn(move || {
+                sender
+                    .send(page.try_read().map(|page| page.data[0]).ok())
+                    .unwrap();
+                released.recv().unwrap();
+                sender.send(Some(page.read_guard().data[0])).unwrap();
+            });
+
+            writer.data[0] = 42;
+            assert_eq!(None, receiver.recv().unwrap());
+            drop(writer);
+            released_sender.send(()).unwrap();
+
+            assert_eq!(Some(42), receiver.recv().unwrap());
+        });
+        assert_eq!(Some(true), manager.is_dirty(0));
+    }
+
+    #[test]
+    pub fn upgrade_fails_while_others_read() {
+        let mut manager = PageManager::in_memory(2);
+        manager.add_empty_pages("memory.db", 2);
+        let page = manager.find_page(0).unwrap();
+
+        let other = page.read().unwrap();
+        let reader = match page.read_guard().try_upgrade() {
+            Ok(_) => panic!("Upgraded while another thread was reading"),
+            Err(reader) => reader,
+        };
+        drop(other);
+
+        // The failed upgrade still holds the page for reading
+        assert!(page.try_write().is_err());
+        assert!(reader.try_upgrade().is_ok());
+    }
+
+    #[test]
+    pub fn releasing_write_guard_bumps_write_version() {
+        let mut manager = PageManager::in_memory(2);
+        manager.add_empty_pages("memory.db", 2);
+        let page = manager.find_page(0).unwrap();
+
+        let seen = page.read_guard().write_version;
+        drop(page.write_guard());
+        assert_eq!(seen + 1, page.read_guard().write_version);
+
+        // An upgrade is a write too
+        let writer = page.read_guard().try_upgrade();
+        assert!(writer.is_ok());
+        drop(writer);
+        assert_eq!(seen + 2, page.read_guard().write_version);
+    }
+}
@@ -1,146 +1,980 @@
 use std::{
     cmp::min,
-    collections::BTreeMap,
-    sync::{Arc, RwLock},
+    collections::{BTreeMap, HashSet},
+    error::Error,
+    sync::{Arc, Mutex, RwLock, TryLockError},
+    time::{Duration, Instant},
 };
 
 use crate::{
+    catalog::{self, CATALOG_FILE},
     disk_manager::DiskManager,
-    page::{Page, PageId, PAGE_SIZE_BYTES},
-    usage_tracker::UsageTracker,
+    error::{DiskError, InvalidConfig, YarddError},
+    free_space_map::FreeSpaceMap,
+    page::{Page, PageId, PageType},
+    page_arena::PageArena,
+    usage_tracker::{ReplacementPolicy, UsageTracker},
 };
 
 pub type PagePointer = Arc<RwLock<Page>>;
+pub type SharedPageManager = Arc<Mutex<PageManager>>;
+
+/// When modified pages are written back to disk.
+///
+/// Only changes made under a `PageWriteGuard` are seen by `WriteThrough`.
+/// A page changed through a plain `RwLock` write latch is left dirty and
+/// written back like under `WriteBack`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyncPolicy {
+    // Dirty pages are only written when evicted or flushed. Fastest, but
+    // anything not yet evicted is lost in a crash.
+    WriteBack,
+    // A page is written as soon as its write guard is released. Every release
+    // costs a write, so this is the slowest.
+    WriteThrough,
+    // Dirty pages are written on the first fetch once the duration has passed
+    // since the last write, bounding how much work a crash can lose.
+    Periodic(Duration),
+}
+
+/// Collects the settings for a PageManager and checks they fit together.
+/// `PageManager::new` is the shorthand for a writable pool with defaults.
+pub struct PageManagerBuilder {
+    max_num_pages: usize,
+    base_directory: Option<String>,
+    in_memory: bool,
+    read_only: bool,
+    sync_policy: SyncPolicy,
+    replacement_policy: Option<Box<dyn ReplacementPolicy + Send>>,
+    low_watermark: Option<usize>,
+    double_write: bool,
+}
+
+impl PageManagerBuilder {
+    pub fn base_directory(mut self, base_directory: &str) -> Self {
+        self.base_directory = Some(base_directory.to_string());
+        self
+    }
+
+    pub fn in_memory(mut self) -> Self {
+        self.in_memory = true;
+        self
+    }
+
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    pub fn sync_policy(mut self, sync_policy: SyncPolicy) -> Self {
+        self.sync_policy = sync_policy;
+        self
+    }
+
+    pub fn replacement_policy(mut self, policy: Box<dyn ReplacementPolicy + Send>) -> Self {
+        self.replacement_policy = Some(policy);
+        self
+    }
+
+    pub fn low_watermark(mut self, low_watermark: usize) -> Self {
+        self.low_watermark = Some(low_watermark);
+        self
+    }
+
+    pub fn double_write(mut self, double_write: bool) -> Self {
+        self.double_write = double_write;
+        self
+    }
+
+    fn validate(&self) -> Result<(), InvalidConfig> {
+        if self.max_num_pages == 0 {
+            return Err(InvalidConfig("the pool must hold at least one page"));
+        }
+
+        if self.in_memory == self.base_directory.is_some() {
+            return Err(InvalidConfig(
+                "exactly one of a base directory or in-memory is needed",
+            ));
+        }
+
+        if self.read_only && self.in_memory {
+            return Err(InvalidConfig(
+                "an in-memory pool has nothing to open read-only",
+            ));
+        }
+
+        let writes = !self.read_only && !self.in_memory;
+        if !writes && self.sync_policy != SyncPolicy::WriteBack {
+            return Err(InvalidConfig("only a pool that writes to disk can sync"));
+        }
+
+        if !writes && self.double_write {
+            return Err(InvalidConfig(
+                "only a pool that writes to disk can double write",
+            ));
+        }
+
+        if self
+            .low_watermark
+            .is_some_and(|low_watermark| low_watermark >= self.max_num_pages)
+        {
+            return Err(InvalidConfig(
+                "the low watermark must leave room for the page being loaded",
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn build(self) -> Result<PageManager, Box<dyn Error>> {
+        self.validate()?;
+
+        let mut manager = match &self.base_directory {
+            _ if self.in_memory => PageManager::in_memory(self.max_num_pages),
+            Some(base_directory) if self.read_only => {
+                PageManager::open_read_only(self.max_num_pages, base_directory)?
+            }
+            Some(base_directory) => {
+                PageManager::with_sync_policy(self.max_num_pages, base_directory, self.sync_policy)
+            }
+            None => unreachable!("Validated above"),
+        };
+
+        if let Some(policy) = self.replacement_policy {
+            manager.set_replacement_policy(policy);
+        }
+
+        if let Some(low_watermark) = self.low_watermark {
+            manager.set_low_watermark(low_watermark);
+        }
+
+        manager
+            .pool
+            .lock()
+            .unwrap()
+            .disk_manager
+            .set_double_write(self.double_write);
+
+        Ok(manager)
+    }
+}
 
 pub struct PageManager {
-    disk_manager: DiskManager,
-    usage_tracker: UsageTracker,
-    pages: BTreeMap<PageId, PagePointer>,
-    empty_pages: Vec<PageId>, // List of pages allocated by the disk manager which are empty. Not necessarily memory in the page manager
+    // Hits only take the page table's read lock and the usage tracker, so
+    // fetches of resident pages run concurrently. Misses take the pool lock as
+    // well. Locks are always taken in the order pool, pages, usage tracker.
+    pages: RwLock<BTreeMap<PageId, PagePointer>>,
+    usage_tracker: Mutex<Box<dyn ReplacementPolicy + Send>>,
+    pool: Arc<Mutex<BufferPool>>,
     max_num_pages: usize,
+    low_watermark: usize, // Evictions on a miss with a full pool go down to this many pages
+    catalog_page_id: Option<PageId>,
+    index_roots: BTreeMap<String, PageId>, // Mirrors the catalog page
+    sync_policy: SyncPolicy,
+    last_sync: Mutex<Instant>,
+    flushed_lsn: Option<u64>, // None until a log reports what it has made durable
+}
+
+// Everything a miss changes besides the page table, so pages are loaded one at a time
+struct BufferPool {
+    disk_manager: DiskManager,
+    arena: PageArena,
+    free_resident: Vec<PageId>, // Free pages that are in the buffer pool
+    unallocated: Vec<PageId>,   // Free pages that only exist on disk
+    free_space: FreeSpaceMap,
+}
+
+/// Lets a page write itself to disk when its write guard is released, under
+/// `SyncPolicy::WriteThrough`. Pages loaded under other policies don't get one.
+#[derive(Clone)]
+pub struct WriteThrough {
+    pool: Arc<Mutex<BufferPool>>,
+}
+
+impl WriteThrough {
+    pub(crate) fn write(&self, page: &mut Page) -> Result<(), YarddError> {
+        let mut pool = self.pool.lock().unwrap();
+        if !pool.writes_to_disk() {
+            return Ok(());
+        }
+
+        page.update_checksum();
+        pool.free_space.record_page(page);
+        pool.disk_manager.save_page(page.page_id, &page.data)?;
+        page.is_dirty = false;
+
+        Ok(())
+    }
 }
 
 impl PageManager {
     pub fn new(max_num_pages: usize, base_directory: &str) -> PageManager {
+        Self::with_sync_policy(max_num_pages, base_directory, SyncPolicy::WriteBack)
+    }
+
+    pub fn builder(max_num_pages: usize) -> PageManagerBuilder {
+        PageManagerBuilder {
+            max_num_pages,
+            base_directory: None,
+            in_memory: false,
+            read_only: false,
+            sync_policy: SyncPolicy::WriteBack,
+            replacement_policy: None,
+            low_watermark: None,
+            double_write: false,
+        }
+    }
+
+    pub fn with_sync_policy(
+        max_num_pages: usize,
+        base_directory: &str,
+        sync_policy: SyncPolicy,
+    ) -> PageManager {
         PageManager {
-            disk_manager: DiskManager::new(base_directory),
-            usage_tracker: UsageTracker::new(),
-            pages: BTreeMap::new(),
-            empty_pages: vec![],
+            pages: RwLock::new(BTreeMap::new()),
+            usage_tracker: Mutex::new(Box::new(UsageTracker::with_capacity(max_num_pages))),
+            pool: Arc::new(Mutex::new(BufferPool {
+                disk_manager: DiskManager::new(base_directory),
+                arena: PageArena::new(max_num_pages),
+                free_resident: Vec::with_capacity(max_num_pages),
+                unallocated: vec![],
+                free_space: FreeSpaceMap::new(),
+            })),
             max_num_pages,
+            low_watermark: max_num_pages.saturating_sub(1),
+            catalog_page_id: None,
+            index_roots: BTreeMap::new(),
+            sync_policy,
+            last_sync: Mutex::new(Instant::now()),
+            flushed_lsn: None,
         }
     }
 
+    // Opens an existing database for inspection. Pages can still be modified in
+    // memory, but writing a dirty page back to disk is an error.
+    pub fn open_read_only(
+        max_num_pages: usize,
+        base_directory: &str,
+    ) -> Result<PageManager, Box<dyn Error>> {
+        let mut manager = PageManager::new(max_num_pages, base_directory);
+        manager.pool.lock().unwrap().disk_manager = DiskManager::open_read_only(base_directory)?;
+        manager.load_catalog()?;
+
+        Ok(manager)
+    }
+
+    /// Reopens the database under base_directory for reading and writing,
+    /// with the index roots recorded in its catalog.
+    pub fn open(max_num_pages: usize, base_directory: &str) -> Result<PageManager, Box<dyn Error>> {
+        let mut manager = PageManager::new(max_num_pages, base_directory);
+        manager.pool.lock().unwrap().disk_manager = DiskManager::open(base_directory)?;
+        manager.load_catalog()?;
+
+        Ok(manager)
+    }
+
+    // The catalog is the first page of its file, once set_root has written it
+    fn load_catalog(&mut self) -> Result<(), YarddError> {
+        let Some(page_id) = self.pages_in_file(CATALOG_FILE).first().copied() else {
+            return Ok(());
+        };
+
+        let page = self.find_page(page_id)?;
+        let page = page.read().unwrap();
+        if page.is_initialized() && page.try_read_page_type() == Ok(PageType::Catalog) {
+            self.index_roots = catalog::read_roots(&page);
+        }
+        self.catalog_page_id = Some(page_id);
+
+        Ok(())
+    }
+
+    /// A pool with no disk backing, for tests and caches. Modified pages are
+    /// never evicted, only clean ones, which read back as empty. Running out of
+    /// clean pages to evict is the same as running out of memory.
+    pub fn in_memory(max_num_pages: usize) -> PageManager {
+        let manager = PageManager::new(max_num_pages, "");
+        manager.pool.lock().unwrap().disk_manager = DiskManager::in_memory();
+
+        manager
+    }
+
+    // Pages already in the pool start out in the new policy as if just loaded
+    pub fn set_replacement_policy(&mut self, mut policy: Box<dyn ReplacementPolicy + Send>) {
+        for page_id in self.pages.get_mut().unwrap().keys() {
+            policy.insert(*page_id);
+        }
+
+        *self.usage_tracker.get_mut().unwrap() = policy;
+    }
+
+    /// How far a miss on a full pool evicts, as a number of resident pages.
+    /// By default one page is evicted per miss. A lower watermark evicts a
+    /// batch in one pass with one write, so the misses that follow are cheap.
+    pub fn set_low_watermark(&mut self, low_watermark: usize) {
+        assert!(
+            low_watermark < self.max_num_pages,
+            "Low watermark must leave room for the page being loaded"
+        );
+
+        self.low_watermark = low_watermark;
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.pool.lock().unwrap().disk_manager.is_read_only()
+    }
+
+    pub fn is_in_memory(&self) -> bool {
+        self.pool.lock().unwrap().disk_manager.is_in_memory()
+    }
+
+    // A read-only manager can drop clean pages but has nowhere to put dirty ones
+    fn check_writable(
+        pool: &BufferPool,
+        pages: &BTreeMap<PageId, PagePointer>,
+        page_ids: &[PageId],
+    ) -> Result<(), DiskError> {
+        if pool.disk_manager.is_read_only()
+            && page_ids
+                .iter()
+                .any(|page_id| pages.get(page_id).is_none_or(page_is_dirty))
+        {
+            return Err(DiskError::ReadOnly);
+        }
+
+        Ok(())
+    }
+
     pub fn add_empty_pages(&mut self, file: &str, n_pages: usize) {
-        let empty_pages = self.disk_manager.allocate_pages(n_pages, file).unwrap();
+        let write_through = self.write_through();
+        let mut pool = self.pool.lock().unwrap();
+        let pool = &mut *pool;
+        let pages = self.pages.get_mut().unwrap();
+        let usage_tracker = self.usage_tracker.get_mut().unwrap();
+
+        let empty_pages = pool.disk_manager.allocate_pages(n_pages, file).unwrap();
+
+        // Every page could end up free and evicted, so size the list for that once
+        // rather than growing it while pages churn through the pool
+        let total_pages = pool.disk_manager.page_count();
+        pool.unallocated
+            .reserve(total_pages.saturating_sub(pool.unallocated.len()));
 
-        let buffer_spots = self.max_num_pages - self.pages.len();
+        let buffer_spots = self.max_num_pages - pages.len();
         let len = min(buffer_spots, n_pages);
 
         for (i, id) in empty_pages.iter().enumerate() {
             if i < len {
-                self.add_free_page(*id);
+                let page = Arc::new(RwLock::new(Page {
+                    page_id: *id,
+                    data: pool.arena.allocate(),
+                    is_dirty: false,
+                    write_version: 0,
+                    write_through: write_through.clone(),
+                }));
+                pages.insert(*id, page);
+                usage_tracker.insert(*id);
+
+                pool.free_resident.push(*id);
             } else {
-                self.empty_pages.push(*id);
+                pool.unallocated.push(*id);
             }
         }
     }
 
-    fn add_free_page(&mut self, page_id: PageId) {
-        // Arena allocate eventually
-        let page = Arc::new(RwLock::new(Page {
-            page_id: page_id,
-            data: vec![0; PAGE_SIZE_BYTES as usize],
-            is_dirty: false,
-        }));
-        self.pages.insert(page_id, page);
-        self.usage_tracker.insert(page_id);
+    // Hands a page that is no longer referenced back to the free list
+    pub fn release_page(&mut self, page_id: PageId) {
+        let mut pool = self.pool.lock().unwrap();
+        pool.free_space.remove(page_id);
+
+        if self.pages.get_mut().unwrap().contains_key(&page_id) {
+            pool.free_resident.push(page_id);
+        } else {
+            pool.unallocated.push(page_id);
+        }
+    }
+
+    /// Gives the free pages at the end of a file back to the filesystem, e.g.
+    /// after compacting it, and returns how many were removed. A free page
+    /// someone still holds a pointer to stays, and so does every page before it.
+    pub fn truncate_file(&mut self, file_name: &str) -> Result<usize, Box<dyn Error>> {
+        let mut pool = self.pool.lock().unwrap();
+        let pages = self.pages.get_mut().unwrap();
+
+        let free: HashSet<PageId> = pool
+            .free_resident
+            .iter()
+            .filter(|page_id| Arc::strong_count(&pages[*page_id]) == 1)
+            .chain(pool.unallocated.iter())
+            .copied()
+            .collect();
+
+        let truncated = pool.disk_manager.truncate_file(file_name, &free)?;
+        let truncated_set: HashSet<PageId> = truncated.iter().copied().collect();
+
+        pool.free_resident
+            .retain(|page_id| !truncated_set.contains(page_id));
+        pool.unallocated
+            .retain(|page_id| !truncated_set.contains(page_id));
+
+        let usage_tracker = self.usage_tracker.get_mut().unwrap();
+        for page_id in &truncated {
+            pool.free_space.remove(*page_id);
+            if let Some(page) = pages.remove(page_id) {
+                usage_tracker.remove(*page_id);
+                pool.release_buffer(page);
+            }
+        }
+
+        Ok(truncated.len())
+    }
+
+    // Every page stored in the file, whether or not it's resident
+    pub fn pages_in_file(&self, file_name: &str) -> Vec<PageId> {
+        self.pool
+            .lock()
+            .unwrap()
+            .disk_manager
+            .pages_in_file(file_name)
+    }
+
+    // Number of free pages, resident or not
+    pub fn free_page_count(&self) -> usize {
+        let pool = self.pool.lock().unwrap();
+        pool.free_resident.len() + pool.unallocated.len()
+    }
+
+    // Prefers pages already in the pool so handing one out doesn't cost a read
+    pub fn next_free_page(&mut self) -> Result<PagePointer, YarddError> {
+        let page_id = {
+            let mut pool = self.pool.lock().unwrap();
+            pool.free_resident
+                .pop()
+                .or_else(|| pool.unallocated.pop())
+                .ok_or(YarddError::PoolExhausted)?
+        };
 
-        self.empty_pages.push(page_id);
+        self.reset_free_page(page_id)
     }
 
-    pub fn next_free_page(&mut self) -> PagePointer {
-        if self.empty_pages.len() == 0 {
-            panic!("No empty pages left"); // out of memory
+    /// Like `next_free_page`, but the page is stored in `file_name`, so e.g. a
+    /// table's pages can be kept in its own file. A page is added to the file
+    /// when it has none free.
+    pub fn next_free_page_in(&mut self, file_name: &str) -> Result<PagePointer, YarddError> {
+        let in_file: HashSet<PageId> = self.pages_in_file(file_name).into_iter().collect();
+
+        let free_page = {
+            let mut pool = self.pool.lock().unwrap();
+            let pool = &mut *pool;
+            let free_page = [&mut pool.free_resident, &mut pool.unallocated]
+                .into_iter()
+                .find_map(|free| {
+                    let i = free.iter().rposition(|page_id| in_file.contains(page_id))?;
+                    Some(free.remove(i))
+                });
+            free_page
+        };
+
+        match free_page {
+            Some(page_id) => self.reset_free_page(page_id),
+            None => {
+                self.add_empty_pages(file_name, 1);
+                self.next_free_page_in(file_name)
+            }
         }
+    }
+
+    // A released page may still hold its old contents, in memory or on disk.
+    // Nothing else references a free page, so the latch is uncontended. If it
+    // can't be loaded the page stays free.
+    fn reset_free_page(&mut self, page_id: PageId) -> Result<PagePointer, YarddError> {
+        let page = match self.find_page(page_id) {
+            Ok(page) => page,
+            Err(error) => {
+                self.pool.lock().unwrap().unallocated.push(page_id);
+                return Err(error);
+            }
+        };
+        page.write().unwrap().reset();
+
+        Ok(page)
+    }
+
+    // Lets an inserter report free space as soon as it has written to a data page
+    pub fn update_free_space_hint(&mut self, page_id: PageId, free_bytes: usize) {
+        self.pool
+            .lock()
+            .unwrap()
+            .free_space
+            .record(page_id, free_bytes);
+    }
+
+    pub fn free_space_hint(&self, page_id: PageId) -> Option<usize> {
+        self.pool.lock().unwrap().free_space.get(page_id)
+    }
+
+    // The data page with the most free space, if it has at least `needed` bytes.
+    // Hints are approximate so the caller must still handle a full page.
+    pub fn page_with_free_space(&self, needed: usize) -> Option<PageId> {
+        self.pool.lock().unwrap().free_space.page_with_room(needed)
+    }
+
+    // Data pages with a hint, most free space first, as of the call
+    pub fn pages_by_free_space(&self) -> impl Iterator<Item = (PageId, usize)> {
+        let ranked: Vec<_> = self.pool.lock().unwrap().free_space.ranked().collect();
+        ranked.into_iter()
+    }
 
-        // Future optimization: try to find one that's in memory already
-        let page_id = self.empty_pages.pop().unwrap();
+    // Called by the log once every record up to `lsn` is durable
+    pub fn set_flushed_lsn(&mut self, lsn: u64) {
+        self.flushed_lsn = Some(lsn);
+    }
 
-        let page = self.find_page(page_id);
+    // Write-ahead rule: a dirty page can't reach disk before the log records
+    // describing its changes. Only called for unreferenced pages, so the latch is free.
+    fn log_allows_eviction(&self, page: &PagePointer) -> bool {
+        let Some(flushed_lsn) = self.flushed_lsn else {
+            return true;
+        };
 
-        page
+        let page = page.read().unwrap();
+        !page.is_dirty || page.read_lsn() <= flushed_lsn
     }
 
-    fn evict_next_page(&mut self) -> Option<()> {
-        let mut page_to_evict = None;
+    // Finds up to n pages that can be evicted, least recently used first.
+    // Widens the candidate list until enough unreferenced pages turn up.
+    fn eviction_victims(
+        &self,
+        pool: &BufferPool,
+        pages: &BTreeMap<PageId, PagePointer>,
+        n: usize,
+    ) -> Vec<PageId> {
+        let mut k = n;
 
-        for (page_id, _) in self.usage_tracker.last_used.iter() {
-            let page = self.pages.get(page_id).unwrap();
+        loop {
+            let candidates = self.usage_tracker.lock().unwrap().evict_candidates(k);
 
             // If there's only one reference then it must not be in use by any clients.
-            // Note this only work because we've already locked the page_manager
-            // Consider making your own class that does this automatically.
-            if Arc::strong_count(page) == 1 {
-                page_to_evict = Some(*page_id);
+            // References are only handed out under the page table's lock, which the
+            // caller holds for writing, so the count can't go up before the page is removed.
+            let victims: Vec<PageId> = candidates
+                .iter()
+                .copied()
+                .filter(|page_id| Arc::strong_count(&pages[page_id]) == 1)
+                .filter(|page_id| self.log_allows_eviction(&pages[page_id]))
+                .filter(|page_id| {
+                    !pool.disk_manager.is_in_memory() || !page_is_dirty(&pages[page_id])
+                })
+                .take(n)
+                .collect();
+
+            if victims.len() == n || candidates.len() < k {
+                return victims;
+            }
+
+            k *= 2;
+        }
+    }
+
+    // Records the root of a named index in the catalog page
+    pub fn set_root(&mut self, index_name: &str, root: PageId) -> Result<(), YarddError> {
+        self.index_roots.insert(index_name.to_string(), root);
+
+        let catalog_page_id = match self.catalog_page_id {
+            Some(page_id) => page_id,
+            None => {
+                let disk_manager = &mut self.pool.lock().unwrap().disk_manager;
+                let page_id = disk_manager.allocate_pages(1, CATALOG_FILE)?[0];
+                self.catalog_page_id = Some(page_id);
+                page_id
+            }
+        };
+
+        // Only the page manager touches the catalog page and set_root has the
+        // manager to itself, so the latch is uncontended
+        let page = self.find_page(catalog_page_id)?;
+        catalog::write_roots(&mut page.write().unwrap(), &self.index_roots);
+
+        Ok(())
+    }
+
+    pub fn get_root(&self, index_name: &str) -> Option<PageId> {
+        self.index_roots.get(index_name).copied()
+    }
+
+    // Pages in the pool in page id order. Iterating doesn't count as a use,
+    // but a page can't be evicted while its pointer is held.
+    pub fn resident_pages(&self) -> impl Iterator<Item = (PageId, PagePointer)> {
+        let pages: Vec<_> = self
+            .pages
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(page_id, page)| (*page_id, page.clone()))
+            .collect();
+
+        pages.into_iter()
+    }
+
+    /// Describes the pool for debugging a hang: one line per resident page in
+    /// page id order, with its reference count, the pool's own included, so a
+    /// page with more than one reference is pinned and can't be evicted.
+    /// Latches are only tried, so the dump never waits on a page. A page whose
+    /// read latch can't be had is write latched, and its dirty flag is unknown.
+    pub fn debug_dump(&self) -> String {
+        let pages = self.pages.read().unwrap();
+        let mut dump = format!("{} of {} pages resident\n", pages.len(), self.max_num_pages);
+
+        for (page_id, page) in pages.iter() {
+            let state = match page.try_read() {
+                Ok(page) if page.is_dirty => "dirty",
+                Ok(_) => "clean",
+                Err(TryLockError::WouldBlock) => "write latched",
+                Err(TryLockError::Poisoned(_)) => "poisoned",
+            };
+
+            dump += &format!(
+                "page {}: {} refs, {}\n",
+                page_id,
+                Arc::strong_count(page),
+                state
+            );
+        }
+
+        dump
+    }
+
+    // Whether a resident page has unsaved changes. Only takes a read latch.
+    pub fn is_dirty(&self, page_id: PageId) -> Option<bool> {
+        self.pages.read().unwrap().get(&page_id).map(page_is_dirty)
+    }
+
+    // Writes every dirty page back to disk and marks it clean. Pages someone
+    // is latching are skipped and stay dirty, since waiting on them could deadlock.
+    pub fn flush_all(&mut self) -> Result<(), Box<dyn Error>> {
+        let mut pool = self.pool.lock().unwrap();
+        let pages = self.pages.get_mut().unwrap();
+
+        // Modified pages stay dirty so they're never evicted
+        if pool.disk_manager.is_in_memory() {
+            return Ok(());
+        }
+
+        let mut guards = vec![];
+        for page in pages.values() {
+            let page = match page.try_write() {
+                Ok(page) => page,
+                Err(TryLockError::WouldBlock) => continue,
+                Err(TryLockError::Poisoned(_)) => return Err("Page lock poisoned".into()),
+            };
+            if page.is_dirty {
+                guards.push(page);
+            }
+        }
+
+        if !guards.is_empty() && pool.disk_manager.is_read_only() {
+            return Err(DiskError::ReadOnly.into());
+        }
+
+        for page in guards.iter_mut() {
+            page.update_checksum();
+            pool.free_space.record_page(page);
+        }
+
+        let writes: Vec<(PageId, &[u8])> = guards
+            .iter()
+            .map(|page| (page.page_id, &page.data[..]))
+            .collect();
+        pool.disk_manager.save_pages(&writes)?;
+
+        for page in guards.iter_mut() {
+            page.is_dirty = false;
+        }
+
+        Ok(())
+    }
+
+    // Flushes all dirty pages and syncs them to stable storage
+    pub fn close(mut self) -> Result<(), Box<dyn Error>> {
+        self.flush_all()?;
+        self.pool.lock().unwrap().disk_manager.sync()
+    }
+
+    // Evicts up to n unreferenced pages in LRU order, batching their writes.
+    // Returns the number of pages evicted.
+    pub fn evict_n(&mut self, n: usize) -> Result<usize, YarddError> {
+        let mut pool = self.pool.lock().unwrap();
+        self.evict_from(&mut pool, n)
+    }
+
+    fn evict_from(&self, pool: &mut BufferPool, n: usize) -> Result<usize, YarddError> {
+        let pages: Vec<PagePointer> = {
+            let mut table = self.pages.write().unwrap();
+            let victims = self.eviction_victims(pool, &table, n);
+            Self::check_writable(pool, &table, &victims)?;
+
+            let mut usage_tracker = self.usage_tracker.lock().unwrap();
+            victims
+                .iter()
+                .map(|page_id| {
+                    usage_tracker.remove(*page_id);
+                    pool.free_page_evicted(*page_id);
+                    table.remove(page_id).unwrap()
+                })
+                .collect()
+        };
+
+        let mut guards: Vec<_> = pages.iter().map(|page| page.write().unwrap()).collect();
+        for page in guards.iter_mut() {
+            Self::stamp_for_write(page);
+            if page.is_dirty {
+                pool.free_space.record_page(page);
+            }
+        }
+
+        let writes: Vec<(PageId, &[u8])> = guards
+            .iter()
+            .map(|page| (page.page_id, &page.data[..]))
+            .collect();
+
+        if pool.writes_to_disk() {
+            pool.disk_manager.save_pages(&writes)?;
+        }
+
+        drop(guards);
+        let n_evicted = pages.len();
+        for page in pages {
+            pool.release_buffer(page);
+        }
+
+        Ok(n_evicted)
+    }
+
+    // Evicted pages are written even when clean, so their checksum is
+    // refreshed in case the data was changed without marking them dirty.
+    // Blank pages stay all zeros.
+    fn stamp_for_write(page: &mut Page) {
+        if page.is_dirty || page.is_initialized() {
+            page.update_checksum();
+        }
+    }
+
+    // Loads any pages that aren't resident yet without making them most recently used,
+    // so a read-ahead doesn't push out pages that are actually in use. Stops early
+    // if the pool is full of referenced pages, or of dirty ones a read-only manager can't write.
+    pub fn prefetch(&self, page_ids: &[PageId]) -> Result<(), YarddError> {
+        let mut pool = self.pool.lock().unwrap();
+
+        for page_id in page_ids {
+            let n_resident = {
+                let pages = self.pages.read().unwrap();
+                if pages.contains_key(page_id) {
+                    continue;
+                }
+                pages.len()
+            };
+
+            if n_resident == self.max_num_pages && !matches!(self.evict_from(&mut pool, 1), Ok(1)) {
                 break;
             }
+
+            let mut page = pool.read_page(*page_id)?;
+            page.write_through = self.write_through();
+            let mut pages = self.pages.write().unwrap();
+            pages.insert(*page_id, Arc::new(RwLock::new(page)));
+            self.usage_tracker.lock().unwrap().insert_cold(*page_id);
         }
 
-        if let Some(page_id) = page_to_evict {
-            let page = self.pages.remove(&page_id).unwrap();
-            self.usage_tracker.last_used.remove(&page_id);
-            let page_inner = page.write().unwrap();
+        Ok(())
+    }
+
+    fn load_page(&self, page_id: PageId) -> Result<PagePointer, YarddError> {
+        let mut pool = self.pool.lock().unwrap();
 
-            self.disk_manager
-                .save_page(page_id, &page_inner.data)
-                .unwrap();
+        // Another thread may have loaded the page while this one waited for the pool
+        let n_resident = {
+            let pages = self.pages.read().unwrap();
+            if let Some(page) = pages.get(&page_id) {
+                self.usage_tracker.lock().unwrap().touch(page_id);
+                return Ok(page.clone());
+            }
+            pages.len()
+        };
 
-            Some(())
-        } else {
-            None
+        if n_resident == self.max_num_pages {
+            let evicted = self.evict_from(&mut pool, n_resident - self.low_watermark)?;
+            if evicted == 0 {
+                return Err(YarddError::PoolExhausted);
+            }
+        }
+
+        let mut page = pool.read_page(page_id)?;
+        page.write_through = self.write_through();
+        let page = Arc::new(RwLock::new(page));
+
+        let mut pages = self.pages.write().unwrap();
+        pages.insert(page_id, page.clone());
+        self.usage_tracker.lock().unwrap().insert(page_id);
+
+        Ok(page)
+    }
+
+    fn write_through(&self) -> Option<WriteThrough> {
+        (self.sync_policy == SyncPolicy::WriteThrough).then(|| WriteThrough {
+            pool: self.pool.clone(),
+        })
+    }
+
+    fn apply_sync_policy(&self) -> Result<(), YarddError> {
+        let due = match self.sync_policy {
+            SyncPolicy::WriteBack | SyncPolicy::WriteThrough => false,
+            SyncPolicy::Periodic(interval) => self.last_sync.lock().unwrap().elapsed() >= interval,
+        };
+
+        if due {
+            let mut pool = self.pool.lock().unwrap();
+            if !pool.disk_manager.is_in_memory() {
+                self.write_unlatched_dirty_pages(&mut pool)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Only tries the latches, since the caller may be holding some of them.
+    // Pages that are latched get written on a later pass.
+    fn write_unlatched_dirty_pages(&self, pool: &mut BufferPool) -> Result<(), YarddError> {
+        for page in self.pages.read().unwrap().values() {
+            let Ok(mut page) = page.try_write() else {
+                continue;
+            };
+
+            if page.is_dirty {
+                page.update_checksum();
+                pool.free_space.record_page(&page);
+                pool.disk_manager.save_page(page.page_id, &page.data)?;
+                page.is_dirty = false;
+            }
+        }
+
+        *self.last_sync.lock().unwrap() = Instant::now();
+
+        Ok(())
+    }
+
+    /// Fetches a page, loading it from disk on a miss. Takes `&self`, so
+    /// threads sharing the manager fetch resident pages without waiting on
+    /// each other. Misses are loaded one at a time.
+    ///
+    /// Fails if the page can't be read back, e.g. its file is gone or the
+    /// page is torn, or if writing back dirty pages first fails.
+    pub fn find_page(&self, page_id: PageId) -> Result<PagePointer, YarddError> {
+        self.apply_sync_policy()?;
+
+        let resident = self.pages.read().unwrap().get(&page_id).cloned();
+        match resident {
+            Some(page) => {
+                self.usage_tracker.lock().unwrap().touch(page_id);
+                Ok(page)
+            }
+            None => self.load_page(page_id),
         }
     }
+}
+
+impl BufferPool {
+    // Whether evicted and flushed pages actually reach the disk
+    fn writes_to_disk(&self) -> bool {
+        !self.disk_manager.is_read_only() && !self.disk_manager.is_in_memory()
+    }
 
-    fn load_page(&mut self, page_id: PageId) -> PagePointer {
-        if self.pages.len() == self.max_num_pages {
-            self.evict_next_page().expect("All pages are in use");
+    // A free page that leaves the pool is no longer resident
+    fn free_page_evicted(&mut self, page_id: PageId) {
+        if let Some(i) = self.free_resident.iter().position(|id| *id == page_id) {
+            self.free_resident.swap_remove(i);
+            self.unallocated.push(page_id);
         }
+    }
+
+    // Returns an evicted page's buffer to the arena. Victims are only chosen
+    // when the pool holds the sole reference, so unwrapping can't fail.
+    fn release_buffer(&mut self, page: PagePointer) {
+        let page = Arc::try_unwrap(page)
+            .unwrap_or_else(|_| panic!("Evicted page is still referenced"))
+            .into_inner()
+            .unwrap();
 
-        let data = self.disk_manager.load_page(page_id).unwrap();
+        self.arena.release(page.data);
+    }
+
+    fn read_page(&mut self, page_id: PageId) -> Result<Page, YarddError> {
+        let mut data = self.arena.allocate();
+        if let Err(error) = self.disk_manager.load_page_into(page_id, &mut data) {
+            self.arena.release(data);
+            return Err(error);
+        }
 
-        let page = Arc::new(RwLock::new(Page {
+        let page = Page {
             page_id,
             data,
             is_dirty: false,
-        }));
+            write_version: 0,
+            write_through: None,
+        };
 
-        self.pages.insert(page_id, page.clone());
-        self.usage_tracker.insert(page_id);
+        // Catch offset bugs and torn pages before anyone interprets the bytes
+        if let Err(error) = page.validate_header().and_then(|()| page.verify_checksum()) {
+            self.arena.release(page.data);
+            return Err(error);
+        }
+
+        // Hints only live in memory, so rebuild them as pages are read
+        self.free_space.record_page(&page);
 
-        page
+        Ok(page)
     }
+}
 
-    pub fn find_page(&mut self, page_id: PageId) -> PagePointer {
-        if let Some(page) = self.pages.get(&page_id) {
-            self.usage_tracker.touch(page_id);
-            page.clone()
-        } else {
-            self.load_page(page_id)
+// A poisoned page counts as dirty so flush_all reports it
+fn page_is_dirty(page: &PagePointer) -> bool {
+    page.read().map(|page| page.is_dirty).unwrap_or(true)
+}
+
+impl Drop for PageManager {
+    fn drop(&mut self) {
+        // A panic while the pool was locked may have left it half updated
+        if self.pool.is_poisoned() || self.pages.is_poisoned() {
+            return;
         }
+
+        // Drop can't report a failure, `close` is the way to see one
+        let _ = self.flush_all();
     }
 }
 
 #[cfg(test)]
 mod page_manager_tests {
     use std::{
-        fs::{create_dir_all, remove_dir_all},
+        fs::{create_dir_all, read, remove_dir_all},
         path::Path,
+        sync::Arc,
+        thread::{self, sleep},
+        time::Duration,
     };
 
-    use crate::page::PAGE_SIZE_BYTES;
+    use crate::{
+        data_page::DataPageMut,
+        disk_btree::{IndexPageMut, KeyEntry},
+        error::{InvalidConfig, YarddError},
+        page::{
+            PageHeader, PageId, PageType, HEADER_SIZE, MAGIC_NUMBER_START, PAGE_MAGIC_NUMBER,
+            PAGE_SIZE_BYTES, SLOTS_HEADER_SIZE, SLOTS_HEADER_START,
+        },
+        page_guard::PageLatch,
+        usage_tracker::LfuPolicy,
+    };
 
-    use super::PageManager;
+    use super::{catalog, PageManager, SharedPageManager, SyncPolicy};
 
     fn setup_test_dir(base_dir: &str) {
         let path = Path::new(base_dir);
@@ -151,6 +985,16 @@ mod page_manager_tests {
         let _ = remove_dir_all(base_dir);
     }
 
+    fn data_page_header(page_id: u64) -> PageHeader {
+        PageHeader {
+            magic_number: PAGE_MAGIC_NUMBER,
+            page_type: PageType::DataPage,
+            log_sequence_number: 0,
+            parent_page_id: 0,
+            page_id,
+        }
+    }
+
     #[test]
     pub fn allocate_empty_pages() {
         let base_dir = "./test1";
@@ -160,8 +1004,11 @@ mod page_manager_tests {
 
         manager.add_empty_pages("empty.db", 100);
 
-        assert_eq!(manager.pages.len(), 50);
-        assert_eq!(manager.usage_tracker.last_used.len(), 50);
+        assert_eq!(manager.pages.read().unwrap().len(), 50);
+        assert_eq!(manager.usage_tracker.lock().unwrap().len(), 50);
+        assert_eq!(manager.pool.lock().unwrap().free_resident.len(), 50);
+        assert_eq!(manager.pool.lock().unwrap().unallocated.len(), 50);
+        assert_eq!(manager.free_page_count(), 100);
 
         cleanup(base_dir);
     }
@@ -175,41 +1022,45 @@ mod page_manager_tests {
         manager.add_empty_pages("empty.db", 2);
 
         let page_id_1 = {
-            let page = manager.next_free_page();
+            let page = manager.next_free_page().unwrap();
             let mut page = page.write().expect("Failed to unlock mutex");
-            page.data.fill(88);
-            page.page_id
+            let page_id = page.page_id;
+            page.write_header(data_page_header(page_id));
+            page.data[HEADER_SIZE..].fill(88);
+            page_id
         };
 
         let page_id_2 = {
-            let page = manager.next_free_page();
+            let page = manager.next_free_page().unwrap();
             let mut page = page.write().expect("Failed to unlock mutex");
-            page.data.fill(77);
-            page.page_id
+            let page_id = page.page_id;
+            page.write_header(data_page_header(page_id));
+            page.data[HEADER_SIZE..].fill(77);
+            page_id
         };
 
-        assert_eq!(manager.pages.len(), 1);
-        assert_eq!(manager.usage_tracker.last_used.len(), 1);
+        assert_eq!(manager.pages.read().unwrap().len(), 1);
+        assert_eq!(manager.usage_tracker.lock().unwrap().len(), 1);
 
         {
-            let page = manager.find_page(page_id_1);
+            let page = manager.find_page(page_id_1).unwrap();
             let page = page.read().expect("Failed to unlock mutex");
 
             assert_eq!(page.data.len() as u16, PAGE_SIZE_BYTES);
-            for b in page.data.iter() {
+            for b in page.data[HEADER_SIZE..].iter() {
                 assert_eq!(*b, 88);
             }
         }
 
-        assert_eq!(manager.pages.len(), 1);
-        assert_eq!(manager.usage_tracker.last_used.len(), 1);
+        assert_eq!(manager.pages.read().unwrap().len(), 1);
+        assert_eq!(manager.usage_tracker.lock().unwrap().len(), 1);
 
         {
-            let page = manager.find_page(page_id_2);
+            let page = manager.find_page(page_id_2).unwrap();
             let page = page.read().expect("Failed to unlock mutex");
 
             assert_eq!(page.data.len() as u16, PAGE_SIZE_BYTES);
-            for b in page.data.iter() {
+            for b in page.data[HEADER_SIZE..].iter() {
                 assert_eq!(*b, 77);
             }
         }
@@ -219,47 +1070,47 @@ mod page_manager_tests {
 
     #[test]
     fn evict_lru_page() {
-        let base_dir = "./test2";
+        let base_dir = "./test4";
         setup_test_dir(base_dir);
 
         let mut manager = PageManager::new(2, base_dir);
         manager.add_empty_pages("empty.db", 3);
 
         let page_id_1 = {
-            let page = manager.next_free_page();
+            let page = manager.next_free_page().unwrap();
             let page = page.read().unwrap();
             page.page_id
         };
 
         let page_id_2 = {
-            let page = manager.next_free_page();
+            let page = manager.next_free_page().unwrap();
             let page = page.read().unwrap();
             page.page_id
         };
 
         let page_id_3 = {
-            let page = manager.next_free_page();
+            let page = manager.next_free_page().unwrap();
             let page = page.read().unwrap();
             page.page_id
         };
 
-        assert_eq!(manager.empty_pages.len(), 0);
-        assert_eq!(manager.pages.len(), 2);
+        assert_eq!(manager.free_page_count(), 0);
+        assert_eq!(manager.pages.read().unwrap().len(), 2);
 
         // Ensure that pages 1 and 2 are the most recently used
         {
-            let _page_1 = manager.find_page(page_id_1);
-            let _page_2 = manager.find_page(page_id_2);
+            let _page_1 = manager.find_page(page_id_1).unwrap();
+            let _page_2 = manager.find_page(page_id_2).unwrap();
         }
 
-        let (id, _) = manager.usage_tracker.last_used.peek().unwrap();
+        let id = &manager.usage_tracker.lock().unwrap().evict_candidates(1)[0];
         assert_eq!(*id, page_id_1);
 
         {
-            let _page = manager.find_page(page_id_3);
+            let _page = manager.find_page(page_id_3).unwrap();
         }
 
-        let (id, _) = manager.usage_tracker.last_used.peek().unwrap();
+        let id = &manager.usage_tracker.lock().unwrap().evict_candidates(1)[0];
         assert_eq!(*id, page_id_2);
 
         cleanup(base_dir);
@@ -274,39 +1125,1174 @@ mod page_manager_tests {
         manager.add_empty_pages("empty.db", 3);
 
         let page_id_1 = {
-            let page = manager.next_free_page();
+            let page = manager.next_free_page().unwrap();
             let page = page.read().unwrap();
             page.page_id
         };
 
         let page_id_2 = {
-            let page = manager.next_free_page();
+            let page = manager.next_free_page().unwrap();
             let page = page.read().unwrap();
             page.page_id
         };
 
         let page_id_3 = {
-            let page = manager.next_free_page();
+            let page = manager.next_free_page().unwrap();
             let page = page.read().unwrap();
             page.page_id
         };
 
         // Hold on to the reference to page_1
-        let _page_1 = manager.find_page(page_id_1);
+        let _page_1 = manager.find_page(page_id_1).unwrap();
         {
-            let _page_2 = manager.find_page(page_id_2);
+            let _page_2 = manager.find_page(page_id_2).unwrap();
         }
 
-        let (id, _) = manager.usage_tracker.last_used.peek().unwrap();
+        let id = &manager.usage_tracker.lock().unwrap().evict_candidates(1)[0];
         assert_eq!(*id, page_id_1);
 
         {
-            let _page = manager.find_page(page_id_3);
+            let _page = manager.find_page(page_id_3).unwrap();
         }
 
-        let (id, _) = manager.usage_tracker.last_used.peek().unwrap();
+        let id = &manager.usage_tracker.lock().unwrap().evict_candidates(1)[0];
         assert_eq!(*id, page_id_1);
 
         cleanup(base_dir);
     }
+
+    #[test]
+    pub fn load_corrupt_page_fails() {
+        let base_dir = "./test5";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::new(1, base_dir);
+        manager.add_empty_pages("empty.db", 2);
+
+        let page_id_1 = {
+            let page = manager.next_free_page().unwrap();
+            let mut page = page.write().unwrap();
+            let page_id = page.page_id;
+            page.write_header(data_page_header(page_id));
+            page.data[MAGIC_NUMBER_START] ^= 0xFF;
+            page_id
+        };
+
+        // Evicts the corrupted page to disk
+        {
+            let _page = manager.next_free_page().unwrap();
+        }
+
+        assert!(matches!(
+            manager.load_page(page_id_1),
+            Err(YarddError::Corruption(id)) if id == page_id_1
+        ));
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn evict_n_persists_victims() {
+        let base_dir = "./test9";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::new(4, base_dir);
+        manager.add_empty_pages("empty.db", 4);
+
+        let mut page_ids = vec![];
+        for i in 0..4 {
+            let page = manager.next_free_page().unwrap();
+            let mut page = page.write().unwrap();
+            let page_id = page.page_id;
+            page.write_header(data_page_header(page_id));
+            page.data[HEADER_SIZE..].fill(i + 1);
+            page_ids.push(page_id);
+        }
+
+        // Hold a reference to one page so it can't be evicted
+        let pinned = manager.find_page(page_ids[0]).unwrap();
+
+        assert_eq!(3, manager.evict_n(5).unwrap());
+        assert_eq!(1, manager.pages.read().unwrap().len());
+        assert_eq!(1, manager.usage_tracker.lock().unwrap().len());
+        drop(pinned);
+
+        for (i, page_id) in page_ids.iter().enumerate() {
+            let page = manager.find_page(*page_id).unwrap();
+            let page = page.read().unwrap();
+            for b in page.data[HEADER_SIZE..].iter() {
+                assert_eq!(*b, i as u8 + 1);
+            }
+        }
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn prefetch_pages_are_resident() {
+        let base_dir = "./test10";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::new(4, base_dir);
+        manager.add_empty_pages("empty.db", 4);
+
+        let page_ids: Vec<_> = (0..4)
+            .map(|_| manager.next_free_page().unwrap().read().unwrap().page_id)
+            .collect();
+
+        assert_eq!(4, manager.evict_n(4).unwrap());
+        assert!(manager.pages.read().unwrap().is_empty());
+
+        {
+            let _page = manager.find_page(page_ids[0]).unwrap();
+        }
+        manager.prefetch(&page_ids[1..3]).unwrap();
+
+        assert_eq!(3, manager.pages.read().unwrap().len());
+        assert!(manager.pages.read().unwrap().contains_key(&page_ids[1]));
+        assert!(manager.pages.read().unwrap().contains_key(&page_ids[2]));
+
+        // Prefetched pages are colder than the page that was actually used
+        let id = &manager.usage_tracker.lock().unwrap().evict_candidates(1)[0];
+        assert_ne!(*id, page_ids[0]);
+
+        let resident = manager
+            .pages
+            .read()
+            .unwrap()
+            .get(&page_ids[1])
+            .unwrap()
+            .clone();
+        let found = manager.find_page(page_ids[1]).unwrap();
+        assert!(Arc::ptr_eq(&resident, &found));
+        assert_eq!(3, manager.pages.read().unwrap().len());
+
+        cleanup(base_dir);
+    }
+
+    fn read_first_page(base_dir: &str) -> Vec<u8> {
+        let data = read(Path::new(base_dir).join("empty.db")).unwrap();
+        data[..PAGE_SIZE_BYTES as usize].to_vec()
+    }
+
+    #[test]
+    pub fn close_flushes_dirty_pages() {
+        let base_dir = "./test11";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::new(4, base_dir);
+        manager.add_empty_pages("empty.db", 4);
+
+        let page = manager.find_page(0).unwrap();
+        {
+            let mut page = page.write().unwrap();
+            page.write_header(data_page_header(0));
+            page.data[HEADER_SIZE..].fill(42);
+        }
+
+        manager.close().unwrap();
+        assert!(!page.read().unwrap().is_dirty);
+
+        let data = read_first_page(base_dir);
+        assert_eq!(page.read().unwrap().data.to_vec(), data);
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn drop_flushes_dirty_pages() {
+        let base_dir = "./test12";
+        setup_test_dir(base_dir);
+
+        {
+            let mut manager = PageManager::new(4, base_dir);
+            manager.add_empty_pages("empty.db", 4);
+
+            let page = manager.find_page(0).unwrap();
+            let mut page = page.write().unwrap();
+            page.write_header(data_page_header(0));
+            page.data[HEADER_SIZE..].fill(43);
+        }
+
+        let data = read_first_page(base_dir);
+        assert!(data[HEADER_SIZE..].iter().all(|b| *b == 43));
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn flush_skips_latched_pages() {
+        let base_dir = "./test76";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::new(4, base_dir);
+        manager.add_empty_pages("empty.db", 4);
+
+        let latched = manager.find_page(0).unwrap();
+        let mut guard = latched.write().unwrap();
+        guard.write_header(data_page_header(0));
+        guard.is_dirty = true;
+
+        let page = manager.find_page(1).unwrap();
+        {
+            let mut page = page.write().unwrap();
+            page.write_header(data_page_header(1));
+            page.is_dirty = true;
+        }
+
+        manager.flush_all().unwrap();
+        assert!(!page.read().unwrap().is_dirty);
+        assert!(guard.is_dirty);
+
+        // Dropping the manager while a page is latched doesn't wait on it
+        drop(manager);
+        drop(guard);
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn eviction_skips_pinned_candidates() {
+        let base_dir = "./test13";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::new(4, base_dir);
+        manager.add_empty_pages("empty.db", 4);
+
+        let page_ids: Vec<_> = (0..4)
+            .map(|_| manager.next_free_page().unwrap().read().unwrap().page_id)
+            .collect();
+
+        // Establish a known LRU order
+        for page_id in page_ids.iter() {
+            let _page = manager.find_page(*page_id).unwrap();
+            sleep(Duration::from_millis(2));
+        }
+
+        let _pinned_1 = manager.find_page(page_ids[0]).unwrap();
+        sleep(Duration::from_millis(2));
+        let _pinned_2 = manager.find_page(page_ids[2]).unwrap();
+
+        assert_eq!(
+            vec![page_ids[1], page_ids[3], page_ids[0], page_ids[2]],
+            manager.usage_tracker.lock().unwrap().evict_candidates(4)
+        );
+
+        let victims = manager.eviction_victims(
+            &manager.pool.lock().unwrap(),
+            &manager.pages.read().unwrap(),
+            4,
+        );
+        assert_eq!(vec![page_ids[1], page_ids[3]], victims);
+
+        assert_eq!(1, manager.evict_n(1).unwrap());
+        assert!(!manager.pages.read().unwrap().contains_key(&page_ids[1]));
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn reloading_pages_reuses_arena_buffers() {
+        let base_dir = "./test20";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::new(4, base_dir);
+        manager.add_empty_pages("empty.db", 12);
+
+        let page_ids: Vec<_> = (0..12)
+            .map(|_| manager.next_free_page().unwrap().read().unwrap().page_id)
+            .collect();
+
+        // Cycle through three times as many pages as fit in the pool
+        for _ in 0..3 {
+            for page_id in page_ids.iter() {
+                manager.find_page(*page_id).unwrap();
+            }
+        }
+
+        // Without the arena every load would allocate a fresh buffer, here the
+        // block for the whole pool is the only allocation
+        assert_eq!(1, manager.pool.lock().unwrap().arena.allocations());
+        assert_eq!(0, manager.pool.lock().unwrap().arena.available());
+        for page_id in page_ids[8..].iter() {
+            let page = manager.find_page(*page_id).unwrap();
+            assert!(page.read().unwrap().data.is_in_arena());
+        }
+
+        assert_eq!(4, manager.evict_n(4).unwrap());
+        assert_eq!(4, manager.pool.lock().unwrap().arena.available());
+        assert_eq!(1, manager.pool.lock().unwrap().arena.allocations());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn recycled_page_is_reset() {
+        let base_dir = "./test21";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::new(4, base_dir);
+        manager.add_empty_pages("empty.db", 4);
+
+        let page_id = {
+            let page = manager.next_free_page().unwrap();
+            let mut page = page.write().unwrap();
+            let mut node = IndexPageMut::<u64>::init_page(PageType::IndexLeaf, 0, &mut page);
+            node.append_key(KeyEntry::leaf(5, 1, 2)).unwrap();
+            page.page_id
+        };
+
+        manager.release_page(page_id);
+
+        let page = manager.next_free_page().unwrap();
+        let page = page.read().unwrap();
+        assert_eq!(page_id, page.page_id);
+        assert!(
+            page.data[SLOTS_HEADER_START..SLOTS_HEADER_START + SLOTS_HEADER_SIZE]
+                .iter()
+                .all(|b| *b == 0)
+        );
+        assert!(page.data.iter().all(|b| *b == 0));
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn free_pages_come_from_resident_first() {
+        let base_dir = "./test22";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::new(2, base_dir);
+        manager.add_empty_pages("empty.db", 4);
+
+        assert_eq!(2, manager.pool.lock().unwrap().free_resident.len());
+        assert_eq!(2, manager.pool.lock().unwrap().unallocated.len());
+
+        let resident: Vec<_> = manager.pages.read().unwrap().keys().copied().collect();
+        for _ in 0..2 {
+            let page_id = manager.next_free_page().unwrap().read().unwrap().page_id;
+            assert!(resident.contains(&page_id));
+        }
+        assert_eq!(0, manager.pool.lock().unwrap().free_resident.len());
+        assert_eq!(2, manager.pool.lock().unwrap().unallocated.len());
+
+        // Taking an on-disk page evicts one of the resident ones
+        let page_id = manager.next_free_page().unwrap().read().unwrap().page_id;
+        assert!(!resident.contains(&page_id));
+        assert_eq!(1, manager.pool.lock().unwrap().unallocated.len());
+
+        manager.release_page(page_id);
+        assert_eq!(1, manager.pool.lock().unwrap().free_resident.len());
+
+        // Evicting a free page moves it to the on-disk list
+        assert_eq!(2, manager.evict_n(2).unwrap());
+        assert_eq!(0, manager.pool.lock().unwrap().free_resident.len());
+        assert_eq!(2, manager.pool.lock().unwrap().unallocated.len());
+        assert_eq!(2, manager.free_page_count());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn resident_pages_in_page_id_order() {
+        let base_dir = "./test23";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::new(8, base_dir);
+        manager.add_empty_pages("a.db", 3);
+        manager.add_empty_pages("b.db", 3);
+
+        let last_used_before: Vec<_> = manager.usage_tracker.lock().unwrap().evict_candidates(6);
+
+        let resident: Vec<_> = manager.resident_pages().collect();
+        assert_eq!(6, resident.len());
+
+        let page_ids: Vec<_> = resident.iter().map(|(page_id, _)| *page_id).collect();
+        let mut sorted = page_ids.clone();
+        sorted.sort();
+        assert_eq!(sorted, page_ids);
+
+        for (page_id, page) in resident {
+            assert_eq!(page_id, page.read().unwrap().page_id);
+        }
+
+        assert_eq!(
+            last_used_before,
+            manager.usage_tracker.lock().unwrap().evict_candidates(6)
+        );
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn is_dirty_tracks_flushes() {
+        let base_dir = "./test24";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::new(2, base_dir);
+        manager.add_empty_pages("empty.db", 3);
+
+        let page_id = {
+            let page = manager.next_free_page().unwrap();
+            let page = page.read().unwrap();
+            page.page_id
+        };
+        assert_eq!(Some(false), manager.is_dirty(page_id));
+
+        manager
+            .find_page(page_id)
+            .unwrap()
+            .write()
+            .unwrap()
+            .write_header(data_page_header(page_id));
+        assert_eq!(Some(true), manager.is_dirty(page_id));
+
+        manager.flush_all().unwrap();
+        assert_eq!(Some(false), manager.is_dirty(page_id));
+
+        let unallocated = manager.pool.lock().unwrap().unallocated[0];
+        assert_eq!(None, manager.is_dirty(unallocated));
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn catalog_roots_persisted() {
+        let base_dir = "./test32";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::new(2, base_dir);
+        manager.add_empty_pages("empty.db", 2);
+
+        manager.set_root("orders", 5).unwrap();
+        manager.set_root("customers", 9).unwrap();
+        manager.set_root("orders", 7).unwrap();
+
+        assert_eq!(Some(7), manager.get_root("orders"));
+        assert_eq!(Some(9), manager.get_root("customers"));
+        assert_eq!(None, manager.get_root("missing"));
+
+        // Push the catalog page out to disk and read it back
+        let catalog_page_id = manager.catalog_page_id.unwrap();
+        manager.flush_all().unwrap();
+        manager.evict_n(2).unwrap();
+        assert!(!manager.pages.read().unwrap().contains_key(&catalog_page_id));
+
+        let page = manager.find_page(catalog_page_id).unwrap();
+        let roots = catalog::read_roots(&page.read().unwrap());
+        assert_eq!(manager.index_roots, roots);
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn read_only_manager_refuses_to_write() {
+        let base_dir = "./test36";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::new(4, base_dir);
+        manager.add_empty_pages("empty.db", 4);
+        for page_id in [0, 1] {
+            let page = manager.find_page(page_id).unwrap();
+            let mut page = page.write().unwrap();
+            page.write_header(data_page_header(page_id));
+            page.data[HEADER_SIZE..].fill(42);
+        }
+        manager.close().unwrap();
+        let before = read_first_page(base_dir);
+
+        let mut manager = PageManager::open_read_only(4, base_dir).unwrap();
+        assert!(manager.is_read_only());
+
+        {
+            let page = manager.find_page(0).unwrap();
+            let mut page = page.write().unwrap();
+            assert_eq!(42, page.data[HEADER_SIZE]);
+
+            page.data[HEADER_SIZE] = 7;
+            page.is_dirty = true;
+        }
+
+        assert_eq!(Err(YarddError::ReadOnly), manager.evict_n(1));
+        assert!(manager.flush_all().is_err());
+        assert_eq!(before, read_first_page(base_dir));
+
+        // Clean pages can still be dropped from the pool
+        manager.find_page(1).unwrap();
+        manager.find_page(0).unwrap().write().unwrap().is_dirty = false;
+        assert_eq!(2, manager.evict_n(2).unwrap());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn write_through_persists_on_release() {
+        let base_dir = "./test39";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::with_sync_policy(4, base_dir, SyncPolicy::WriteThrough);
+        manager.add_empty_pages("empty.db", 4);
+        let on_disk = |manager: &PageManager| {
+            let pool = manager.pool.lock().unwrap();
+            pool.disk_manager.load_page(0).unwrap()
+        };
+
+        let page = manager.find_page(0).unwrap();
+        let mut guard = page.write_guard();
+        guard.write_header(data_page_header(0));
+        guard.data[HEADER_SIZE..].fill(44);
+        assert_eq!(0, on_disk(&manager)[HEADER_SIZE]);
+
+        drop(guard);
+        assert!(!page.read().unwrap().is_dirty);
+        assert_eq!(page.read().unwrap().data.to_vec(), on_disk(&manager));
+
+        // Pages read back from disk write through as well
+        drop(page);
+        assert_eq!(4, manager.evict_n(4).unwrap());
+        let page = manager.find_page(0).unwrap();
+        page.write_guard().data[HEADER_SIZE] = 45;
+        assert_eq!(45, on_disk(&manager)[HEADER_SIZE]);
+
+        // A plain latch isn't seen, so the change waits to be written back
+        {
+            let mut page = page.write().unwrap();
+            page.data[HEADER_SIZE] = 46;
+            page.is_dirty = true;
+        }
+        assert_eq!(45, on_disk(&manager)[HEADER_SIZE]);
+        assert_eq!(Some(true), manager.is_dirty(0));
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn write_back_waits_for_flush() {
+        let base_dir = "./test40";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::new(4, base_dir);
+        manager.add_empty_pages("empty.db", 4);
+
+        {
+            let page = manager.find_page(0).unwrap();
+            let mut page = page.write().unwrap();
+            page.write_header(data_page_header(0));
+            page.data[HEADER_SIZE..].fill(44);
+        }
+        manager.find_page(1).unwrap();
+
+        assert_eq!(Some(true), manager.is_dirty(0));
+        assert_eq!(0, read_first_page(base_dir)[HEADER_SIZE]);
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn eviction_skips_pages_ahead_of_the_log() {
+        let base_dir = "./test43";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::new(3, base_dir);
+        manager.add_empty_pages("empty.db", 6);
+        manager.set_flushed_lsn(10);
+
+        for page_id in 0..3 {
+            let page = manager.find_page(page_id).unwrap();
+            let mut page = page.write().unwrap();
+            page.write_header(data_page_header(page_id));
+            page.write_lsn(if page_id == 0 { 11 } else { 10 });
+            sleep(Duration::from_millis(2));
+        }
+
+        // Page 0 is least recently used, but its latest change isn't in the log yet
+        assert_eq!(1, manager.evict_n(1).unwrap());
+        assert!(manager.pages.read().unwrap().contains_key(&0));
+        assert!(!manager.pages.read().unwrap().contains_key(&1));
+
+        manager.set_flushed_lsn(11);
+        assert_eq!(1, manager.evict_n(1).unwrap());
+        assert!(!manager.pages.read().unwrap().contains_key(&0));
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn lfu_evicts_rarely_used_page() {
+        let base_dir = "./test45";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::new(3, base_dir);
+        manager.set_replacement_policy(Box::new(LfuPolicy::new(1000)));
+        manager.add_empty_pages("empty.db", 4);
+
+        for _ in 0..10 {
+            manager.find_page(0).unwrap();
+        }
+        manager.find_page(1).unwrap();
+        for _ in 0..5 {
+            manager.find_page(2).unwrap();
+        }
+
+        // Page 0 was used first but most often
+        assert_eq!(1, manager.evict_n(1).unwrap());
+        assert!(manager.pages.read().unwrap().contains_key(&0));
+        assert!(!manager.pages.read().unwrap().contains_key(&1));
+        assert!(manager.pages.read().unwrap().contains_key(&2));
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn free_lists_do_not_grow_under_churn() {
+        let base_dir = "./test46";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::new(4, base_dir);
+        assert!(manager.pool.lock().unwrap().free_resident.capacity() >= 4);
+
+        manager.add_empty_pages("empty.db", 12);
+        let free_resident_capacity = manager.pool.lock().unwrap().free_resident.capacity();
+        let unallocated_capacity = manager.pool.lock().unwrap().unallocated.capacity();
+        let arena_allocations = manager.pool.lock().unwrap().arena.allocations();
+
+        // Hand out every page, cycling them through the pool, then free them all
+        let page_ids: Vec<PageId> = (0..12)
+            .map(|_| {
+                let page = manager.next_free_page().unwrap();
+                let mut page = page.write().unwrap();
+                let page_id = page.page_id;
+                page.write_header(data_page_header(page_id));
+                page_id
+            })
+            .collect();
+        for page_id in page_ids.iter() {
+            manager.find_page(*page_id).unwrap();
+        }
+        for page_id in page_ids {
+            manager.release_page(page_id);
+        }
+        assert_eq!(12, manager.free_page_count());
+
+        assert_eq!(
+            free_resident_capacity,
+            manager.pool.lock().unwrap().free_resident.capacity()
+        );
+        assert_eq!(
+            unallocated_capacity,
+            manager.pool.lock().unwrap().unallocated.capacity()
+        );
+        assert_eq!(
+            arena_allocations,
+            manager.pool.lock().unwrap().arena.allocations()
+        );
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn free_space_hints_rank_data_pages() {
+        let base_dir = "./test47";
+        setup_test_dir(base_dir);
+
+        let tuple_counts = [3, 10, 0, 6];
+        let page_ids: Vec<PageId> = {
+            let mut manager = PageManager::new(4, base_dir);
+            manager.add_empty_pages("empty.db", 4);
+
+            let page_ids: Vec<PageId> = tuple_counts
+                .iter()
+                .map(|n_tuples| {
+                    let page = manager.next_free_page().unwrap();
+                    let mut page = page.write().unwrap();
+                    let mut data_page = DataPageMut::init_page(&mut page);
+                    for _ in 0..*n_tuples {
+                        data_page.append_tuple(&[1; 64]).unwrap();
+                    }
+                    page.page_id
+                })
+                .collect();
+
+            // Hints are picked up when the pages are written
+            assert_eq!(None, manager.free_space_hint(page_ids[0]));
+            manager.flush_all().unwrap();
+
+            let ranked: Vec<PageId> = manager
+                .pages_by_free_space()
+                .map(|(page_id, _)| page_id)
+                .collect();
+            assert_eq!(
+                vec![page_ids[2], page_ids[0], page_ids[3], page_ids[1]],
+                ranked
+            );
+
+            let emptiest = manager.free_space_hint(page_ids[2]).unwrap();
+            assert_eq!(Some(page_ids[2]), manager.page_with_free_space(emptiest));
+            assert_eq!(None, manager.page_with_free_space(emptiest + 1));
+
+            manager.release_page(page_ids[2]);
+            assert_eq!(Some(page_ids[0]), manager.page_with_free_space(0));
+
+            page_ids
+        };
+
+        // A new manager rebuilds the hints as it loads pages
+        let manager = PageManager::open_read_only(4, base_dir).unwrap();
+        for page_id in page_ids.iter() {
+            manager.find_page(*page_id).unwrap();
+        }
+        let ranked: Vec<PageId> = manager
+            .pages_by_free_space()
+            .map(|(page_id, _)| page_id)
+            .collect();
+        assert_eq!(
+            vec![page_ids[2], page_ids[0], page_ids[3], page_ids[1]],
+            ranked
+        );
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn miss_on_full_pool_evicts_to_low_watermark() {
+        let base_dir = "./test49";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::new(10, base_dir);
+        manager.set_low_watermark(7);
+        manager.add_empty_pages("empty.db", 12);
+        assert_eq!(10, manager.pages.read().unwrap().len());
+
+        // One miss evicts three clean pages, then loads the missing one
+        manager.find_page(11).unwrap();
+        assert_eq!(8, manager.pages.read().unwrap().len());
+        assert!(manager.pages.read().unwrap().contains_key(&11));
+
+        // Further misses have room and evict nothing
+        manager.find_page(10).unwrap();
+        assert_eq!(9, manager.pages.read().unwrap().len());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn in_memory_manager_never_touches_disk() {
+        let mut manager = PageManager::in_memory(4);
+        assert!(manager.is_in_memory());
+        manager.add_empty_pages("memory.db", 8);
+        assert_eq!(8, manager.free_page_count());
+
+        let page_ids: Vec<PageId> = (0..3)
+            .map(|i| {
+                let page = manager.next_free_page().unwrap();
+                let mut page = page.write().unwrap();
+                let page_id = page.page_id;
+                page.write_header(data_page_header(page_id));
+                page.write_lsn(i);
+                page_id
+            })
+            .collect();
+
+        // Flushing has nowhere to write, so the pages stay dirty and resident
+        manager.flush_all().unwrap();
+        assert_eq!(Some(true), manager.is_dirty(page_ids[0]));
+
+        // Only the one clean page can make room for misses
+        assert_eq!(1, manager.evict_n(4).unwrap());
+        let page = manager.find_page(7).unwrap();
+        assert!(page.read().unwrap().data.iter().all(|b| *b == 0));
+        drop(page);
+
+        for (i, page_id) in page_ids.iter().enumerate() {
+            let page = manager.find_page(*page_id).unwrap();
+            let page = page.read().unwrap();
+            assert_eq!(PageType::DataPage, page.read_page_type());
+            assert_eq!(i as u64, page.read_lsn());
+        }
+
+        assert!(!Path::new("memory.db").exists());
+    }
+
+    #[test]
+    pub fn builder_applies_settings() {
+        let base_dir = "./test58";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::builder(10)
+            .base_directory(base_dir)
+            .sync_policy(SyncPolicy::WriteThrough)
+            .replacement_policy(Box::new(LfuPolicy::new(1000)))
+            .low_watermark(7)
+            .double_write(true)
+            .build()
+            .unwrap();
+
+        assert_eq!(SyncPolicy::WriteThrough, manager.sync_policy);
+        assert_eq!(7, manager.low_watermark);
+        assert!(!manager.is_read_only());
+
+        manager.add_empty_pages("empty.db", 12);
+        let page = manager.next_free_page().unwrap();
+        let page_id = {
+            let mut page = page.write_guard();
+            let page_id = page.page_id;
+            page.write_header(data_page_header(page_id));
+            page_id
+        };
+        drop(page);
+
+        // Releasing the guard saved the page, through the double write buffer
+        assert_eq!(Some(false), manager.is_dirty(page_id));
+        assert!(Path::new(base_dir).join("double_write.buf").exists());
+
+        // A miss on the full pool evicts down to the watermark
+        manager.find_page(11).unwrap();
+        assert_eq!(8, manager.pages.read().unwrap().len());
+
+        let in_memory = PageManager::builder(4).in_memory().build().unwrap();
+        assert!(in_memory.is_in_memory());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn builder_rejects_conflicting_settings() {
+        let invalid = |builder: super::PageManagerBuilder| {
+            let error = builder
+                .build()
+                .err()
+                .expect("Expected an invalid configuration");
+            assert!(error.downcast_ref::<InvalidConfig>().is_some(), "{}", error);
+        };
+
+        invalid(PageManager::builder(0).in_memory());
+        invalid(PageManager::builder(4));
+        invalid(
+            PageManager::builder(4)
+                .in_memory()
+                .base_directory("./unused"),
+        );
+        invalid(PageManager::builder(4).in_memory().read_only());
+        invalid(
+            PageManager::builder(4)
+                .base_directory("./unused")
+                .read_only()
+                .sync_policy(SyncPolicy::WriteThrough),
+        );
+        invalid(PageManager::builder(4).in_memory().double_write(true));
+        invalid(PageManager::builder(4).in_memory().low_watermark(4));
+
+        assert!(!Path::new("./unused").exists());
+    }
+
+    #[test]
+    pub fn checksum_restamped_when_written_back() {
+        let base_dir = "./test66";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::new(2, base_dir);
+        manager.add_empty_pages("pages.db", 4);
+
+        let mut page_ids = vec![];
+        for _ in 0..2 {
+            let page = manager.next_free_page().unwrap();
+            let mut page = page.write().unwrap();
+            let page_id = page.page_id;
+            page.write_header(data_page_header(page_id));
+            page_ids.push(page_id);
+        }
+        manager.flush_all().unwrap();
+
+        // Change both pages behind the pool's back, without restamping them
+        // or marking them dirty
+        for page_id in &page_ids {
+            let page = manager.find_page(*page_id).unwrap();
+            let mut page = page.write().unwrap();
+            page.data[HEADER_SIZE] = 42;
+            page.is_dirty = false;
+        }
+
+        // Written back one at a time and then as a batch
+        manager.prefetch(&[page_ids[0] + 2]).unwrap();
+        manager.evict_n(2).unwrap();
+
+        for page_id in &page_ids {
+            let page = manager.find_page(*page_id).unwrap();
+            let page = page.read().unwrap();
+            assert_eq!(42, page.data[HEADER_SIZE]);
+            assert_eq!(Ok(()), page.verify_checksum());
+        }
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn page_manager_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<PageManager>();
+        assert_send_sync::<SharedPageManager>();
+    }
+
+    #[test]
+    pub fn concurrent_fetches_through_shared_manager() {
+        let base_dir = "./test67";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::new(8, base_dir);
+        manager.add_empty_pages("shared.db", 32);
+
+        let page_ids: Vec<PageId> = (0..32)
+            .map(|_| {
+                let page = manager.next_free_page().unwrap();
+                let mut page = page.write().unwrap();
+                let page_id = page.page_id;
+                page.write_header(data_page_header(page_id));
+                page.write_lsn(page_id);
+                page_id
+            })
+            .collect();
+
+        // Four times as many pages as fit, so threads miss and evict under each other
+        let manager = &manager;
+        thread::scope(|scope| {
+            for chunk in page_ids.chunks(8) {
+                scope.spawn(move || {
+                    for _ in 0..20 {
+                        for page_id in chunk {
+                            let page = manager.find_page(*page_id).unwrap();
+                            let page = page.read().unwrap();
+                            assert_eq!(*page_id, page.page_id);
+                            assert_eq!(*page_id, page.read_lsn());
+                        }
+                    }
+                });
+            }
+        });
+
+        assert!(manager.resident_pages().count() <= 8);
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn debug_dump_reports_pinned_pages() {
+        let mut manager = PageManager::in_memory(4);
+        manager.add_empty_pages("memory.db", 3);
+
+        let pinned = manager.find_page(1).unwrap();
+        let latched = manager.find_page(2).unwrap();
+        let latched = latched.write().unwrap();
+
+        let dump = manager.debug_dump();
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(
+            vec![
+                "3 of 4 pages resident",
+                "page 0: 1 refs, clean",
+                "page 1: 2 refs, clean",
+                "page 2: 2 refs, write latched",
+            ],
+            lines
+        );
+
+        drop(latched);
+        pinned.write().unwrap().is_dirty = true;
+        assert!(manager.debug_dump().contains("page 1: 2 refs, dirty"));
+
+        drop(pinned);
+        assert!(manager.debug_dump().contains("page 1: 1 refs, dirty"));
+    }
+
+    #[test]
+    pub fn find_page_reports_missing_file() {
+        let base_dir = "./test68";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::new(2, base_dir);
+        manager.add_empty_pages("gone.db", 2);
+        let page_id = manager.next_free_page().unwrap().read().unwrap().page_id;
+        assert_eq!(2, manager.evict_n(2).unwrap());
+
+        std::fs::remove_file(Path::new(base_dir).join("gone.db")).unwrap();
+
+        match manager.find_page(page_id) {
+            Err(YarddError::Io(error)) => assert_eq!(std::io::ErrorKind::NotFound, error.kind()),
+            other => panic!("Expected a missing file error, got {:?}", other.map(|_| ())),
+        }
+        assert!(manager.pages.read().unwrap().is_empty());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn next_free_page_in_stays_in_file() {
+        let mut manager = PageManager::in_memory(8);
+        manager.add_empty_pages("orders.db", 2);
+        manager.add_empty_pages("users.db", 2);
+
+        let orders = manager.pages_in_file("orders.db");
+        let users = manager.pages_in_file("users.db");
+
+        for _ in 0..2 {
+            let page_id = manager
+                .next_free_page_in("orders.db")
+                .unwrap()
+                .read()
+                .unwrap()
+                .page_id;
+            assert!(orders.contains(&page_id));
+        }
+
+        // orders.db is used up, so it grows rather than taking a users.db page
+        let page_id = manager
+            .next_free_page_in("orders.db")
+            .unwrap()
+            .read()
+            .unwrap()
+            .page_id;
+        assert!(!orders.contains(&page_id) && !users.contains(&page_id));
+        assert_eq!(3, manager.pages_in_file("orders.db").len());
+        assert_eq!(2, manager.free_page_count());
+
+        let page_id = manager
+            .next_free_page_in("users.db")
+            .unwrap()
+            .read()
+            .unwrap()
+            .page_id;
+        assert!(users.contains(&page_id));
+
+        // Released pages go back to their own file's pool
+        manager.release_page(orders[0]);
+        assert_eq!(
+            orders[0],
+            manager
+                .next_free_page_in("orders.db")
+                .unwrap()
+                .read()
+                .unwrap()
+                .page_id
+        );
+    }
+
+    #[test]
+    pub fn truncate_file_shrinks_past_free_trailing_pages() {
+        let base_dir = "./test71";
+        setup_test_dir(base_dir);
+        let file_len = || read(format!("{base_dir}/table.db")).unwrap().len();
+
+        let mut manager = PageManager::new(3, base_dir);
+        manager.add_empty_pages("table.db", 6);
+        let page_ids = manager.pages_in_file("table.db");
+        let full_len = file_len();
+
+        // Only the second page stays in use
+        for _ in 0..6 {
+            manager.next_free_page_in("table.db").unwrap();
+        }
+        for page_id in [
+            page_ids[0],
+            page_ids[2],
+            page_ids[3],
+            page_ids[4],
+            page_ids[5],
+        ] {
+            manager.release_page(page_id);
+        }
+
+        // Still held, so it stays along with every page before it
+        let held = manager.find_page(page_ids[5]).unwrap();
+        assert_eq!(0, manager.truncate_file("table.db").unwrap());
+        drop(held);
+
+        assert_eq!(4, manager.truncate_file("table.db").unwrap());
+        assert_eq!(&page_ids[..2], manager.pages_in_file("table.db").as_slice());
+        assert_eq!(full_len - 4 * PAGE_SIZE_BYTES as usize, file_len());
+        assert_eq!(1, manager.free_page_count());
+
+        // The page in use and the free one before it still work
+        manager.find_page(page_ids[1]).unwrap();
+        assert_eq!(
+            page_ids[0],
+            manager
+                .next_free_page_in("table.db")
+                .unwrap()
+                .read()
+                .unwrap()
+                .page_id
+        );
+
+        // Without files only the pages are forgotten
+        let mut manager = PageManager::in_memory(3);
+        manager.add_empty_pages("table.db", 3);
+        let page_ids = manager.pages_in_file("table.db");
+        for _ in 0..3 {
+            manager.next_free_page_in("table.db").unwrap();
+        }
+        manager.release_page(page_ids[1]);
+        manager.release_page(page_ids[2]);
+
+        assert_eq!(2, manager.truncate_file("table.db").unwrap());
+        assert_eq!(&page_ids[..1], manager.pages_in_file("table.db").as_slice());
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn catalog_roots_survive_reopening() {
+        let base_dir = "./test72";
+        setup_test_dir(base_dir);
+
+        let mut manager = PageManager::new(4, base_dir);
+        manager.add_empty_pages("index.db", 2);
+        manager.set_root("orders", 1).unwrap();
+        let catalog_page_id = manager.catalog_page_id.unwrap();
+        manager.close().unwrap();
+
+        let mut manager = PageManager::open(4, base_dir).unwrap();
+        assert_eq!(Some(1), manager.get_root("orders"));
+
+        // The catalog page is reused rather than allocated again
+        manager.set_root("customers", 0).unwrap();
+        assert_eq!(Some(catalog_page_id), manager.catalog_page_id);
+        assert_eq!(1, manager.pages_in_file(catalog::CATALOG_FILE).len());
+        drop(manager);
+
+        let manager = PageManager::open(4, base_dir).unwrap();
+        assert_eq!(Some(1), manager.get_root("orders"));
+        assert_eq!(Some(0), manager.get_root("customers"));
+
+        let manager = PageManager::open_read_only(4, base_dir).unwrap();
+        assert_eq!(Some(0), manager.get_root("customers"));
+
+        cleanup(base_dir);
+    }
+
+    #[test]
+    pub fn pinned_pool_reports_exhaustion() {
+        let mut manager = PageManager::in_memory(2);
+        manager.add_empty_pages("memory.db", 3);
+
+        let pinned: Vec<_> = (0..2).map(|_| manager.next_free_page().unwrap()).collect();
+        let unloaded = manager.pool.lock().unwrap().unallocated[0];
+
+        assert_eq!(
+            Some(YarddError::PoolExhausted),
+            manager.find_page(unloaded).err()
+        );
+        assert_eq!(
+            Some(YarddError::PoolExhausted),
+            manager.next_free_page().err()
+        );
+
+        // Once a page is let go, the free page can be loaded in its place
+        drop(pinned);
+        let third = manager.next_free_page().unwrap();
+        assert_eq!(
+            Some(YarddError::PoolExhausted),
+            manager.next_free_page().err()
+        );
+        drop(third);
+    }
 }
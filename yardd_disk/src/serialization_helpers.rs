@@ -1,4 +1,4 @@
-use std::{io::Write, mem::size_of};
+use std::mem::size_of;
 
 pub fn read_i16(v: &[u8], start: usize) -> i16 {
     i16::from_be_bytes([v[start], v[start + 1]])
@@ -25,10 +25,25 @@ pub fn read_u64(v: &[u8], start: usize) -> u64 {
     ])
 }
 
+// CRC-32 (IEEE) over the chunks as if they were one contiguous buffer
+pub fn crc32(chunks: &[&[u8]]) -> u32 {
+    let mut crc = 0xFFFFFFFF_u32;
+
+    for byte in chunks.iter().flat_map(|chunk| chunk.iter()) {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB88320 & mask);
+        }
+    }
+
+    !crc
+}
+
 pub fn write_bytes(v: &mut [u8], start: usize, bytes: &[u8]) -> usize {
-    let mut range = &mut v[start..];
-    range.write_all(bytes);
-    start + bytes.len()
+    let end = start + bytes.len();
+    v[start..end].copy_from_slice(bytes);
+    end
 }
 
 pub fn write_u16(v: &mut [u8], start: usize, n: u16) -> usize {
@@ -40,20 +55,14 @@ pub fn write_u16(v: &mut [u8], start: usize, n: u16) -> usize {
 
 pub fn write_u32(v: &mut [u8], start: usize, n: u32) -> usize {
     let bytes = u32::to_be_bytes(n);
-
-    for i in 0..4 {
-        v[start + i] = bytes[i];
-    }
+    v[start..start + bytes.len()].copy_from_slice(&bytes);
 
     start + size_of::<u32>()
 }
 
 pub fn write_u64(v: &mut [u8], start: usize, n: u64) -> usize {
     let bytes = u64::to_be_bytes(n);
-
-    for i in 0..8 {
-        v[start + i] = bytes[i];
-    }
+    v[start..start + bytes.len()].copy_from_slice(&bytes);
 
     start + size_of::<u64>()
 }
@@ -1,10 +1,14 @@
-use std::{cmp::Ordering, time::SystemTime};
+use std::{
+    cmp::{min, Ordering},
+    collections::HashMap,
+    time::SystemTime,
+};
 
 use priority_queue::PriorityQueue;
 
 use crate::page::PageId;
 
-#[derive(Eq, PartialEq, Ord)]
+#[derive(Eq, PartialEq)]
 pub struct InverseSystemTime {
     time: SystemTime,
 }
@@ -17,16 +21,15 @@ impl InverseSystemTime {
     }
 }
 
+impl Ord for InverseSystemTime {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.time.cmp(&self.time)
+    }
+}
+
 impl PartialOrd for InverseSystemTime {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        match self.time.partial_cmp(&other.time) {
-            Some(ord) => match ord {
-                Ordering::Less => Some(Ordering::Greater),
-                Ordering::Equal => Some(Ordering::Equal),
-                Ordering::Greater => Some(Ordering::Less),
-            },
-            None => None,
-        }
+        Some(self.cmp(other))
     }
 }
 
@@ -53,11 +56,53 @@ fn test_equality() {
     assert!(t1 == t2);
 }
 
+#[test]
+fn test_queue_peeks_least_recently_used() {
+    let t1 = InverseSystemTime {
+        time: SystemTime::now(),
+    };
+    let t2 = InverseSystemTime {
+        time: SystemTime::UNIX_EPOCH,
+    };
+    assert_eq!(Ordering::Less, t1.cmp(&t2));
+
+    let mut last_used = PriorityQueue::new();
+    last_used.push(1, t2);
+    last_used.push(2, t1);
+
+    assert_eq!(Some(&1), last_used.peek().map(|(page_id, _)| page_id));
+}
+
+/// Decides which resident pages to evict first.
+pub trait ReplacementPolicy {
+    fn insert(&mut self, page_id: PageId);
+
+    // Tracks a page as the first to evict, e.g. a prefetched page nobody has asked for yet
+    fn insert_cold(&mut self, page_id: PageId);
+
+    fn touch(&mut self, page_id: PageId);
+    fn remove(&mut self, page_id: PageId);
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Up to k pages in the order they should be evicted
+    fn evict_candidates(&self, k: usize) -> Vec<PageId>;
+}
+
+// Least recently used
 pub struct UsageTracker {
-    // Make this a trait
     pub last_used: PriorityQueue<PageId, InverseSystemTime>,
 }
 
+impl Default for UsageTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl UsageTracker {
     pub fn new() -> Self {
         UsageTracker {
@@ -65,12 +110,180 @@ impl UsageTracker {
         }
     }
 
-    pub fn insert(&mut self, page_id: PageId) {
+    pub fn with_capacity(capacity: usize) -> Self {
+        UsageTracker {
+            last_used: PriorityQueue::with_capacity(capacity),
+        }
+    }
+}
+
+impl ReplacementPolicy for UsageTracker {
+    fn insert(&mut self, page_id: PageId) {
         self.last_used.push(page_id, InverseSystemTime::now());
     }
 
-    pub fn touch(&mut self, page_id: PageId) {
+    fn insert_cold(&mut self, page_id: PageId) {
+        self.last_used.push(
+            page_id,
+            InverseSystemTime {
+                time: SystemTime::UNIX_EPOCH,
+            },
+        );
+    }
+
+    fn touch(&mut self, page_id: PageId) {
         self.last_used
             .change_priority(&page_id, InverseSystemTime::now());
     }
+
+    fn remove(&mut self, page_id: PageId) {
+        self.last_used.remove(&page_id);
+    }
+
+    fn len(&self) -> usize {
+        self.last_used.len()
+    }
+
+    // The k least recently used pages, oldest first
+    fn evict_candidates(&self, k: usize) -> Vec<PageId> {
+        let mut entries: Vec<_> = self.last_used.iter().collect();
+        let k = min(k, entries.len());
+
+        // Older pages have a higher priority
+        let oldest_first = |(_, a): &(&PageId, &InverseSystemTime),
+                            (_, b): &(&PageId, &InverseSystemTime)| {
+            b.cmp(a)
+        };
+
+        if k < entries.len() {
+            entries.select_nth_unstable_by(k, oldest_first);
+            entries.truncate(k);
+        }
+        entries.sort_by(oldest_first);
+
+        entries.into_iter().map(|(page_id, _)| *page_id).collect()
+    }
+}
+
+#[test]
+fn test_evict_candidates_oldest_first() {
+    let mut tracker = UsageTracker::with_capacity(5);
+
+    let at = |secs| InverseSystemTime {
+        time: SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs),
+    };
+
+    tracker.last_used.push(10, at(50));
+    tracker.last_used.push(11, at(10));
+    tracker.last_used.push(12, at(40));
+    tracker.last_used.push(13, at(20));
+    tracker.last_used.push(14, at(30));
+
+    assert_eq!(vec![11, 13, 14], tracker.evict_candidates(3));
+    assert_eq!(vec![11, 13, 14, 12, 10], tracker.evict_candidates(10));
+    assert!(tracker.evict_candidates(0).is_empty());
+}
+
+/// Least frequently used. Every `aging_interval` accesses all counts are
+/// halved, so pages that were hot a long time ago eventually become evictable.
+pub struct LfuPolicy {
+    counts: HashMap<PageId, (u64, u64)>, // (accesses, tick of last access)
+    tick: u64,
+    aging_interval: u64,
+}
+
+impl LfuPolicy {
+    pub fn new(aging_interval: u64) -> Self {
+        assert!(aging_interval > 0, "Aging interval must be positive");
+
+        LfuPolicy {
+            counts: HashMap::new(),
+            tick: 0,
+            aging_interval,
+        }
+    }
+
+    fn access(&mut self, page_id: PageId, count: u64) {
+        self.tick += 1;
+        self.counts.insert(page_id, (count, self.tick));
+
+        if self.tick.is_multiple_of(self.aging_interval) {
+            for (count, _) in self.counts.values_mut() {
+                *count /= 2;
+            }
+        }
+    }
+}
+
+impl ReplacementPolicy for LfuPolicy {
+    fn insert(&mut self, page_id: PageId) {
+        self.access(page_id, 1);
+    }
+
+    fn insert_cold(&mut self, page_id: PageId) {
+        self.counts.insert(page_id, (0, 0));
+    }
+
+    fn touch(&mut self, page_id: PageId) {
+        if let Some(&(count, _)) = self.counts.get(&page_id) {
+            self.access(page_id, count + 1);
+        }
+    }
+
+    fn remove(&mut self, page_id: PageId) {
+        self.counts.remove(&page_id);
+    }
+
+    fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    // Fewest accesses first, ties broken by least recent access
+    fn evict_candidates(&self, k: usize) -> Vec<PageId> {
+        let mut entries: Vec<_> = self.counts.iter().collect();
+        entries.sort_by_key(|(_, usage)| **usage);
+
+        entries
+            .into_iter()
+            .take(k)
+            .map(|(page_id, _)| *page_id)
+            .collect()
+    }
+}
+
+#[test]
+fn test_lfu_evicts_least_frequent() {
+    let mut policy = LfuPolicy::new(1000);
+
+    policy.insert(1);
+    policy.insert(2);
+    for _ in 0..10 {
+        policy.touch(1);
+    }
+    policy.insert(3);
+    policy.touch(3);
+
+    // Page 2 was used once, page 3 twice, page 1 eleven times
+    assert_eq!(vec![2, 3, 1], policy.evict_candidates(3));
+
+    policy.remove(2);
+    assert_eq!(vec![3], policy.evict_candidates(1));
+}
+
+#[test]
+fn test_lfu_aging_decays_old_pages() {
+    let mut policy = LfuPolicy::new(4);
+
+    policy.insert(1);
+    for _ in 0..7 {
+        policy.touch(1);
+    }
+    assert_eq!(3, policy.counts[&1].0);
+
+    // Page 2 now keeps being used while page 1 goes cold
+    policy.insert(2);
+    for _ in 0..11 {
+        policy.touch(2);
+    }
+    assert_eq!(vec![1, 2], policy.evict_candidates(2));
 }